@@ -0,0 +1,283 @@
+//! Programmatic construction of bytecode modules
+//!
+//! [`ModuleBuilder`] allows creating a [`Module`] without going through the
+//! source compiler, e.g. for tooling and tests. Constants are added one by one
+//! and referred to by the indices the `add_*` methods return.
+
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+use super::instruction::Instruction;
+use super::writer::{RawConstant, RawModule, RawStructType};
+use super::{BytecodeConfig, Constant, Function, InstructionSequence, Module, Number, StructType};
+
+#[derive(Debug, Clone)]
+enum BuilderConstant {
+    Number(Number),
+    String(String),
+    Function(usize, Vec<u8>),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+enum BuilderStructType {
+    Empty,
+    Positional(usize),
+    Named(Vec<usize>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModuleBuilder {
+    constants: Vec<BuilderConstant>,
+    globals: BTreeMap<usize, usize>,
+    struct_types: BTreeMap<usize, BuilderStructType>,
+    methods: BTreeMap<(usize, usize), usize>,
+}
+
+impl ModuleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a number constant and returns its index.
+    pub fn add_number(&mut self, value: impl Into<Number>) -> usize {
+        self.add_constant(BuilderConstant::Number(value.into()))
+    }
+
+    /// Adds a string constant and returns its index. Strings are
+    /// deduplicated, so adding the same string twice returns the same index.
+    pub fn add_string(&mut self, value: &str) -> usize {
+        let existing = self.constants.iter().position(
+            |constant| matches!(constant, BuilderConstant::String(string) if string == value),
+        );
+        existing.unwrap_or_else(|| self.add_constant(BuilderConstant::String(value.to_string())))
+    }
+
+    /// Adds a byte string constant and returns its index. Like strings, byte
+    /// strings are deduplicated.
+    pub fn add_bytes(&mut self, value: &[u8]) -> usize {
+        let existing = self.constants.iter().position(
+            |constant| matches!(constant, BuilderConstant::Bytes(bytes) if bytes == value),
+        );
+        existing.unwrap_or_else(|| self.add_constant(BuilderConstant::Bytes(value.to_vec())))
+    }
+
+    /// Adds a function constant with the given body and returns its index.
+    /// Operands of the instructions are encoded as single bytes, so constant
+    /// indices used in the body must be below 256; otherwise, this fails.
+    pub fn add_function<I>(&mut self, arity: usize, body: I) -> crate::Result<usize>
+    where
+        I: IntoIterator<Item = Instruction>,
+    {
+        let mut encoded = Vec::new();
+        for ins in body {
+            ins.encode(&mut encoded)?;
+        }
+        Ok(self.add_constant(BuilderConstant::Function(arity, encoded)))
+    }
+
+    /// Makes the constant at index `value` available as the global `name`.
+    /// Returns the index of the name's string constant.
+    pub fn add_global(&mut self, name: &str, value: usize) -> usize {
+        let name = self.add_string(name);
+        self.globals.insert(name, value);
+        name
+    }
+
+    /// Declares a struct type. Returns the index of the name's string
+    /// constant.
+    pub fn add_struct(&mut self, name: &str, struct_type: StructType<'_>) -> usize {
+        let struct_type = match struct_type {
+            StructType::Empty => BuilderStructType::Empty,
+            StructType::Positional(count) => BuilderStructType::Positional(count),
+            StructType::Named(fields) => BuilderStructType::Named(
+                fields
+                    .into_iter()
+                    .map(|field| self.add_string(field))
+                    .collect(),
+            ),
+        };
+        let name = self.add_string(name);
+        self.struct_types.insert(name, struct_type);
+        name
+    }
+
+    /// Makes the function constant at index `function` callable as a method
+    /// `name` on instances of `struct_type`.
+    pub fn add_method(&mut self, struct_type: &str, name: &str, function: usize) {
+        let struct_type = self.add_string(struct_type);
+        let name = self.add_string(name);
+        self.methods.insert((struct_type, name), function);
+    }
+
+    fn add_constant(&mut self, constant: BuilderConstant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    fn string(&self, index: usize) -> &str {
+        match &self.constants[index] {
+            BuilderConstant::String(value) => value,
+            // names are only ever added via `add_string`
+            _ => unreachable!("constant #{index} is not a string"),
+        }
+    }
+
+    /// Creates a module referencing the constants of this builder.
+    pub fn build(&self) -> Module<'_> {
+        let constants = self
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                BuilderConstant::Number(value) => Constant::Number(value.clone()),
+                BuilderConstant::String(value) => Constant::String(value),
+                BuilderConstant::Function(arity, body) => {
+                    Constant::Function(Function::new(*arity, InstructionSequence::new(body)))
+                }
+                BuilderConstant::Bytes(value) => Constant::Bytes(value),
+            })
+            .collect();
+        let globals = self
+            .globals
+            .iter()
+            .map(|(&name, &value)| (self.string(name), value))
+            .collect();
+        let struct_types = self
+            .struct_types
+            .iter()
+            .map(|(&name, struct_type)| {
+                let struct_type = match struct_type {
+                    BuilderStructType::Empty => StructType::Empty,
+                    BuilderStructType::Positional(count) => StructType::Positional(*count),
+                    BuilderStructType::Named(fields) => {
+                        StructType::Named(fields.iter().map(|&field| self.string(field)).collect())
+                    }
+                };
+                (self.string(name), struct_type)
+            })
+            .collect();
+        let methods = self
+            .methods
+            .iter()
+            .map(|(&(struct_type, name), &value)| {
+                ((self.string(struct_type), self.string(name)), value)
+            })
+            .collect();
+        Module::new(constants, globals, struct_types, methods)
+    }
+
+    /// Writes the module in the sprachli bytecode format, as understood by
+    /// [`parse_bytecode`](crate::parse_bytecode).
+    pub fn write_bytecode<W: Write>(&self, w: &mut W) -> Result<()> {
+        self.write_bytecode_with_config(w, &BytecodeConfig::default())
+    }
+
+    /// Writes the module like [`write_bytecode`](Self::write_bytecode), but
+    /// with the given config's magic string and version.
+    pub fn write_bytecode_with_config<W: Write>(
+        &self,
+        w: &mut W,
+        config: &BytecodeConfig,
+    ) -> Result<()> {
+        let constants = self
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                BuilderConstant::Number(value) => RawConstant::Number(value),
+                BuilderConstant::String(value) => RawConstant::String(value),
+                BuilderConstant::Function(arity, body) => RawConstant::Function(*arity, body),
+                BuilderConstant::Bytes(value) => RawConstant::Bytes(value),
+            })
+            .collect();
+        let struct_types = self
+            .struct_types
+            .iter()
+            .map(|(&name, struct_type)| {
+                let struct_type = match struct_type {
+                    BuilderStructType::Empty => RawStructType::Empty,
+                    BuilderStructType::Positional(count) => RawStructType::Positional(*count),
+                    BuilderStructType::Named(fields) => RawStructType::Named(fields),
+                };
+                (name, struct_type)
+            })
+            .collect();
+        let module = RawModule {
+            constants,
+            globals: self.globals.clone(),
+            struct_types,
+            methods: self.methods.clone(),
+        };
+        module.write(w, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{InlineConstant, Instruction};
+    use crate::parse_bytecode;
+
+    #[test]
+    fn test_round_trip() {
+        let mut builder = ModuleBuilder::new();
+        let unit = builder
+            .add_function(0, [Instruction::InlineConstant(InlineConstant::Unit)])
+            .unwrap();
+        builder.add_global("main", unit);
+        builder.add_struct("Point", StructType::Named(vec!["x", "y"]));
+        builder.add_method("Point", "main", unit);
+        let answer = builder.add_number(42);
+        let bytes = builder.add_bytes(b"\x00\xff");
+        assert_eq!(builder.add_string("main"), builder.add_string("main"));
+        assert_eq!(builder.add_bytes(b"\x00\xff"), bytes);
+
+        let module = builder.build();
+        let mut bytecode = Vec::new();
+        builder.write_bytecode(&mut bytecode).unwrap();
+        let parsed = parse_bytecode(&bytecode).unwrap();
+
+        assert_eq!(parsed, module);
+        assert!(matches!(parsed.constant(answer), Some(Constant::Number(_))));
+        assert_eq!(parsed.constant(bytes), Some(&Constant::Bytes(b"\x00\xff")));
+        assert!(parsed.method("Point", "main").is_some());
+        assert_eq!(
+            parsed.struct_type("Point"),
+            Some(&StructType::Named(vec!["x", "y"])),
+        );
+    }
+
+    #[test]
+    fn test_equality() {
+        let build = |number: &str, body: Vec<Instruction>| {
+            let mut builder = ModuleBuilder::new();
+            builder.add_number(number.parse::<Number>().unwrap());
+            let main = builder.add_function(0, body).unwrap();
+            builder.add_global("main", main);
+            builder
+        };
+
+        let unit = || vec![Instruction::InlineConstant(InlineConstant::Unit)];
+        let a = build("1", unit());
+        // numbers are compared by value, regardless of scale
+        let b = build("1.00", unit());
+        let c = build("2", unit());
+        let d = build(
+            "1",
+            vec![Instruction::InlineConstant(InlineConstant::Bool(true))],
+        );
+
+        assert_eq!(a.build(), b.build());
+        assert_ne!(a.build(), c.build());
+        assert_ne!(a.build(), d.build());
+    }
+
+    #[test]
+    fn test_operand_too_large() {
+        let mut builder = ModuleBuilder::new();
+        let result = builder.add_function(0, [Instruction::Constant(300), Instruction::Return]);
+        assert!(matches!(
+            result,
+            Err(crate::Error::OperandTooLarge(Instruction::Constant(300)))
+        ));
+    }
+}
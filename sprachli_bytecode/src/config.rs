@@ -0,0 +1,41 @@
+//! The identity of the bytecode format
+//!
+//! Every bytecode file starts with a magic string and a format version. Forks
+//! of sprachli can use their own [`BytecodeConfig`], so that their files and
+//! stock `.sprb` files are not mistaken for each other.
+
+use std::io::{Result, Write};
+
+/// The magic string at the start of stock sprachli bytecode files.
+pub const MAGIC: &[u8] = b"sprachli";
+
+/// The version of the stock sprachli bytecode format. It must be increased
+/// whenever the encoding changes, e.g. when opcodes are renumbered.
+pub const VERSION: u16 = 1;
+
+/// The header that bytecode is written with and expected to start with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BytecodeConfig {
+    pub magic: &'static [u8],
+    pub version: u16,
+}
+
+impl BytecodeConfig {
+    pub const fn new(magic: &'static [u8], version: u16) -> Self {
+        Self { magic, version }
+    }
+
+    /// Writes the header, i.e. the magic string followed by the big-endian
+    /// version.
+    pub fn write_header<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_all(self.magic)?;
+        w.write_all(&self.version.to_be_bytes())?;
+        Ok(())
+    }
+}
+
+impl Default for BytecodeConfig {
+    fn default() -> Self {
+        Self::new(MAGIC, VERSION)
+    }
+}
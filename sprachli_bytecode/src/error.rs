@@ -2,12 +2,14 @@ use std::fmt;
 
 use nom::error::ParseError;
 
-use super::instruction::Opcode;
+use super::instruction::{Instruction, Opcode};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("ParseError: {0}")]
     ParseError(String),
+    #[error("Unsupported bytecode version: {0}")]
+    UnsupportedVersion(u16),
     #[error("Invalid constant pool entry: unknown kind")]
     InvalidConstantKind,
     #[error("Invalid constant pool entry: invalid utf8 string")]
@@ -24,8 +26,14 @@ pub enum Error {
     InvalidConstantRef(usize, usize),
     #[error("Constant #{0} was not a {1}")]
     InvalidConstantRefType(usize, &'static str),
+    #[error("Jump at offset {0} doesn't target an instruction")]
+    InvalidJumpTarget(usize),
+    #[error("{1:?} at offset {0} refers to a local beyond the frame of at most {2} values")]
+    InvalidLocalRef(usize, Instruction, usize),
     #[error("Invalid struct: unknown kind")]
     InvalidStructTypeKind,
+    #[error("Operand of {0:?} doesn't fit into a byte")]
+    OperandTooLarge(Instruction),
 }
 
 impl<I: fmt::Debug> From<nom::error::Error<I>> for Error {
@@ -4,15 +4,19 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use sprachli_fmt::{FormatterExt, ModuleFormat};
 use sprachli_parser::ast;
 
-use super::Module;
+use super::{Error, Module, Result};
 
 pub use ast::{BinaryOperator, UnaryOperator};
 
+/// The opcodes of the bytecode format. The value `0` is reserved for
+/// intentionally invalid opcodes, so that zeroed memory is never decoded as a
+/// valid instruction. New opcodes are appended at the end, so that existing
+/// ones keep their encoding.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Opcode {
     // constants
-    Constant,
+    Constant = 1,
     Unit,
     True,
     False,
@@ -42,12 +46,51 @@ pub enum Opcode {
     JumpBackward,
     JumpForwardIf,
     JumpBackwardIf,
+
+    // functions
+    LoadFunction,
+
+    // methods
+    LoadMethod,
+
+    // tuples
+    MakeTuple,
+    Unpack,
+
+    // lists
+    MakeList,
+    IndexGet,
+    IndexSet,
+
+    // output
+    Print,
+    PrintLine,
+
+    // jump tables
+    Switch,
+
+    // maps
+    MakeMap,
+
+    // strings
+    Stringify,
+
+    // no-op
+    Nop,
+
+    // resources
+    Drop,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
 pub enum Instruction {
     // constants
     Constant(usize),
+    /// Loads the global function named by a string constant, e.g. the callee
+    /// of a call to a top-level function. Unlike with `LoadNamed`, the global
+    /// is statically known to be a function of the module, so that the VM can
+    /// resolve it once instead of looking up the name on every load.
+    LoadFunction(usize),
     InlineConstant(InlineConstant),
 
     // calculations
@@ -59,22 +102,84 @@ pub enum Instruction {
     StoreLocal(usize),
     LoadNamed(usize),
     StoreNamed(usize),
+    /// Looks up the named method on the type of the struct on top of the
+    /// stack, and pushes the method under that struct, so that the struct
+    /// becomes the method's first argument.
+    LoadMethod(usize),
+    /// Replaces the struct on top of the stack with its field at the given
+    /// position.
     LoadPositionalField(usize),
     StorePositionalField(usize),
+    /// Replaces the struct on top of the stack with its field named by the
+    /// given constant.
     LoadNamedField(usize),
     StoreNamedField(usize),
 
+    // tuples
+    /// Replaces the given number of values on top of the stack with a tuple
+    /// containing them.
+    MakeTuple(usize),
+    /// Replaces the tuple on top of the stack with its elements; the tuple
+    /// must have exactly the given number of elements.
+    Unpack(usize),
+
+    // lists
+    /// Replaces the given number of values on top of the stack with a list
+    /// containing them.
+    MakeList(usize),
+    /// Replaces a list and an index on top of the stack with the list's
+    /// element at that index.
+    IndexGet,
+    /// Replaces a list, an index and a value on top of the stack with a copy
+    /// of the list, in which the element at that index is the value.
+    IndexSet,
+
+    // maps
+    /// Replaces the given number of key/value pairs on top of the stack with
+    /// a map containing them. Keys must be strings; later pairs override
+    /// earlier ones with the same key.
+    MakeMap(usize),
+
+    // strings
+    /// Replaces the value on top of the stack with its string
+    /// representation, as it would be printed.
+    Stringify,
+
+    // output
+    /// Replaces the value on top of the stack with unit, after writing it to
+    /// the VM's output.
+    Print,
+    /// Like `Print`, but also writes a line break.
+    PrintLine,
+
     // stack management
     Pop,
     PopScope(usize),
 
     // functions
     Call(usize),
+    /// Ends the current function with the value on top of the stack. All of
+    /// the function's locals are discarded, including those of nested
+    /// scopes, so no `PopScope` is needed before it.
     Return,
 
     // jumps
     Jump(Offset),
     JumpIf(Offset),
+    /// Pops an integer and executes the jump at that position in the table of
+    /// the given number of `Jump`s that follows. Other values skip the table.
+    Switch(usize),
+
+    // no-op
+    /// Does nothing. Since it is one byte long, an instruction can be blanked
+    /// out by replacing it with `Nop`s (see [`Instruction::blanked`]) without
+    /// changing any jump offsets.
+    Nop,
+
+    // resources
+    /// Pops the value on top of the stack, running its destructor if it is
+    /// droppable and this was its last reference.
+    Drop,
 }
 
 impl Instruction {
@@ -83,6 +188,7 @@ impl Instruction {
 
         let effect = match self {
             Constant(_) => 1,
+            LoadFunction(_) => 1,
             InlineConstant(_) => 1,
             Unary(_) => 0,
             Binary(_) => -1,
@@ -90,10 +196,19 @@ impl Instruction {
             StoreLocal(_) => -1,
             LoadNamed(_) => 1,
             StoreNamed(_) => -1,
-            LoadPositionalField(_) => 1,
+            LoadMethod(_) => 1,
+            LoadPositionalField(_) => 0,
             StorePositionalField(_) => -1,
-            LoadNamedField(_) => 1,
+            LoadNamedField(_) => 0,
             StoreNamedField(_) => -1,
+            MakeTuple(len) => 1 - isize::try_from(len).expect("illegal tuple length"),
+            Unpack(len) => isize::try_from(len).expect("illegal tuple length") - 1,
+            MakeList(len) => 1 - isize::try_from(len).expect("illegal list length"),
+            IndexGet => -1,
+            IndexSet => -2,
+            MakeMap(len) => 1 - 2 * isize::try_from(len).expect("illegal map length"),
+            Stringify => 0,
+            Print | PrintLine => 0,
             Pop => -1,
             PopScope(_depth) => return None,
             Call(arity) => -isize::try_from(arity).expect("illegal arity"),
@@ -101,6 +216,9 @@ impl Instruction {
             Return => -1,
             Jump(_) => 0,
             JumpIf(_) => -1,
+            Switch(_) => -1,
+            Nop => 0,
+            Drop => -1,
         };
 
         Some(effect)
@@ -111,6 +229,7 @@ impl Instruction {
 
         match self {
             Constant(_) => 2,
+            LoadFunction(_) => 2,
             InlineConstant(_) => 1,
             Unary(_) => 2,
             Binary(_) => 2,
@@ -118,19 +237,131 @@ impl Instruction {
             StoreLocal(_) => 2,
             LoadNamed(_) => 2,
             StoreNamed(_) => 2,
+            LoadMethod(_) => 2,
             LoadPositionalField(_) => 2,
             StorePositionalField(_) => 2,
             LoadNamedField(_) => 2,
             StoreNamedField(_) => 2,
+            MakeTuple(_) => 2,
+            Unpack(_) => 2,
+            MakeList(_) => 2,
+            IndexGet => 1,
+            IndexSet => 1,
+            MakeMap(_) => 2,
+            Stringify => 1,
+            Print | PrintLine => 1,
             Pop => 1,
             PopScope(_) => 2,
             Call(_) => 2,
             Return => 1,
             Jump(_) => 2,
             JumpIf(_) => 2,
+            Switch(_) => 2,
+            Nop => 1,
+            Drop => 1,
         }
     }
 
+    /// The `Nop`s that can replace this instruction, keeping the offsets of
+    /// all following instructions the same.
+    pub fn blanked(self) -> impl Iterator<Item = Instruction> {
+        std::iter::repeat_n(Instruction::Nop, self.encoded_len())
+    }
+
+    /// The opcode this instruction is encoded with. Instructions with an
+    /// inline constant or a jump direction map to one of several opcodes.
+    pub fn opcode(self) -> Opcode {
+        use InlineConstant as Const;
+        use Instruction as In;
+        use Offset::*;
+        use Opcode as Op;
+
+        match self {
+            In::Constant(_) => Op::Constant,
+            In::LoadFunction(_) => Op::LoadFunction,
+            In::InlineConstant(Const::Unit) => Op::Unit,
+            In::InlineConstant(Const::Bool(true)) => Op::True,
+            In::InlineConstant(Const::Bool(false)) => Op::False,
+            In::Unary(_) => Op::Unary,
+            In::Binary(_) => Op::Binary,
+            In::LoadLocal(_) => Op::LoadLocal,
+            In::StoreLocal(_) => Op::StoreLocal,
+            In::LoadNamed(_) => Op::LoadNamed,
+            In::StoreNamed(_) => Op::StoreNamed,
+            In::LoadMethod(_) => Op::LoadMethod,
+            In::LoadPositionalField(_) => Op::LoadPositionalField,
+            In::StorePositionalField(_) => Op::StorePositionalField,
+            In::LoadNamedField(_) => Op::LoadNamedField,
+            In::StoreNamedField(_) => Op::StoreNamedField,
+            In::MakeTuple(_) => Op::MakeTuple,
+            In::Unpack(_) => Op::Unpack,
+            In::MakeList(_) => Op::MakeList,
+            In::MakeMap(_) => Op::MakeMap,
+            In::Stringify => Op::Stringify,
+            In::IndexGet => Op::IndexGet,
+            In::IndexSet => Op::IndexSet,
+            In::Print => Op::Print,
+            In::PrintLine => Op::PrintLine,
+            In::Pop => Op::Pop,
+            In::PopScope(_) => Op::PopScope,
+            In::Call(_) => Op::Call,
+            In::Return => Op::Return,
+            In::Jump(Forward(_)) => Op::JumpForward,
+            In::Jump(Backward(_)) => Op::JumpBackward,
+            In::JumpIf(Forward(_)) => Op::JumpForwardIf,
+            In::JumpIf(Backward(_)) => Op::JumpBackwardIf,
+            In::Switch(_) => Op::Switch,
+            In::Nop => Op::Nop,
+            In::Drop => Op::Drop,
+        }
+    }
+
+    /// The numeric operand of this instruction: a constant or local index,
+    /// field position, length, depth, arity, the magnitude of a jump offset,
+    /// or the size of a jump table. Instructions without an operand, or whose
+    /// operand is an operator, return `None`.
+    pub fn operand(self) -> Option<usize> {
+        use Instruction::*;
+        use Offset::*;
+
+        match self {
+            Constant(index) | LoadFunction(index) => Some(index),
+            LoadLocal(index) | StoreLocal(index) => Some(index),
+            LoadNamed(index) | StoreNamed(index) | LoadMethod(index) => Some(index),
+            LoadPositionalField(index) | StorePositionalField(index) => Some(index),
+            LoadNamedField(index) | StoreNamedField(index) => Some(index),
+            MakeTuple(len) | Unpack(len) | MakeList(len) | MakeMap(len) => Some(len),
+            PopScope(depth) => Some(depth),
+            Call(arity) => Some(arity),
+            Jump(Forward(offset) | Backward(offset)) => Some(offset),
+            JumpIf(Forward(offset) | Backward(offset)) => Some(offset),
+            Switch(len) => Some(len),
+            InlineConstant(_) | Unary(_) | Binary(_) | IndexGet | IndexSet | Stringify => None,
+            Print | PrintLine | Pop | Return | Nop | Drop => None,
+        }
+    }
+
+    /// Appends this instruction's binary encoding to `body`: the
+    /// [`opcode`](Self::opcode), followed by the operator or the
+    /// [`operand`](Self::operand), if any. Operands are encoded as single
+    /// bytes; larger operands fail instead of being truncated, and nothing is
+    /// appended in that case.
+    pub fn encode(self, body: &mut Vec<u8>) -> Result<()> {
+        use Instruction::*;
+
+        let operand = match self {
+            Unary(op) => Some(op.into()),
+            Binary(op) => Some(op.into()),
+            _ => self
+                .operand()
+                .map(|operand| u8::try_from(operand).map_err(|_| Error::OperandTooLarge(self)))
+                .transpose()?,
+        };
+        body.push(self.opcode().into());
+        body.extend(operand);
+        Ok(())
+    }
+
     pub fn fmt_with<M: ModuleFormat>(
         &self,
         f: &mut fmt::Formatter<'_>,
@@ -141,13 +372,22 @@ impl Instruction {
         match self {
             Constant(index) => {
                 if let Some(module) = module {
-                    write!(f, "CONST #{index:<8} -- ")?;
+                    fmt_constant_ref(f, module, "CONST", *index)?;
                     f.fmt_constant(module, *index)?;
                 } else {
                     write!(f, "CONST #{index}")?;
                 }
                 Ok(())
             }
+            LoadFunction(index) => {
+                if let Some(module) = module {
+                    fmt_constant_ref(f, module, "LOAD FN", *index)?;
+                    f.fmt_constant(module, *index)?;
+                } else {
+                    write!(f, "LOAD FN #{index}")?;
+                }
+                Ok(())
+            }
             InlineConstant(value) => write!(f, "CONST {value:?}"),
             Pop => write!(f, "POP"),
             Unary(op) => write!(f, "UNARY {op:?}"),
@@ -156,7 +396,7 @@ impl Instruction {
             StoreLocal(local) => write!(f, "STORE _{local}"),
             LoadNamed(index) => {
                 if let Some(module) = module {
-                    write!(f, "LOAD #{index:<9} -- ")?;
+                    fmt_constant_ref(f, module, "LOAD", *index)?;
                     f.fmt_constant_ident(module, *index)?;
                 } else {
                     write!(f, "LOAD #{index}")?;
@@ -165,18 +405,27 @@ impl Instruction {
             }
             StoreNamed(index) => {
                 if let Some(module) = module {
-                    write!(f, "STORE #{index:<8} -- ")?;
+                    fmt_constant_ref(f, module, "STORE", *index)?;
                     f.fmt_constant_ident(module, *index)?;
                 } else {
                     write!(f, "STORE #{index}")?;
                 }
                 Ok(())
             }
+            LoadMethod(index) => {
+                if let Some(module) = module {
+                    fmt_constant_ref(f, module, "LOAD METHOD", *index)?;
+                    f.fmt_constant_ident(module, *index)?;
+                } else {
+                    write!(f, "LOAD METHOD #{index}")?;
+                }
+                Ok(())
+            }
             LoadPositionalField(index) => write!(f, "LOAD FIELD _{index}"),
             StorePositionalField(index) => write!(f, "STORE FIELD _{index}"),
             LoadNamedField(index) => {
                 if let Some(module) = module {
-                    write!(f, "LOAD FIELD #{index:<3} -- ")?;
+                    fmt_constant_ref(f, module, "LOAD FIELD", *index)?;
                     f.fmt_constant_ident(module, *index)?;
                 } else {
                     write!(f, "LOAD FIELD #{index}")?;
@@ -185,22 +434,53 @@ impl Instruction {
             }
             StoreNamedField(index) => {
                 if let Some(module) = module {
-                    write!(f, "STORE FIELD #{index:<2} -- ")?;
+                    fmt_constant_ref(f, module, "STORE FIELD", *index)?;
                     f.fmt_constant_ident(module, *index)?;
                 } else {
                     write!(f, "STORE FIELD #{index}")?;
                 }
                 Ok(())
             }
+            MakeTuple(len) => write!(f, "MAKE TUPLE {len}"),
+            Unpack(len) => write!(f, "UNPACK {len}"),
+            MakeList(len) => write!(f, "MAKE LIST {len}"),
+            MakeMap(len) => write!(f, "MAKE MAP {len}"),
+            Stringify => write!(f, "STRINGIFY"),
+            IndexGet => write!(f, "INDEX GET"),
+            IndexSet => write!(f, "INDEX SET"),
+            Print => write!(f, "PRINT"),
+            PrintLine => write!(f, "PRINT LINE"),
             PopScope(depth) => write!(f, "POP SCOPE {depth}"),
             Call(arity) => write!(f, "CALL {arity}"),
             Return => write!(f, "RETURN"),
             Jump(offset) => write!(f, "JUMP {offset:?}"),
             JumpIf(offset) => write!(f, "JUMP_IF {offset:?}"),
+            Switch(len) => write!(f, "SWITCH {len}"),
+            Nop => write!(f, "NOP"),
+            Drop => write!(f, "DROP"),
         }
     }
 }
 
+/// The length of the longest mnemonic of an instruction referring to a
+/// constant, including the `#` of the index, i.e. `LOAD METHOD #`
+const MNEMONIC_WIDTH: usize = 13;
+
+/// Writes the mnemonic and index of an instruction referring to a constant,
+/// padded so that the following comments line up for all such instructions.
+/// The padding depends on the widest index in the module, with at least two
+/// digits reserved.
+fn fmt_constant_ref<M: ModuleFormat>(
+    f: &mut fmt::Formatter<'_>,
+    module: &M,
+    mnemonic: &str,
+    index: usize,
+) -> fmt::Result {
+    let width = MNEMONIC_WIDTH + module.index_width().max(2);
+    let operand = format!("{mnemonic} #{index}");
+    write!(f, "{operand:<width$} -- ")
+}
+
 impl fmt::Debug for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.fmt_with::<Module>(f, None)
@@ -229,3 +509,95 @@ impl fmt::Debug for Offset {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, InstructionSequence};
+
+    #[test]
+    fn test_zero_opcode_is_invalid() {
+        assert!(Opcode::try_from(0).is_err());
+
+        let body = InstructionSequence::new(&[0]);
+        let error = body.iter().next().unwrap().unwrap_err();
+        assert!(matches!(error, Error::InvalidOpcode(0)));
+    }
+
+    #[test]
+    fn test_iter_stops_after_error() {
+        // without stopping, the iterator would continue with `Constant(7)`
+        let body = InstructionSequence::new(&[0, 1, 7]);
+        let mut iter = body.iter();
+        assert!(matches!(iter.next(), Some(Err(Error::InvalidOpcode(0)))));
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+
+        let body = InstructionSequence::new(&[1]);
+        let mut iter = body.iter().with_offset();
+        assert!(matches!(
+            iter.next(),
+            Some((0, Err(Error::IncompleteInstruction(Opcode::Constant)))),
+        ));
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_first_opcode_is_one() {
+        assert_eq!(u8::from(Opcode::Constant), 1);
+
+        let body = InstructionSequence::new(&[1, 7]);
+        let ins = body.iter().next().unwrap().unwrap();
+        assert_eq!(ins, Instruction::Constant(7));
+    }
+
+    #[test]
+    fn test_opcode_and_operand() {
+        use Instruction as In;
+
+        let instructions = [
+            In::Constant(3),
+            In::InlineConstant(InlineConstant::Bool(false)),
+            In::Binary(BinaryOperator::Add),
+            In::Call(2),
+            In::JumpIf(Offset::Backward(5)),
+            In::Return,
+        ];
+        let mut encoded = Vec::new();
+        for ins in instructions {
+            ins.encode(&mut encoded).unwrap();
+        }
+
+        let body = InstructionSequence::new(&encoded);
+        let pairs: Vec<_> = body
+            .iter()
+            .map(|ins| ins.unwrap())
+            .map(|ins| (ins.opcode(), ins.operand()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                (Opcode::Constant, Some(3)),
+                (Opcode::False, None),
+                (Opcode::Binary, None),
+                (Opcode::Call, Some(2)),
+                (Opcode::JumpBackwardIf, Some(5)),
+                (Opcode::Return, None),
+            ],
+        );
+
+        // the opcode is the first byte of the encoding
+        for ins in instructions {
+            let mut encoded = Vec::new();
+            ins.encode(&mut encoded).unwrap();
+            assert_eq!(encoded[0], u8::from(ins.opcode()));
+        }
+
+        // operands that don't fit into a byte are not truncated
+        let mut encoded = Vec::new();
+        let error = In::Constant(256).encode(&mut encoded).unwrap_err();
+        assert!(matches!(error, Error::OperandTooLarge(In::Constant(256))));
+        assert!(encoded.is_empty());
+    }
+}
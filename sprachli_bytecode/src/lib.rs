@@ -7,11 +7,17 @@
 
 use std::fmt;
 
+pub mod builder;
+mod config;
 mod error;
 pub mod instruction;
+mod loaded;
 pub mod parser;
+pub mod writer;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter::FusedIterator;
+use std::ops::Range;
 
 use bigdecimal::BigDecimal;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
@@ -19,16 +25,20 @@ use sprachli_fmt::{FormatterExt, IteratorExt, ModuleFormat};
 
 use instruction::{InlineConstant, Instruction, Offset, Opcode};
 
+pub use builder::ModuleBuilder;
+pub use config::{BytecodeConfig, MAGIC, VERSION};
 pub use error::*;
-pub use parser::parse_bytecode;
+pub use loaded::LoadedModule;
+pub use parser::{parse_bytecode, parse_bytecode_with_config};
 
 pub type Number = BigDecimal;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Module<'b> {
     constants: Vec<Constant<'b>>,
     globals: BTreeMap<&'b str, usize>,
     struct_types: BTreeMap<&'b str, StructType<'b>>,
+    methods: BTreeMap<(&'b str, &'b str), usize>,
 }
 
 impl<'b> Module<'b> {
@@ -36,11 +46,13 @@ impl<'b> Module<'b> {
         constants: Vec<Constant<'b>>,
         globals: BTreeMap<&'b str, usize>,
         struct_types: BTreeMap<&'b str, StructType<'b>>,
+        methods: BTreeMap<(&'b str, &'b str), usize>,
     ) -> Self {
         Self {
             constants,
             globals,
             struct_types,
+            methods,
         }
     }
 
@@ -61,6 +73,24 @@ impl<'b> Module<'b> {
         self.constant(index)
     }
 
+    /// Returns the function bound to the named global, if it is one.
+    pub fn function(&self, name: &str) -> Option<&Function<'b>> {
+        match self.global(name)? {
+            Constant::Function(function) => Some(function),
+            _ => None,
+        }
+    }
+
+    /// Iterates over all globals that are functions, in name order.
+    pub fn functions(&self) -> impl Iterator<Item = (&'b str, &Function<'b>)> + '_ {
+        self.globals
+            .iter()
+            .filter_map(|(&name, &index)| match self.constant(index)? {
+                Constant::Function(function) => Some((name, function)),
+                _ => None,
+            })
+    }
+
     pub fn struct_types(&self) -> &BTreeMap<&'b str, StructType<'b>> {
         &self.struct_types
     }
@@ -68,6 +98,151 @@ impl<'b> Module<'b> {
     pub fn struct_type(&self, name: &str) -> Option<&StructType<'b>> {
         self.struct_types.get(name)
     }
+
+    /// Iterates over all struct types, in name order, without having to match
+    /// on [`StructType`].
+    pub fn struct_defs(&self) -> impl Iterator<Item = StructDef<'_, 'b>> + '_ {
+        self.struct_types
+            .iter()
+            .map(|(&name, struct_type)| StructDef { name, struct_type })
+    }
+
+    /// The methods defined in `impl` blocks, keyed by struct type and method
+    /// name. The values are indices of function constants.
+    pub fn methods(&self) -> &BTreeMap<(&'b str, &'b str), usize> {
+        &self.methods
+    }
+
+    pub fn method(&self, struct_type: &'b str, name: &'b str) -> Option<&Constant<'b>> {
+        let index = *self.methods.get(&(struct_type, name))?;
+        self.constant(index)
+    }
+
+    /// Returns the names that functions load or store by name, but that are
+    /// neither globals nor struct types of this module, in name order. These
+    /// would fail at runtime, unless the host provides them, e.g. as natives.
+    pub fn unresolved_globals(&self) -> Vec<&'b str> {
+        use Instruction::{LoadNamed, StoreNamed};
+
+        let mut names = BTreeSet::new();
+        for constant in &self.constants {
+            let Constant::Function(function) = constant else {
+                continue;
+            };
+            // an undecodable body is not this method's concern; stop at the error
+            for ins in function.body().iter().map_while(|ins| ins.ok()) {
+                if let LoadNamed(index) | StoreNamed(index) = ins {
+                    if let Some(&Constant::String(name)) = self.constant(index) {
+                        names.insert(name);
+                    }
+                }
+            }
+        }
+        names
+            .into_iter()
+            .filter(|name| {
+                !self.globals.contains_key(name) && !self.struct_types.contains_key(name)
+            })
+            .collect()
+    }
+
+    /// Checks that all function bodies can be decoded, that their
+    /// instructions refer to existing constants of the right kind and to
+    /// locals within the function's frame, and that jumps land on instructions
+    /// of the same body. Globals and methods must refer to existing constants
+    /// as well.
+    pub fn validate(&self) -> Result<()> {
+        for constant in &self.constants {
+            if let Constant::Function(function) = constant {
+                self.validate_function(function)?;
+            }
+        }
+        for &index in self.globals.values().chain(self.methods.values()) {
+            self.constant_ref(index)?;
+        }
+        Ok(())
+    }
+
+    fn constant_ref(&self, index: usize) -> Result<&Constant<'b>> {
+        self.constant(index)
+            .ok_or(Error::InvalidConstantRef(index, self.constants.len()))
+    }
+
+    fn validate_function(&self, function: &Function<'b>) -> Result<()> {
+        use Instruction as In;
+
+        let body = function.body();
+        // in stack-balanced code, as the compiler generates it, the frame can't
+        // grow beyond the parameters plus every value pushed anywhere in the
+        // body. Code whose loops keep pushing values can reach locals beyond
+        // that, and is rejected
+        let mut frame_bound = function.arity();
+        for ins in body.iter() {
+            let effect = ins?.stack_effect().unwrap_or(0);
+            frame_bound += usize::try_from(effect).unwrap_or(0);
+        }
+
+        let mut boundaries = BTreeSet::new();
+        let mut jumps = Vec::new();
+        for (offset, ins) in body.iter().with_offset() {
+            let ins = ins?;
+            boundaries.insert(offset);
+            match ins {
+                In::Constant(index) => {
+                    self.constant_ref(index)?;
+                }
+                In::LoadFunction(index) => match self.constant_ref(index)? {
+                    Constant::String(name) if self.function(name).is_some() => {}
+                    _ => Err(Error::InvalidConstantRefType(index, "function name"))?,
+                },
+                In::LoadNamed(index)
+                | In::StoreNamed(index)
+                | In::LoadMethod(index)
+                | In::LoadNamedField(index)
+                | In::StoreNamedField(index) => match self.constant_ref(index)? {
+                    Constant::String(_) => {}
+                    _ => Err(Error::InvalidConstantRefType(index, "string"))?,
+                },
+                In::LoadLocal(index) | In::StoreLocal(index) if index >= frame_bound => {
+                    Err(Error::InvalidLocalRef(offset, ins, frame_bound))?;
+                }
+                In::Jump(target) | In::JumpIf(target) => {
+                    // jump offsets are relative to the end of the jump instruction
+                    let end = offset + ins.encoded_len();
+                    let target = match target {
+                        Offset::Forward(distance) => end.checked_add(distance),
+                        Offset::Backward(distance) => end.checked_sub(distance),
+                    };
+                    jumps.push((offset, target));
+                }
+                In::Switch(len) => {
+                    // each case jumps into the table, or past it; every
+                    // entry must therefore start an instruction
+                    let end = offset + ins.encoded_len();
+                    let entry_len = In::Jump(Offset::Forward(0)).encoded_len();
+                    for case in 0..=len {
+                        jumps.push((offset, Some(end + case * entry_len)));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // jumping to the end of the body is the same as returning
+        boundaries.insert(body.get().len());
+        for (offset, target) in jumps {
+            if !target.is_some_and(|target| boundaries.contains(&target)) {
+                Err(Error::InvalidJumpTarget(offset))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a human readable listing of the module's constants, globals,
+    /// struct types and methods; the same as its alternate `Debug` output.
+    pub fn disassemble(&self) -> String {
+        format!("{self:#?}")
+    }
 }
 
 impl<'b> ModuleFormat for Module<'b> {
@@ -81,6 +256,10 @@ impl<'b> ModuleFormat for Module<'b> {
         };
         Some((constant, string))
     }
+
+    fn constant_count(&self) -> usize {
+        self.constants.len()
+    }
 }
 
 impl fmt::Debug for Module<'_> {
@@ -112,6 +291,13 @@ impl fmt::Debug for Module<'_> {
                 f.write_str("\n")?;
             }
             f.write_str("    },\n")?;
+            f.write_str("    methods: {\n")?;
+            for ((struct_type, name), index) in &self.methods {
+                write!(f, "        {struct_type}::{name}: {index} -- ")?;
+                f.fmt_constant(self, *index)?;
+                f.write_str("\n")?;
+            }
+            f.write_str("    },\n")?;
             f.write_str("}")?;
             Ok(())
         } else {
@@ -119,6 +305,7 @@ impl fmt::Debug for Module<'_> {
                 .field("constants", &self.constants)
                 .field("globals", &self.globals)
                 .field("struct_types", &self.struct_types)
+                .field("methods", &self.methods)
                 .finish()
         }
     }
@@ -130,13 +317,17 @@ pub enum ConstantKind {
     Number,
     String,
     Function,
+    Bytes,
 }
 
-#[derive(Clone)]
+/// Constants compare structurally; numbers compare by value, so `1.0` and `1`
+/// are equal, and functions compare by arity and instruction bytes.
+#[derive(Clone, PartialEq, Eq)]
 pub enum Constant<'b> {
     Number(Number),
     String(&'b str),
     Function(Function<'b>),
+    Bytes(&'b [u8]),
 }
 
 impl<'b> Constant<'b> {
@@ -152,6 +343,7 @@ impl<'b> Constant<'b> {
             Number(value) => fmt::Display::fmt(value, f),
             String(value) => value.fmt(f),
             Function(value) => value.fmt_with(f, module),
+            Bytes(value) => write!(f, "b\"{}\"", value.escape_ascii()),
         }
     }
 }
@@ -162,7 +354,7 @@ impl fmt::Debug for Constant<'_> {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Function<'b> {
     arity: usize,
     body: InstructionSequence<'b>,
@@ -177,10 +369,50 @@ impl<'b> Function<'b> {
         self.arity
     }
 
-    pub fn body(&self) -> &InstructionSequence {
+    pub fn body(&self) -> &InstructionSequence<'_> {
         &self.body
     }
 
+    /// The length of the function's body in bytes.
+    pub fn body_len(&self) -> usize {
+        self.body.get().len()
+    }
+
+    /// The number of instructions in the function's body. Fails if the body
+    /// can't be decoded.
+    pub fn instruction_count(&self) -> Result<usize> {
+        self.instructions()
+            .try_fold(0, |count, ins| ins.map(|_| count + 1))
+    }
+
+    /// Decodes the function's body instruction by instruction.
+    #[inline]
+    pub fn instructions(&self) -> InstructionIter<'_, '_> {
+        self.body.iter()
+    }
+
+    /// Returns a human readable listing of the function's instructions, with
+    /// constants resolved through the given module. This is the same text
+    /// the function has in the module's disassembly.
+    pub fn disassemble(&self, module: &Module<'_>) -> String {
+        struct Disassembly<'a, 'b, 'm> {
+            function: &'a Function<'b>,
+            module: &'a Module<'m>,
+        }
+
+        impl fmt::Debug for Disassembly<'_, '_, '_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.function.fmt_with(f, Some(self.module))
+            }
+        }
+
+        let disassembly = Disassembly {
+            function: self,
+            module,
+        };
+        format!("{disassembly:#?}")
+    }
+
     pub(crate) fn fmt_with<M: ModuleFormat>(
         &self,
         f: &mut fmt::Formatter<'_>,
@@ -226,6 +458,16 @@ pub enum StructType<'b> {
     Named(Vec<&'b str>),
 }
 
+impl StructType<'_> {
+    pub fn kind(&self) -> StructTypeKind {
+        match self {
+            StructType::Empty => StructTypeKind::Empty,
+            StructType::Positional(_) => StructTypeKind::Positional,
+            StructType::Named(_) => StructTypeKind::Named,
+        }
+    }
+}
+
 impl fmt::Debug for StructType<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use StructType::*;
@@ -262,7 +504,36 @@ impl fmt::Debug for StructType<'_> {
     }
 }
 
-#[derive(Clone)]
+/// A struct type together with its name, see [`Module::struct_defs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StructDef<'a, 'b> {
+    name: &'b str,
+    struct_type: &'a StructType<'b>,
+}
+
+impl<'a, 'b> StructDef<'a, 'b> {
+    pub fn name(&self) -> &'b str {
+        self.name
+    }
+
+    pub fn kind(&self) -> StructTypeKind {
+        self.struct_type.kind()
+    }
+
+    /// The names of the fields; empty for empty and positional structs.
+    pub fn field_names(&self) -> &'a [&'b str] {
+        match self.struct_type {
+            StructType::Named(fields) => fields,
+            _ => &[],
+        }
+    }
+
+    pub fn struct_type(&self) -> &'a StructType<'b> {
+        self.struct_type
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
 pub struct InstructionSequence<'b>(&'b [u8]);
 
 impl<'b> InstructionSequence<'b> {
@@ -274,10 +545,73 @@ impl<'b> InstructionSequence<'b> {
         self.0
     }
 
+    /// Returns the instructions in the given byte range, if that range starts
+    /// and ends on instruction boundaries. Jumps inside the returned sequence
+    /// are not adjusted, so jumps leaving the range will be invalid.
+    pub fn slice(&self, range: Range<usize>) -> Option<InstructionSequence<'b>> {
+        let instructions = self.0.get(range.clone())?;
+
+        let is_boundary = |target: usize| {
+            let mut iter = self.iter();
+            loop {
+                let offset = iter.offset();
+                if offset >= target {
+                    return offset == target;
+                }
+                if !matches!(iter.next(), Some(Ok(_))) {
+                    return false;
+                }
+            }
+        };
+
+        (is_boundary(range.start) && is_boundary(range.end)).then_some(Self(instructions))
+    }
+
     #[inline]
     pub fn iter(&self) -> InstructionIter<'_, '_> {
         InstructionIter::new(self)
     }
+
+    /// Compares the decoded instructions instead of the raw bytes. Anything
+    /// after a `Return` that no jump leads past is unreachable, e.g. padding,
+    /// and doesn't take part in the comparison. Sequences that can't be
+    /// decoded are only equal if their bytes are.
+    pub fn semantic_eq(&self, other: &InstructionSequence<'_>) -> bool {
+        match (
+            self.reachable_instructions(),
+            other.reachable_instructions(),
+        ) {
+            (Some(left), Some(right)) => left == right,
+            _ => self.0 == other.0,
+        }
+    }
+
+    fn reachable_instructions(&self) -> Option<Vec<Instruction>> {
+        use Instruction::{Jump, JumpIf, Return, Switch};
+
+        let mut instructions = Vec::new();
+        // the end of the code that forward jumps seen so far can reach
+        let mut reachable_end = 0;
+        let mut iter = self.iter();
+        while let Some(ins) = iter.next() {
+            let ins = ins.ok()?;
+            // jump offsets are relative to the end of the jump instruction
+            let end = iter.offset();
+            if let Jump(Offset::Forward(offset)) | JumpIf(Offset::Forward(offset)) = ins {
+                reachable_end = reachable_end.max(end + offset);
+            }
+            if let Switch(len) = ins {
+                // values outside the table skip its jumps
+                let table_len = len * Jump(Offset::Forward(0)).encoded_len();
+                reachable_end = reachable_end.max(end + table_len);
+            }
+            instructions.push(ins);
+            if ins == Return && end > reachable_end {
+                break;
+            }
+        }
+        Some(instructions)
+    }
 }
 
 impl<'a, 'b> IntoIterator for &'a InstructionSequence<'b>
@@ -339,6 +673,8 @@ where
     instructions: &'a InstructionSequence<'b>,
     offset: usize,
     iter: std::slice::Iter<'b, u8>,
+    /// Set after a decoding error; the iterator doesn't continue past it.
+    failed: bool,
 }
 
 impl<'a, 'b> InstructionIter<'a, 'b> {
@@ -347,6 +683,7 @@ impl<'a, 'b> InstructionIter<'a, 'b> {
             instructions,
             offset: 0,
             iter: instructions.get().iter(),
+            failed: false,
         }
     }
 
@@ -387,6 +724,7 @@ impl<'a, 'b> InstructionIter<'a, 'b> {
         Ok(f(parameter as usize))
     }
 
+    #[allow(clippy::result_unit_err)]
     pub fn jump(&mut self, offset: Offset) -> std::result::Result<(), ()> {
         use Offset::*;
 
@@ -398,12 +736,8 @@ impl<'a, 'b> InstructionIter<'a, 'b> {
 
         Ok(())
     }
-}
 
-impl Iterator for InstructionIter<'_, '_> {
-    type Item = Result<Instruction>;
-
-    fn next(&mut self) -> Option<Self::Item> {
+    fn decode(&mut self) -> Option<Result<Instruction>> {
         use InlineConstant as Inl;
         use Instruction as In;
         use Opcode as Op;
@@ -412,6 +746,7 @@ impl Iterator for InstructionIter<'_, '_> {
             opcode.and_then(|opcode| {
                 let ins = match opcode {
                     Op::Constant => self.instruction_u8(opcode, In::Constant)?,
+                    Op::LoadFunction => self.instruction_u8(opcode, In::LoadFunction)?,
                     Op::Unit => In::InlineConstant(Inl::Unit),
                     Op::True => In::InlineConstant(Inl::Bool(true)),
                     Op::False => In::InlineConstant(Inl::Bool(false)),
@@ -432,6 +767,7 @@ impl Iterator for InstructionIter<'_, '_> {
                     Op::LoadLocal => self.instruction_u8(opcode, In::LoadLocal)?,
                     Op::StoreLocal => self.instruction_u8(opcode, In::StoreLocal)?,
                     Op::LoadNamed => self.instruction_u8(opcode, In::LoadNamed)?,
+                    Op::LoadMethod => self.instruction_u8(opcode, In::LoadMethod)?,
                     Op::StoreNamed => self.instruction_u8(opcode, In::StoreNamed)?,
                     Op::LoadPositionalField => {
                         self.instruction_u8(opcode, In::LoadPositionalField)?
@@ -441,6 +777,13 @@ impl Iterator for InstructionIter<'_, '_> {
                     }
                     Op::LoadNamedField => self.instruction_u8(opcode, In::LoadNamedField)?,
                     Op::StoreNamedField => self.instruction_u8(opcode, In::StoreNamedField)?,
+                    Op::MakeTuple => self.instruction_u8(opcode, In::MakeTuple)?,
+                    Op::Unpack => self.instruction_u8(opcode, In::Unpack)?,
+                    Op::MakeList => self.instruction_u8(opcode, In::MakeList)?,
+                    Op::IndexGet => In::IndexGet,
+                    Op::IndexSet => In::IndexSet,
+                    Op::Print => In::Print,
+                    Op::PrintLine => In::PrintLine,
                     Op::Pop => In::Pop,
                     Op::PopScope => self.instruction_u8(opcode, In::PopScope)?,
                     Op::Call => self.instruction_u8(opcode, In::Call)?,
@@ -457,6 +800,11 @@ impl Iterator for InstructionIter<'_, '_> {
                     Op::JumpBackwardIf => {
                         self.instruction_u8(opcode, |off| In::JumpIf(Offset::Backward(off)))?
                     }
+                    Op::Switch => self.instruction_u8(opcode, In::Switch)?,
+                    Op::MakeMap => self.instruction_u8(opcode, In::MakeMap)?,
+                    Op::Stringify => In::Stringify,
+                    Op::Nop => In::Nop,
+                    Op::Drop => In::Drop,
                 };
 
                 Ok(ins)
@@ -465,6 +813,23 @@ impl Iterator for InstructionIter<'_, '_> {
     }
 }
 
+/// After the end of the sequence or a decoding error, the iterator only
+/// returns `None`.
+impl Iterator for InstructionIter<'_, '_> {
+    type Item = Result<Instruction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let ins = self.decode();
+        self.failed = matches!(ins, Some(Err(_)));
+        ins
+    }
+}
+
+impl FusedIterator for InstructionIter<'_, '_> {}
+
 #[derive(Debug, Clone)]
 pub struct OffsetInstructionIter<'a, 'b>(InstructionIter<'a, 'b>);
 
@@ -477,6 +842,7 @@ impl<'a, 'b> OffsetInstructionIter<'a, 'b> {
         self.0.offset()
     }
 
+    #[allow(clippy::result_unit_err)]
     pub fn jump(&mut self, offset: Offset) -> std::result::Result<(), ()> {
         self.0.jump(offset)
     }
@@ -491,3 +857,289 @@ impl Iterator for OffsetInstructionIter<'_, '_> {
         Some((offset, ins))
     }
 }
+
+impl FusedIterator for OffsetInstructionIter<'_, '_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_version() {
+        let mut builder = ModuleBuilder::new();
+        let unit = builder
+            .add_function(0, [Instruction::InlineConstant(InlineConstant::Unit)])
+            .unwrap();
+        builder.add_global("main", unit);
+
+        // version 0 used different opcodes, so its bytecode can't be read anymore
+        let mut bytecode = Vec::new();
+        let old = BytecodeConfig::new(MAGIC, 0);
+        builder
+            .write_bytecode_with_config(&mut bytecode, &old)
+            .unwrap();
+        assert!(matches!(
+            parse_bytecode(&bytecode),
+            Err(Error::UnsupportedVersion(0))
+        ));
+
+        let mut bytecode = Vec::new();
+        builder.write_bytecode(&mut bytecode).unwrap();
+        assert_eq!(parse_bytecode(&bytecode).unwrap(), builder.build());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut builder = ModuleBuilder::new();
+        let answer = builder.add_number(42);
+        let main = builder
+            .add_function(0, [Instruction::Constant(answer), Instruction::Return])
+            .unwrap();
+        builder.add_global("main", main);
+        let module = builder.build();
+
+        let disassembly = module.disassemble();
+        assert_eq!(disassembly, format!("{module:#?}"));
+
+        let Some(Constant::Function(function)) = module.global("main") else {
+            panic!("main is not a function");
+        };
+        let function = function.disassemble(&module);
+        assert!(function.contains("CONST #0"));
+        assert!(function.contains("-- 42"));
+        assert!(disassembly.contains(&function));
+    }
+
+    #[test]
+    fn test_function_size() {
+        use Instruction as In;
+
+        let mut builder = ModuleBuilder::new();
+        let answer = builder.add_number(42);
+        let body = [
+            In::Constant(answer),
+            In::LoadLocal(0),
+            In::Binary(instruction::BinaryOperator::Add),
+            In::InlineConstant(InlineConstant::Unit),
+            In::Pop,
+            In::Return,
+        ];
+        let main = builder.add_function(1, body).unwrap();
+        builder.add_global("main", main);
+        let module = builder.build();
+
+        let Some(Constant::Function(function)) = module.global("main") else {
+            panic!("main is not a function");
+        };
+        assert_eq!(function.arity(), 1);
+        assert_eq!(function.body_len(), 9);
+        assert_eq!(function.instruction_count().unwrap(), 6);
+
+        // a `CONST` missing its operand
+        let body = [Opcode::Constant.into()];
+        let truncated = Function::new(0, InstructionSequence::new(&body));
+        assert_eq!(truncated.body_len(), 1);
+        assert!(truncated.instruction_count().is_err());
+    }
+
+    #[test]
+    fn test_disassembly_alignment() {
+        use Instruction::*;
+
+        let mut builder = ModuleBuilder::new();
+        let names = (0..150)
+            .map(|i| builder.add_string(&format!("name{i}")))
+            .collect::<Vec<_>>();
+        // small and large indices, referenced by instructions of different lengths
+        let body = [names[3], names[120]].into_iter().flat_map(|index| {
+            [
+                Constant(index),
+                LoadNamed(index),
+                StoreNamed(index),
+                LoadMethod(index),
+                LoadNamedField(index),
+                StoreNamedField(index),
+            ]
+        });
+        let main = builder.add_function(0, body).unwrap();
+        builder.add_global("main", main);
+        let disassembly = builder.build().disassemble();
+
+        let columns = disassembly
+            .lines()
+            .filter(|line| line.contains(" #"))
+            .map(|line| line.find(" -- ").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(columns.len(), 12);
+        assert!(columns.iter().all(|&column| column == columns[0]));
+    }
+
+    #[test]
+    fn test_unresolved_globals() {
+        let mut builder = ModuleBuilder::new();
+        let foo = builder.add_string("foo");
+        let point = builder.add_string("Point");
+        let helper = builder.add_string("helper");
+        let unit = Instruction::InlineConstant(InlineConstant::Unit);
+        let helper_fn = builder.add_function(0, [unit]).unwrap();
+        builder.add_global("helper", helper_fn);
+        builder.add_struct("Point", StructType::Empty);
+        let main = builder
+            .add_function(
+                0,
+                [
+                    Instruction::LoadNamed(helper),
+                    Instruction::LoadNamed(point),
+                    Instruction::LoadNamed(foo),
+                    Instruction::Call(0),
+                    Instruction::LoadNamed(foo),
+                    Instruction::Return,
+                ],
+            )
+            .unwrap();
+        builder.add_global("main", main);
+        let module = builder.build();
+
+        assert_eq!(module.unresolved_globals(), vec!["foo"]);
+    }
+
+    #[test]
+    fn test_struct_defs() {
+        let mut builder = ModuleBuilder::new();
+        builder.add_struct("Unit", StructType::Empty);
+        builder.add_struct("Pair", StructType::Positional(2));
+        builder.add_struct("Point", StructType::Named(vec!["x", "y"]));
+        let module = builder.build();
+
+        let defs: Vec<_> = module
+            .struct_defs()
+            .map(|def| (def.name(), def.kind(), def.field_names()))
+            .collect();
+        let expected: [(_, _, &[&str]); 3] = [
+            ("Pair", StructTypeKind::Positional, &[]),
+            ("Point", StructTypeKind::Named, &["x", "y"]),
+            ("Unit", StructTypeKind::Empty, &[]),
+        ];
+        assert_eq!(defs, expected);
+
+        let pair = module.struct_defs().next().unwrap();
+        assert_eq!(pair.struct_type(), &StructType::Positional(2));
+    }
+
+    #[test]
+    fn test_validate() {
+        use Instruction::{Call, Jump, LoadFunction, LoadNamed, Return};
+
+        let build = |body: Vec<Instruction>| {
+            let mut builder = ModuleBuilder::new();
+            builder.add_number(42);
+            let main = builder.add_function(0, body).unwrap();
+            builder.add_global("main", main);
+            builder
+        };
+        let unit = Instruction::InlineConstant(InlineConstant::Unit);
+
+        // constants: 0 = 42, 1 = main's body, 2 = "main"
+        let valid = build(vec![
+            Jump(Offset::Forward(1)),
+            unit,
+            Instruction::Constant(0),
+            Return,
+        ]);
+        valid.build().validate().unwrap();
+        let recursive = build(vec![LoadFunction(2), Call(0), Return]);
+        recursive.build().validate().unwrap();
+
+        let invalid = [
+            (vec![Instruction::Constant(5)], "constant index"),
+            (vec![LoadFunction(0)], "function reference"),
+            (vec![LoadNamed(0)], "name reference"),
+            (
+                vec![Jump(Offset::Forward(1)), Instruction::Constant(0)],
+                "jump into an instruction",
+            ),
+            (vec![Jump(Offset::Backward(3))], "jump before the body"),
+        ];
+        for (body, description) in invalid {
+            let builder = build(body);
+            assert!(builder.build().validate().is_err(), "{description}");
+        }
+    }
+
+    #[test]
+    fn test_validate_locals() {
+        use Instruction::{Binary, LoadLocal, Return, StoreLocal};
+
+        let build = |body: Vec<Instruction>| {
+            let mut builder = ModuleBuilder::new();
+            let main = builder.add_function(2, body).unwrap();
+            builder.add_global("main", main);
+            builder
+        };
+        let add = Binary(instruction::BinaryOperator::Add);
+
+        let valid = build(vec![LoadLocal(0), LoadLocal(1), add, Return]);
+        valid.build().validate().unwrap();
+
+        let invalid = build(vec![LoadLocal(0), LoadLocal(200), add, Return]);
+        let error = invalid.build().validate().unwrap_err();
+        assert!(
+            matches!(error, Error::InvalidLocalRef(2, LoadLocal(200), 4)),
+            "{error}"
+        );
+        assert!(error.to_string().contains("LOAD _200"), "{error}");
+
+        let invalid = build(vec![LoadLocal(0), StoreLocal(5), LoadLocal(1), Return]);
+        let error = invalid.build().validate().unwrap_err();
+        assert!(
+            matches!(error, Error::InvalidLocalRef(2, StoreLocal(5), 4)),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        use Instruction::{InlineConstant as Inline, JumpIf, Return};
+
+        let encode = |instructions: &[Instruction], padding: &[u8]| {
+            let mut body = Vec::new();
+            for ins in instructions {
+                ins.encode(&mut body).unwrap();
+            }
+            body.extend_from_slice(padding);
+            body
+        };
+
+        let instructions = [Inline(InlineConstant::Unit), Return];
+        let plain = encode(&instructions, &[]);
+        let padded = encode(&instructions, &[0, 0, 0]);
+        let garbage = encode(&instructions, &[Opcode::Constant.into()]);
+        let plain = InstructionSequence::new(&plain);
+        let padded = InstructionSequence::new(&padded);
+        let garbage = InstructionSequence::new(&garbage);
+        assert_ne!(plain, padded);
+        assert!(plain.semantic_eq(&padded));
+        assert!(padded.semantic_eq(&garbage));
+
+        // code after a return is not padding if a jump leads there
+        let branch = |value: bool| {
+            let instructions = [
+                JumpIf(Offset::Forward(2)),
+                Inline(InlineConstant::Unit),
+                Return,
+                Inline(InlineConstant::Bool(value)),
+                Return,
+            ];
+            encode(&instructions, &[])
+        };
+        let (a, b) = (branch(true), branch(false));
+        let (a, b) = (InstructionSequence::new(&a), InstructionSequence::new(&b));
+        assert!(a.semantic_eq(&a));
+        assert!(!a.semantic_eq(&b));
+
+        // sequences that can't be decoded are compared by bytes
+        let invalid = InstructionSequence::new(&[0]);
+        assert!(invalid.semantic_eq(&InstructionSequence::new(&[0])));
+        assert!(!invalid.semantic_eq(&plain));
+    }
+}
@@ -0,0 +1,132 @@
+//! Modules that own their bytecode
+//!
+//! A [`Module`] borrows the bytes it was parsed from. [`LoadedModule`] keeps
+//! those bytes together with the module, for when there's no separate buffer
+//! that could outlive the module, e.g. when loading a bytecode file.
+
+use std::{fmt, slice};
+
+use super::{parse_bytecode, Error, Module, Result};
+
+/// Bytecode that has been checked to contain a valid module, together with
+/// that module.
+///
+/// The bytecode is parsed and [validated](Module::validate) once on
+/// construction, and [`module`](Self::module) returns the parsed module.
+pub struct LoadedModule {
+    // `module` borrows from `bytecode`. It is declared first so that it is
+    // dropped first, and `bytecode` is never modified
+    module: Module<'static>,
+    bytecode: Vec<u8>,
+}
+
+impl LoadedModule {
+    pub fn parse(bytecode: Vec<u8>) -> Result<Self> {
+        // SAFETY: this extends the lifetime of the borrowed bytes to
+        // `'static`. The bytes live in the vector's heap buffer, which doesn't
+        // move when the vector is moved into `Self`. That buffer is never
+        // reallocated or freed while the module exists: `bytecode` is private
+        // and only ever borrowed immutably, so it can't be modified or
+        // replaced, and the fields' declaration order drops `module` before
+        // `bytecode`. The `'static` lifetime never escapes either: `module()`
+        // shortens it to the lifetime of `&self`, and `Clone` parses the
+        // cloned bytes instead of copying the module
+        let bytes = unsafe { slice::from_raw_parts(bytecode.as_ptr(), bytecode.len()) };
+        let module = parse_bytecode(bytes)?;
+        module.validate()?;
+        Ok(Self { module, bytecode })
+    }
+
+    pub fn bytecode(&self) -> &[u8] {
+        &self.bytecode
+    }
+
+    pub fn module(&self) -> &Module<'_> {
+        &self.module
+    }
+}
+
+impl Clone for LoadedModule {
+    fn clone(&self) -> Self {
+        // the clone's module must borrow from the clone's bytecode
+        Self::parse(self.bytecode.clone()).expect("bytecode was already parsed successfully")
+    }
+}
+
+impl PartialEq for LoadedModule {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytecode == other.bytecode
+    }
+}
+
+impl Eq for LoadedModule {}
+
+impl fmt::Debug for LoadedModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadedModule")
+            .field("module", self.module())
+            .finish()
+    }
+}
+
+impl TryFrom<Vec<u8>> for LoadedModule {
+    type Error = Error;
+
+    fn try_from(bytecode: Vec<u8>) -> Result<Self> {
+        Self::parse(bytecode)
+    }
+}
+
+impl TryFrom<&[u8]> for LoadedModule {
+    type Error = Error;
+
+    fn try_from(bytecode: &[u8]) -> Result<Self> {
+        Self::parse(bytecode.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction;
+    use crate::{Constant, ModuleBuilder};
+
+    #[test]
+    fn test_loaded_module() {
+        let mut builder = ModuleBuilder::new();
+        let answer = builder.add_number(42);
+        let main = builder
+            .add_function(0, [Instruction::Constant(answer)])
+            .unwrap();
+        builder.add_global("main", main);
+        let mut bytecode = Vec::new();
+        builder.write_bytecode(&mut bytecode).unwrap();
+
+        let loaded = LoadedModule::try_from(&bytecode[..]).unwrap();
+        assert_eq!(loaded.bytecode(), bytecode);
+        let module = loaded.module();
+        assert_eq!(module, &builder.build());
+        assert!(matches!(module.global("main"), Some(Constant::Function(_))));
+        assert!(matches!(module.constant(answer), Some(Constant::Number(_))));
+        // the module is parsed once, not on each access
+        assert!(std::ptr::eq(module, loaded.module()));
+
+        // a clone's module borrows the clone's own bytecode
+        let clone = loaded.clone();
+        drop(loaded);
+        assert_eq!(clone.module(), &builder.build());
+
+        assert!(LoadedModule::parse(bytecode[..bytecode.len() - 1].to_vec()).is_err());
+
+        // parsing succeeds, but validation fails: the function loads a missing constant
+        let mut builder = ModuleBuilder::new();
+        let main = builder
+            .add_function(0, [Instruction::Constant(42)])
+            .unwrap();
+        builder.add_global("main", main);
+        let mut bytecode = Vec::new();
+        builder.write_bytecode(&mut bytecode).unwrap();
+        parse_bytecode(&bytecode).unwrap();
+        assert!(LoadedModule::try_from(bytecode).is_err());
+    }
+}
@@ -6,39 +6,52 @@ use nom::number::complete::{be_u16, be_u8};
 use nom::Finish;
 
 use super::{
-    Constant, ConstantKind, Error, Function, InstructionSequence, Module, Number, StructType,
-    StructTypeKind,
+    BytecodeConfig, Constant, ConstantKind, Error, Function, InstructionSequence, Module, Number,
+    StructType, StructTypeKind,
 };
 
 pub type Input<'a> = &'a [u8];
 
 pub type IResult<'a, O, E = Error> = nom::IResult<Input<'a>, O, E>;
 
-pub fn parse_bytecode(i: &[u8]) -> Result<Module, Error> {
-    bytecode(i).finish().map(|(_, bytecode)| bytecode)
+pub fn parse_bytecode(i: &[u8]) -> Result<Module<'_>, Error> {
+    parse_bytecode_with_config(i, &BytecodeConfig::default())
 }
 
-fn bytecode(i: &[u8]) -> IResult<Module> {
-    let (i, _version) = header(i)?;
+/// Parses bytecode that starts with the given config's magic string and
+/// version, instead of the stock ones.
+pub fn parse_bytecode_with_config<'b>(
+    i: &'b [u8],
+    config: &BytecodeConfig,
+) -> Result<Module<'b>, Error> {
+    bytecode(i, config).finish().map(|(_, bytecode)| bytecode)
+}
+
+fn bytecode<'b>(i: &'b [u8], config: &BytecodeConfig) -> IResult<'b, Module<'b>> {
+    let (i, _version) = header(i, config)?;
     let (i, constants) = constants(i)?;
     let (i, globals) = globals(i, &constants)?;
     let (i, struct_types) = struct_types(i, &constants)?;
-    Ok((i, Module::new(constants, globals, struct_types)))
+    let (i, methods) = methods(i, &constants)?;
+    Ok((i, Module::new(constants, globals, struct_types, methods)))
 }
 
-fn header(i: &[u8]) -> IResult<u16> {
-    let (i, _magic) = tag(b"sprachli")(i)?;
+fn header<'b>(i: &'b [u8], config: &BytecodeConfig) -> IResult<'b, u16> {
+    let (i, _magic) = tag(config.magic)(i)?;
     let (i, version) = be_u16(i)?;
+    if version != config.version {
+        Err(nom::Err::Failure(Error::UnsupportedVersion(version)))?;
+    }
     Ok((i, version))
 }
 
-fn constants(i: &[u8]) -> IResult<Vec<Constant>> {
+fn constants(i: &[u8]) -> IResult<'_, Vec<Constant<'_>>> {
     let (i, len) = be_u16(i)?;
     let (i, constants) = count(constant, len as usize)(i)?;
     Ok((i, constants))
 }
 
-fn constant(i: &[u8]) -> IResult<Constant> {
+fn constant(i: &[u8]) -> IResult<'_, Constant<'_>> {
     use ConstantKind::*;
 
     let (i, t) = be_u8(i)?;
@@ -58,10 +71,14 @@ fn constant(i: &[u8]) -> IResult<Constant> {
             let (i, constant) = function(i)?;
             Ok((i, Constant::Function(constant)))
         }
+        Bytes => {
+            let (i, constant) = bytes(i)?;
+            Ok((i, Constant::Bytes(constant)))
+        }
     }
 }
 
-fn number(i: &[u8]) -> IResult<Number> {
+fn number(i: &[u8]) -> IResult<'_, Number> {
     let (i, len) = be_u16(i)?;
     let (i, bytes) = take(len as usize)(i)?;
     let value =
@@ -69,7 +86,7 @@ fn number(i: &[u8]) -> IResult<Number> {
     Ok((i, value))
 }
 
-fn string(i: &[u8]) -> IResult<&str> {
+fn string(i: &[u8]) -> IResult<'_, &str> {
     let (i, len) = be_u16(i)?;
     let (i, bytes) = take(len as usize)(i)?;
     let value =
@@ -77,7 +94,12 @@ fn string(i: &[u8]) -> IResult<&str> {
     Ok((i, value))
 }
 
-fn function(i: &[u8]) -> IResult<Function> {
+fn bytes(i: &[u8]) -> IResult<'_, &[u8]> {
+    let (i, len) = be_u16(i)?;
+    take(len as usize)(i)
+}
+
+fn function(i: &[u8]) -> IResult<'_, Function<'_>> {
     let (i, arity) = be_u16(i)?;
     let (i, len) = be_u16(i)?;
     let (i, bytes) = take(len as usize)(i)?;
@@ -160,3 +182,23 @@ fn struct_type<'b>(
         }
     }
 }
+
+fn methods<'b>(
+    i: &'b [u8],
+    constants: &[Constant<'b>],
+) -> IResult<'b, BTreeMap<(&'b str, &'b str), usize>> {
+    let (i, len) = be_u16(i)?;
+    let (i, methods) = count(|i| method(i, constants), len as usize)(i)?;
+    Ok((i, BTreeMap::from_iter(methods)))
+}
+
+fn method<'b>(i: &'b [u8], constants: &[Constant<'b>]) -> IResult<'b, ((&'b str, &'b str), usize)> {
+    let (i, struct_type) = be_u16(i)?;
+    let (i, name) = be_u16(i)?;
+    let (i, value) = be_u16(i)?;
+
+    let struct_type =
+        get_string_constant(constants, struct_type as usize).map_err(nom::Err::Error)?;
+    let name = get_string_constant(constants, name as usize).map_err(nom::Err::Error)?;
+    Ok((i, ((struct_type, name), value as usize)))
+}
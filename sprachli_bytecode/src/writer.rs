@@ -0,0 +1,137 @@
+//! Serialization of modules in the sprachli bytecode format
+//!
+//! A [`RawModule`] refers to names and constants by index, like the file
+//! format itself does. Both the compiler and the
+//! [`ModuleBuilder`](crate::ModuleBuilder) write their bytecode through it, so
+//! that there is only one place that knows the layout of a bytecode file.
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use super::{BytecodeConfig, ConstantKind, Number, StructTypeKind};
+
+#[derive(Debug, Clone, Copy)]
+pub enum RawConstant<'a> {
+    Number(&'a Number),
+    String(&'a str),
+    /// A function's arity and its encoded body
+    Function(usize, &'a [u8]),
+    Bytes(&'a [u8]),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RawStructType<'a> {
+    Empty,
+    Positional(usize),
+    /// The indices of the string constants naming the fields
+    Named(&'a [usize]),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RawModule<'a> {
+    pub constants: Vec<RawConstant<'a>>,
+    pub globals: BTreeMap<usize, usize>,
+    pub struct_types: BTreeMap<usize, RawStructType<'a>>,
+    pub methods: BTreeMap<(usize, usize), usize>,
+}
+
+impl RawModule<'_> {
+    /// Writes the module with the given config's magic string and version.
+    ///
+    /// All multi-byte integers (lengths, indices, arities) are encoded
+    /// big-endian, matching what [`parse_bytecode`](crate::parse_bytecode)
+    /// expects.
+    pub fn write<W: Write>(&self, w: &mut W, config: &BytecodeConfig) -> Result<()> {
+        config.write_header(w)?;
+        self.write_constants(w)?;
+        self.write_globals(w)?;
+        self.write_struct_types(w)?;
+        self.write_methods(w)?;
+
+        Ok(())
+    }
+
+    fn write_constants<W: Write>(&self, w: &mut W) -> Result<()> {
+        write_u16(w, self.constants.len(), "number of constants")?;
+        for constant in &self.constants {
+            match *constant {
+                RawConstant::Number(value) => {
+                    let value = value.to_string();
+                    w.write_all(&[ConstantKind::Number.into()])?;
+                    write_u16(w, value.len(), "length of number")?;
+                    w.write_all(value.as_bytes())?;
+                }
+                RawConstant::String(value) => {
+                    w.write_all(&[ConstantKind::String.into()])?;
+                    write_u16(w, value.len(), "length of string")?;
+                    w.write_all(value.as_bytes())?;
+                }
+                RawConstant::Function(arity, body) => {
+                    w.write_all(&[ConstantKind::Function.into()])?;
+                    write_u16(w, arity, "arity")?;
+                    write_u16(w, body.len(), "length of function body")?;
+                    w.write_all(body)?;
+                }
+                RawConstant::Bytes(value) => {
+                    w.write_all(&[ConstantKind::Bytes.into()])?;
+                    write_u16(w, value.len(), "length of byte string")?;
+                    w.write_all(value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_globals<W: Write>(&self, w: &mut W) -> Result<()> {
+        write_u16(w, self.globals.len(), "number of globals")?;
+        for (&name, &value) in &self.globals {
+            write_u16(w, name, "index")?;
+            write_u16(w, value, "index")?;
+        }
+        Ok(())
+    }
+
+    fn write_struct_types<W: Write>(&self, w: &mut W) -> Result<()> {
+        write_u16(w, self.struct_types.len(), "number of struct types")?;
+        for (&name, struct_type) in &self.struct_types {
+            write_u16(w, name, "index")?;
+            match *struct_type {
+                RawStructType::Empty => {
+                    w.write_all(&[StructTypeKind::Empty.into()])?;
+                }
+                RawStructType::Positional(count) => {
+                    w.write_all(&[StructTypeKind::Positional.into()])?;
+                    write_u16(w, count, "number of fields")?;
+                }
+                RawStructType::Named(fields) => {
+                    w.write_all(&[StructTypeKind::Named.into()])?;
+                    write_u16(w, fields.len(), "number of fields")?;
+                    for &field in fields {
+                        write_u16(w, field, "index")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_methods<W: Write>(&self, w: &mut W) -> Result<()> {
+        write_u16(w, self.methods.len(), "number of methods")?;
+        for (&(struct_type, name), &value) in &self.methods {
+            write_u16(w, struct_type, "index")?;
+            write_u16(w, name, "index")?;
+            write_u16(w, value, "index")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a length or index as the 16 bits the format uses for it, failing
+/// instead of truncating it and producing a corrupt file.
+fn write_u16<W: Write>(w: &mut W, value: usize, what: &str) -> Result<()> {
+    let value = u16::try_from(value).map_err(|_| {
+        let message = format!("{what} {value} doesn't fit into 16 bits");
+        Error::new(ErrorKind::InvalidData, message)
+    })?;
+    w.write_all(&value.to_be_bytes())
+}
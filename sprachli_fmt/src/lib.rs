@@ -6,6 +6,21 @@ pub trait ModuleFormat {
     type Constant: fmt::Debug;
 
     fn constant(&self, index: usize) -> Option<(&Self::Constant, Option<&str>)>;
+
+    /// The number of constants. By default, this probes [`constant`](Self::constant)
+    /// until it returns `None`; implementors that know the count should
+    /// override this.
+    fn constant_count(&self) -> usize {
+        (0..)
+            .take_while(|&index| self.constant(index).is_some())
+            .count()
+    }
+
+    /// The number of digits of the largest constant index, for aligning
+    /// columns of constant references.
+    fn index_width(&self) -> usize {
+        self.constant_count().saturating_sub(1).to_string().len()
+    }
 }
 
 pub trait FormatterExt<'a> {
@@ -42,10 +57,11 @@ impl<'a> FormatterExt<'a> for fmt::Formatter<'a> {
         index: usize,
     ) -> std::result::Result<Option<&'b str>, fmt::Error> {
         match module.constant(index) {
-            Some((_, Some(name))) => {
+            Some((_, Some(name))) if is_identifier(name) => {
                 self.write_str(name)?;
                 return Ok(Some(name));
             }
+            Some((_, Some(name))) => write!(self, "{name:?} (invalid identifier)")?,
             Some((constant, _)) => write!(self, "{constant:?} (invalid identifier)")?,
             None => self.write_str("illegal constant")?,
         }
@@ -53,6 +69,17 @@ impl<'a> FormatterExt<'a> for fmt::Formatter<'a> {
     }
 }
 
+/// Checks whether the string is a valid identifier: it must start with an
+/// alphabetic character or underscore, followed by alphanumeric characters or
+/// underscores.
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    (first.is_alphabetic() || first == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
 pub struct DebugSexpr<'a, 'b: 'a> {
     fmt: &'a mut fmt::Formatter<'b>,
     compact: bool,
@@ -229,3 +256,59 @@ pub trait IteratorExt : Iterator {
 }
 
 impl<T: ?Sized> IteratorExt for T where T: Iterator { }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestModule(Vec<&'static str>);
+
+    impl ModuleFormat for TestModule {
+        type Constant = &'static str;
+
+        fn constant(&self, index: usize) -> Option<(&Self::Constant, Option<&str>)> {
+            let constant = self.0.get(index)?;
+            Some((constant, Some(*constant)))
+        }
+    }
+
+    struct Ident<'a>(&'a TestModule, usize);
+
+    impl fmt::Debug for Ident<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.fmt_constant_ident(self.0, self.1).map(|_| ())
+        }
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("foo"));
+        assert!(is_identifier("_foo1"));
+        assert!(is_identifier("Foo_Bar"));
+        assert!(!is_identifier(""));
+        assert!(!is_identifier("1foo"));
+        assert!(!is_identifier("1 2"));
+        assert!(!is_identifier("foo-bar"));
+    }
+
+    #[test]
+    fn test_fmt_constant_ident() {
+        let module = TestModule(vec!["foo", "1 2", "_bar"]);
+
+        assert_eq!(format!("{:?}", Ident(&module, 0)), "foo");
+        assert_eq!(
+            format!("{:?}", Ident(&module, 1)),
+            "\"1 2\" (invalid identifier)"
+        );
+        assert_eq!(format!("{:?}", Ident(&module, 2)), "_bar");
+        assert_eq!(format!("{:?}", Ident(&module, 3)), "illegal constant");
+    }
+
+    #[test]
+    fn test_index_width() {
+        assert_eq!(TestModule(vec![]).index_width(), 1);
+        assert_eq!(TestModule(vec!["foo"; 10]).index_width(), 1);
+        assert_eq!(TestModule(vec!["foo"; 11]).index_width(), 2);
+        assert_eq!(TestModule(vec!["foo"; 101]).index_width(), 3);
+    }
+}
@@ -31,19 +31,47 @@ impl fmt::Debug for Declaration<'_> {
 }
 
 /// A path is a possibly qualified name for some declaration.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Path<'input> {
     pub segments: Vec<PathSegment<'input>>,
 }
 
+impl<'input> Path<'input> {
+    pub fn new(segments: Vec<PathSegment<'input>>) -> Self {
+        Self { segments }
+    }
+}
+
+impl fmt::Debug for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i != 0 {
+                f.write_str("::")?;
+            }
+            segment.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
 /// A path segment is a single part of a path.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum PathSegment<'input> {
     Root,
     Super,
     Name(&'input str),
 }
 
+impl fmt::Debug for PathSegment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Root => Ok(()),
+            Self::Super => f.write_str("super"),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
 /// Most constructs have an explicit or implicit visibility that determines
 /// whether a construct can be accessed by code in different modules.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -65,15 +93,47 @@ impl Visibility {
 
 /// Use declarations make some named declaration available in the current scope,
 /// optionally changing the name under which it's available.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Use<'input> {
     pub visibility: Visibility,
     pub path: Path<'input>,
     pub name: Option<&'input str>,
 }
 
+impl<'input> Use<'input> {
+    pub fn new(visibility: Visibility, path: Path<'input>, name: Option<&'input str>) -> Self {
+        Self {
+            visibility,
+            path,
+            name,
+        }
+    }
+}
+
+impl<'input> From<Use<'input>> for Declaration<'input> {
+    fn from(value: Use<'input>) -> Self {
+        Declaration::Use(value)
+    }
+}
+
+impl fmt::Debug for Use<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_sexpr();
+        f.name("use");
+        self.visibility.fmt(&mut f);
+        f.compact_item(&self.path);
+        if let Some(name) = self.name {
+            f.compact_name("as").compact_name(name);
+        }
+        f.finish()
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct FnDeclaration<'input> {
+    /// The lines of the `///` comments preceding the function, without the
+    /// comment markers.
+    pub doc: Vec<&'input str>,
     pub visibility: Visibility,
     pub name: &'input str,
     pub trunk: FnTrunk<'input>,
@@ -82,11 +142,16 @@ pub struct FnDeclaration<'input> {
 impl<'input> FnDeclaration<'input> {
     pub fn new(visibility: Visibility, name: &'input str, trunk: FnTrunk<'input>) -> Self {
         Self {
+            doc: Vec::new(),
             visibility,
             name,
             trunk,
         }
     }
+
+    pub fn with_doc(self, doc: Vec<&'input str>) -> Self {
+        Self { doc, ..self }
+    }
 }
 
 impl<'input> From<FnDeclaration<'input>> for Declaration<'input> {
@@ -108,6 +173,9 @@ impl fmt::Debug for FnDeclaration<'_> {
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct Struct<'input> {
+    /// The lines of the `///` comments preceding the struct, without the
+    /// comment markers.
+    pub doc: Vec<&'input str>,
     pub visibility: Visibility,
     pub name: &'input str,
     pub members: StructMembers<'input>,
@@ -116,11 +184,16 @@ pub struct Struct<'input> {
 impl<'input> Struct<'input> {
     pub fn new(visibility: Visibility, name: &'input str, members: StructMembers<'input>) -> Self {
         Self {
+            doc: Vec::new(),
             visibility,
             name,
             members,
         }
     }
+
+    pub fn with_doc(self, doc: Vec<&'input str>) -> Self {
+        Self { doc, ..self }
+    }
 }
 
 impl<'input> From<Struct<'input>> for Declaration<'input> {
@@ -174,9 +247,35 @@ pub struct Mixin<'input> {
     pub methods: Vec<FnDeclaration<'input>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Impl<'input> {
     pub name: &'input str,
     pub inheritances: Vec<Path<'input>>,
     pub methods: Vec<FnDeclaration<'input>>,
 }
+
+impl<'input> Impl<'input> {
+    pub fn new(name: &'input str, methods: Vec<FnDeclaration<'input>>) -> Self {
+        Self {
+            name,
+            inheritances: Vec::new(),
+            methods,
+        }
+    }
+}
+
+impl<'input> From<Impl<'input>> for Declaration<'input> {
+    fn from(value: Impl<'input>) -> Self {
+        Declaration::Impl(value)
+    }
+}
+
+impl fmt::Debug for Impl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_sexpr();
+        f.name("impl").compact_name(self.name);
+        f.compact_items(&self.inheritances);
+        f.items(&self.methods);
+        f.finish()
+    }
+}
@@ -7,24 +7,37 @@ use super::{FnTrunk, Statement};
 
 #[derive(Clone, PartialEq, Eq)]
 pub enum Expression<'input> {
+    Unit,
     Number(&'input str),
     Bool(bool),
     String(&'input str),
+    Bytes(&'input str),
+    Interpolation(Interpolation<'input>),
     Identifier(&'input str),
     Binary(Binary<'input>),
     Unary(Unary<'input>),
     Call(Call<'input>),
+    MethodCall(MethodCall<'input>),
+    FieldAccess(FieldAccess<'input>),
+    Index(Index<'input>),
+    Tuple(Tuple<'input>),
+    List(List<'input>),
+    Map(Map<'input>),
     Block(Block<'input>),
     Fn(Fn<'input>),
     If(If<'input>),
     Loop(Loop<'input>),
+    Jump(Jump<'input>),
 }
 
 impl Expression<'_> {
     pub(super) fn is_simple(&self) -> bool {
         use Expression::*;
 
-        matches!(self, Number(_) | Bool(_) | String(_) | Identifier(_))
+        matches!(
+            self,
+            Unit | Number(_) | Bool(_) | String(_) | Bytes(_) | Identifier(_)
+        )
     }
 }
 
@@ -33,17 +46,27 @@ impl fmt::Debug for Expression<'_> {
         use Expression::*;
 
         match self {
+            Unit => f.write_str("()"),
             Number(value) => fmt::Display::fmt(value, f),
             Bool(value) => fmt::Display::fmt(value, f),
             String(value) => fmt::Display::fmt(value, f),
+            Bytes(value) => fmt::Display::fmt(value, f),
+            Interpolation(expr) => expr.fmt(f),
             Identifier(name) => f.write_str(name),
             Binary(expr) => expr.fmt(f),
             Unary(expr) => expr.fmt(f),
             Call(expr) => expr.fmt(f),
+            MethodCall(expr) => expr.fmt(f),
+            FieldAccess(expr) => expr.fmt(f),
+            Index(expr) => expr.fmt(f),
+            Tuple(expr) => expr.fmt(f),
+            List(expr) => expr.fmt(f),
+            Map(expr) => expr.fmt(f),
             Block(expr) => expr.fmt(f),
             Fn(expr) => expr.fmt(f),
             If(expr) => expr.fmt(f),
             Loop(expr) => expr.fmt(f),
+            Jump(expr) => expr.fmt(f),
         }
     }
 }
@@ -74,7 +97,7 @@ pub enum BinaryOperator {
     LessEquals,
 }
 
-impl fmt::Debug for BinaryOperator {
+impl fmt::Display for BinaryOperator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use BinaryOperator::*;
 
@@ -99,6 +122,12 @@ impl fmt::Debug for BinaryOperator {
     }
 }
 
+impl fmt::Debug for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct Binary<'input> {
     pub operator: BinaryOperator,
@@ -148,7 +177,7 @@ pub enum UnaryOperator {
     Not,
 }
 
-impl fmt::Debug for UnaryOperator {
+impl fmt::Display for UnaryOperator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use UnaryOperator::*;
 
@@ -159,6 +188,12 @@ impl fmt::Debug for UnaryOperator {
     }
 }
 
+impl fmt::Debug for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct Unary<'input> {
     pub operator: UnaryOperator,
@@ -222,6 +257,238 @@ impl fmt::Debug for Call<'_> {
     }
 }
 
+#[derive(Clone, PartialEq, Eq)]
+pub struct MethodCall<'input> {
+    pub receiver: Box<Expression<'input>>,
+    pub method: &'input str,
+    pub actual_parameters: Vec<Expression<'input>>,
+}
+
+impl<'input> MethodCall<'input> {
+    pub fn new(
+        receiver: Expression<'input>,
+        method: &'input str,
+        actual_parameters: Vec<Expression<'input>>,
+    ) -> Self {
+        let receiver = Box::new(receiver);
+        Self {
+            receiver,
+            method,
+            actual_parameters,
+        }
+    }
+}
+
+impl<'input> From<MethodCall<'input>> for Expression<'input> {
+    fn from(value: MethodCall<'input>) -> Self {
+        Expression::MethodCall(value)
+    }
+}
+
+impl fmt::Debug for MethodCall<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compact =
+            self.receiver.is_simple() && self.actual_parameters.iter().all(Expression::is_simple);
+        f.debug_sexpr_compact(compact)
+            .name("method-call")
+            .item(&self.receiver)
+            .name(self.method)
+            .items(&self.actual_parameters)
+            .finish()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct FieldAccess<'input> {
+    pub receiver: Box<Expression<'input>>,
+    pub field: &'input str,
+}
+
+impl<'input> FieldAccess<'input> {
+    pub fn new(receiver: Expression<'input>, field: &'input str) -> Self {
+        let receiver = Box::new(receiver);
+        Self { receiver, field }
+    }
+}
+
+impl<'input> From<FieldAccess<'input>> for Expression<'input> {
+    fn from(value: FieldAccess<'input>) -> Self {
+        Expression::FieldAccess(value)
+    }
+}
+
+impl fmt::Debug for FieldAccess<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_sexpr_compact(self.receiver.is_simple())
+            .name("field")
+            .item(&self.receiver)
+            .name(self.field)
+            .finish()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Index<'input> {
+    pub receiver: Box<Expression<'input>>,
+    pub index: Box<Expression<'input>>,
+}
+
+impl<'input> Index<'input> {
+    pub fn new(receiver: Expression<'input>, index: Expression<'input>) -> Self {
+        let receiver = Box::new(receiver);
+        let index = Box::new(index);
+        Self { receiver, index }
+    }
+}
+
+impl<'input> From<Index<'input>> for Expression<'input> {
+    fn from(value: Index<'input>) -> Self {
+        Expression::Index(value)
+    }
+}
+
+impl fmt::Debug for Index<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_sexpr_compact(self.receiver.is_simple() && self.index.is_simple())
+            .name("index")
+            .item(&self.receiver)
+            .item(&self.index)
+            .finish()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Tuple<'input> {
+    pub elements: Vec<Expression<'input>>,
+}
+
+impl<'input> Tuple<'input> {
+    pub fn new(elements: Vec<Expression<'input>>) -> Self {
+        Self { elements }
+    }
+}
+
+impl<'input> From<Tuple<'input>> for Expression<'input> {
+    fn from(value: Tuple<'input>) -> Self {
+        Expression::Tuple(value)
+    }
+}
+
+impl fmt::Debug for Tuple<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compact = self.elements.iter().all(Expression::is_simple);
+        f.debug_sexpr_compact(compact)
+            .name("tuple")
+            .items(&self.elements)
+            .finish()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct List<'input> {
+    pub elements: Vec<Expression<'input>>,
+}
+
+impl<'input> List<'input> {
+    pub fn new(elements: Vec<Expression<'input>>) -> Self {
+        Self { elements }
+    }
+}
+
+impl<'input> From<List<'input>> for Expression<'input> {
+    fn from(value: List<'input>) -> Self {
+        Expression::List(value)
+    }
+}
+
+impl fmt::Debug for List<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compact = self.elements.iter().all(Expression::is_simple);
+        f.debug_sexpr_compact(compact)
+            .name("list")
+            .items(&self.elements)
+            .finish()
+    }
+}
+
+/// A map literal, e.g. `{ "a": 1, "b": 2 }`. Keys are arbitrary
+/// expressions, but must evaluate to strings at runtime.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Map<'input> {
+    pub entries: Vec<(Expression<'input>, Expression<'input>)>,
+}
+
+impl<'input> Map<'input> {
+    pub fn new(entries: Vec<(Expression<'input>, Expression<'input>)>) -> Self {
+        Self { entries }
+    }
+}
+
+impl<'input> From<Map<'input>> for Expression<'input> {
+    fn from(value: Map<'input>) -> Self {
+        Expression::Map(value)
+    }
+}
+
+impl fmt::Debug for Map<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compact = self
+            .entries
+            .iter()
+            .all(|(key, value)| key.is_simple() && value.is_simple());
+        let mut f = f.debug_sexpr_compact(compact);
+        f.name("map");
+        for (key, value) in &self.entries {
+            f.item(key).item(value);
+        }
+        f.finish()
+    }
+}
+
+/// A string literal with embedded expressions, e.g. `"x={x}"`. The parts are
+/// converted to strings and concatenated at runtime.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Interpolation<'input> {
+    pub parts: Vec<InterpolationPart<'input>>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum InterpolationPart<'input> {
+    /// Literal text, with escape sequences already resolved
+    Literal(String),
+    Expression(Expression<'input>),
+}
+
+impl<'input> Interpolation<'input> {
+    pub fn new(parts: Vec<InterpolationPart<'input>>) -> Self {
+        Self { parts }
+    }
+}
+
+impl<'input> From<Interpolation<'input>> for Expression<'input> {
+    fn from(value: Interpolation<'input>) -> Self {
+        Expression::Interpolation(value)
+    }
+}
+
+impl fmt::Debug for Interpolation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compact = self.parts.iter().all(|part| match part {
+            InterpolationPart::Literal(_) => true,
+            InterpolationPart::Expression(expr) => expr.is_simple(),
+        });
+        let mut f = f.debug_sexpr_compact(compact);
+        f.name("interpolate");
+        for part in &self.parts {
+            match part {
+                InterpolationPart::Literal(value) => f.item(value),
+                InterpolationPart::Expression(expr) => f.item(expr),
+            };
+        }
+        f.finish()
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct Block<'input> {
     pub statements: Vec<Statement<'input>>,
@@ -247,10 +514,7 @@ impl<'input> From<Block<'input>> for Expression<'input> {
 impl fmt::Debug for Block<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let compact = self.statements.iter().all(Statement::is_simple)
-            && self
-                .expression
-                .as_deref()
-                .map_or(true, Expression::is_simple);
+            && self.expression.as_deref().is_none_or(Expression::is_simple);
         let mut f = f.debug_sexpr_compact(compact);
         f.name("block").items(&self.statements);
         if let Some(expression) = &self.expression {
@@ -327,12 +591,14 @@ impl fmt::Debug for If<'_> {
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct Loop<'input> {
+    /// The loop's label, including the leading `'`, e.g. `'outer`.
+    pub label: Option<&'input str>,
     pub body: Block<'input>,
 }
 
 impl<'input> Loop<'input> {
-    pub fn new(body: Block<'input>) -> Self {
-        Self { body }
+    pub fn new(label: Option<&'input str>, body: Block<'input>) -> Self {
+        Self { label, body }
     }
 }
 
@@ -344,6 +610,68 @@ impl<'input> From<Loop<'input>> for Expression<'input> {
 
 impl fmt::Debug for Loop<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_sexpr().name("loop").item(&self.body).finish()
+        f.debug_sexpr()
+            .name("loop")
+            .names(self.label)
+            .item(&self.body)
+            .finish()
+    }
+}
+
+/// A `return`, `break` or `continue`. Jumps diverge, i.e. never produce a
+/// value, so they can be used wherever an expression is expected.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Jump<'input> {
+    Return(Option<Box<Expression<'input>>>),
+    /// Exits the loop with the given label, or the innermost one.
+    Break(Option<&'input str>, Option<Box<Expression<'input>>>),
+    /// Continues the loop with the given label, or the innermost one.
+    Continue(Option<&'input str>),
+}
+
+impl<'input> Jump<'input> {
+    pub fn new_return(right: Option<Expression<'input>>) -> Self {
+        let right = right.map(Box::new);
+        Self::Return(right)
+    }
+
+    pub fn new_break(label: Option<&'input str>, right: Option<Expression<'input>>) -> Self {
+        let right = right.map(Box::new);
+        Self::Break(label, right)
+    }
+}
+
+impl<'input> From<Jump<'input>> for Expression<'input> {
+    fn from(value: Jump<'input>) -> Self {
+        Expression::Jump(value)
+    }
+}
+
+impl fmt::Debug for Jump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Jump::*;
+
+        match self {
+            Return(expr) => {
+                let compact = expr.as_deref().is_none_or(Expression::is_simple);
+                f.debug_sexpr_compact(compact)
+                    .name("return")
+                    .items(expr.iter())
+                    .finish()
+            }
+            Break(label, expr) => {
+                let compact = expr.as_deref().is_none_or(Expression::is_simple);
+                f.debug_sexpr_compact(compact)
+                    .name("break")
+                    .names(label)
+                    .items(expr.iter())
+                    .finish()
+            }
+            Continue(label) => f
+                .debug_sexpr_compact(true)
+                .name("continue")
+                .names(label)
+                .finish(),
+        }
     }
 }
@@ -8,8 +8,8 @@ use super::{Declaration, Expression, Variable};
 pub enum Statement<'input> {
     Declaration(Declaration<'input>),
     Expression(Expression<'input>),
-    Jump(Jump<'input>),
     VariableDeclaration(VariableDeclaration<'input>),
+    TupleDeclaration(TupleDeclaration<'input>),
     Assignment(Assignment<'input>),
 }
 
@@ -29,97 +29,87 @@ impl fmt::Debug for Statement<'_> {
         match self {
             Self::Declaration(stmt) => stmt.fmt(f),
             Self::Expression(stmt) => stmt.fmt(f),
-            Self::Jump(stmt) => stmt.fmt(f),
             Self::VariableDeclaration(stmt) => stmt.fmt(f),
+            Self::TupleDeclaration(stmt) => stmt.fmt(f),
             Self::Assignment(stmt) => stmt.fmt(f),
         }
     }
 }
 
 #[derive(Clone, PartialEq, Eq)]
-pub enum Jump<'input> {
-    Return(Option<Box<Expression<'input>>>),
-    Break(Option<Box<Expression<'input>>>),
-    Continue,
+pub struct VariableDeclaration<'input> {
+    pub variable: Variable<'input>,
+    pub initializer: Option<Expression<'input>>,
 }
 
-impl<'input> Jump<'input> {
-    pub fn new_return(right: Option<Expression<'input>>) -> Self {
-        let right = right.map(Box::new);
-        Self::Return(right)
-    }
-
-    pub fn new_break(right: Option<Expression<'input>>) -> Self {
-        let right = right.map(Box::new);
-        Self::Break(right)
+impl<'input> VariableDeclaration<'input> {
+    pub fn new(variable: Variable<'input>, initializer: Option<Expression<'input>>) -> Self {
+        Self {
+            variable,
+            initializer,
+        }
     }
 }
 
-impl<'input> From<Jump<'input>> for Statement<'input> {
-    fn from(value: Jump<'input>) -> Self {
-        Statement::Jump(value)
+impl<'input> From<VariableDeclaration<'input>> for Statement<'input> {
+    fn from(value: VariableDeclaration<'input>) -> Self {
+        Statement::VariableDeclaration(value)
     }
 }
 
-impl fmt::Debug for Jump<'_> {
+impl fmt::Debug for VariableDeclaration<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use Jump::*;
-
-        match self {
-            Return(expr) => {
-                let compact = expr.as_deref().map_or(true, Expression::is_simple);
-                f.debug_sexpr_compact(compact)
-                    .name("return")
-                    .items(expr.iter())
-                    .finish()
-            }
-            Break(expr) => {
-                let compact = expr.as_deref().map_or(true, Expression::is_simple);
-                f.debug_sexpr_compact(compact)
-                    .name("break")
-                    .items(expr.iter())
-                    .finish()
-            }
-            Continue => f.debug_sexpr_compact(true).name("continue").finish(),
-        }
+        let compact = self.initializer.as_ref().is_none_or(Expression::is_simple);
+        f.debug_sexpr_compact(compact)
+            .name("let")
+            .compact_item(&self.variable)
+            .items(self.initializer.iter())
+            .finish()
     }
 }
 
+/// A declaration that destructures a tuple into multiple variables, e.g.
+/// `let (a, b) = f();`.
 #[derive(Clone, PartialEq, Eq)]
-pub struct VariableDeclaration<'input> {
-    pub variable: Variable<'input>,
-    pub initializer: Option<Expression<'input>>,
+pub struct TupleDeclaration<'input> {
+    pub variables: Vec<Variable<'input>>,
+    pub initializer: Expression<'input>,
 }
 
-impl<'input> VariableDeclaration<'input> {
-    pub fn new(variable: Variable<'input>, initializer: Option<Expression<'input>>) -> Self {
+impl<'input> TupleDeclaration<'input> {
+    pub fn new(variables: Vec<Variable<'input>>, initializer: Expression<'input>) -> Self {
         Self {
-            variable,
+            variables,
             initializer,
         }
     }
 }
 
-impl<'input> From<VariableDeclaration<'input>> for Statement<'input> {
-    fn from(value: VariableDeclaration<'input>) -> Self {
-        Statement::VariableDeclaration(value)
+impl<'input> From<TupleDeclaration<'input>> for Statement<'input> {
+    fn from(value: TupleDeclaration<'input>) -> Self {
+        Statement::TupleDeclaration(value)
     }
 }
 
-impl fmt::Debug for VariableDeclaration<'_> {
+impl fmt::Debug for TupleDeclaration<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let compact = self
-            .initializer
-            .as_ref()
-            .map_or(true, Expression::is_simple);
+        let compact = self.initializer.is_simple();
         f.debug_sexpr_compact(compact)
             .name("let")
-            .compact_item(&self.variable)
-            .items(self.initializer.iter())
+            .compact_item(&VariableList(&self.variables))
+            .item(&self.initializer)
             .finish()
     }
 }
 
+struct VariableList<'a, 'input>(&'a [Variable<'input>]);
+
+impl fmt::Debug for VariableList<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_sexpr_compact(true).compact_items(self.0).finish()
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct Assignment<'input> {
     pub left: Expression<'input>,
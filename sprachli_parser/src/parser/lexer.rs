@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// A token with its start and end offset in the source
+pub type Spanned<'input> = (usize, Token<'input>, usize);
+
+const KEYWORDS: &[&str] = &[
+    "pub", "use", "as", "super", "fn", "struct", "impl", "return", "break", "continue", "let",
+    "mut", "if", "else", "loop", "true", "false",
+];
+
+// two-character symbols must be tried before their one-character prefixes
+const SYMBOLS: &[&str] = &[
+    "!=", "&&", "::", "<<", "<=", "==", ">=", ">>", "||", "!", "%", "&", "(", ")", "*", "+", ",",
+    "-", ".", "/", ":", ";", "<", "=", ">", "?", "[", "]", "^", "{", "|", "}",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Token<'input> {
+    /// A keyword or punctuation, e.g. `fn` or `==`
+    Symbol(&'input str),
+    Identifier(&'input str),
+    Label(&'input str),
+    Number(&'input str),
+    /// A string literal, including its quotes
+    String(&'input str),
+    /// A byte string literal, including the `b` prefix and quotes
+    Bytes(&'input str),
+    /// Input that is not a valid token, e.g. an unterminated string. Lexing
+    /// stops there, and the grammar doesn't accept it.
+    Invalid(&'input str),
+}
+
+impl<'input> Token<'input> {
+    pub fn text(&self) -> &'input str {
+        use Token::*;
+
+        match *self {
+            Symbol(text) | Identifier(text) | Label(text) | Number(text) | String(text)
+            | Bytes(text) | Invalid(text) => text,
+        }
+    }
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.text(), f)
+    }
+}
+
+/// The `///` doc comments of a source, keyed by the offset of the token
+/// following them.
+#[derive(Debug, Clone, Default)]
+pub struct DocComments<'input>(HashMap<usize, Vec<&'input str>>);
+
+impl<'input> DocComments<'input> {
+    /// Returns the lines of the doc comments preceding the token at the given
+    /// offset, without the comment markers.
+    pub fn get(&self, location: usize) -> Vec<&'input str> {
+        self.0.get(&location).cloned().unwrap_or_default()
+    }
+}
+
+/// Splits the source into tokens for the parser. Comments and whitespace are
+/// skipped, except that `///` comments on their own lines are collected as
+/// doc comments of the token following them. Elsewhere, doc comments are just
+/// comments.
+///
+/// lalrpop's built-in lexer could only skip doc comments or turn them into
+/// tokens; as tokens, the grammar would have to accept them anywhere, or
+/// stray `///` comments in existing sources would become syntax errors.
+/// Otherwise, the tokens are the same as with lalrpop's lexer, except that a
+/// line continuation in a string may also end in a lone `\r`.
+pub fn lex<E>(
+    input: &str,
+) -> (
+    impl Iterator<Item = Result<Spanned<'_>, E>>,
+    DocComments<'_>,
+) {
+    let mut lexer = Lexer {
+        input,
+        position: 0,
+        line_start: true,
+        doc: Vec::new(),
+        docs: DocComments::default(),
+    };
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next_token() {
+        let invalid = matches!(token.1, Token::Invalid(_));
+        tokens.push(token);
+        if invalid {
+            break;
+        }
+    }
+    (tokens.into_iter().map(Ok), lexer.docs)
+}
+
+struct Lexer<'input> {
+    input: &'input str,
+    position: usize,
+    /// Whether only whitespace precedes the position on its line
+    line_start: bool,
+    /// The doc comment lines that will be attached to the next token
+    doc: Vec<&'input str>,
+    docs: DocComments<'input>,
+}
+
+impl<'input> Lexer<'input> {
+    fn rest(&self) -> &'input str {
+        &self.input[self.position..]
+    }
+
+    fn next_token(&mut self) -> Option<Spanned<'input>> {
+        self.skip_trivia();
+
+        let start = self.position;
+        let rest = self.rest();
+        let first = rest.chars().next()?;
+        let (token, len) = if first.is_ascii_digit() {
+            number(rest)
+        } else if let Some(len) = rest.strip_prefix('b').and_then(|bytes| quoted(bytes, true)) {
+            // if the byte string is malformed, `b` is an identifier
+            (Token::Bytes(&rest[..len + 1]), len + 1)
+        } else if first == '_' || first.is_ascii_alphabetic() {
+            let len = identifier(rest);
+            let text = &rest[..len];
+            let token = if KEYWORDS.contains(&text) {
+                Token::Symbol(text)
+            } else {
+                Token::Identifier(text)
+            };
+            (token, len)
+        } else if first == '\'' && identifier(&rest[1..]) > 0 {
+            let len = identifier(&rest[1..]) + 1;
+            (Token::Label(&rest[..len]), len)
+        } else if first == '"' {
+            match quoted(rest, false) {
+                Some(len) => (Token::String(&rest[..len]), len),
+                None => (Token::Invalid(rest), rest.len()),
+            }
+        } else if rest.starts_with("/*") {
+            // `skip_trivia` leaves only unterminated block comments
+            (Token::Invalid(rest), rest.len())
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            (Token::Symbol(&rest[..symbol.len()]), symbol.len())
+        } else {
+            let len = first.len_utf8();
+            (Token::Invalid(&rest[..len]), len)
+        };
+
+        if !self.doc.is_empty() {
+            let doc = std::mem::take(&mut self.doc);
+            self.docs.0.insert(start, doc);
+        }
+        self.position += len;
+        self.line_start = false;
+        Some((start, token, self.position))
+    }
+
+    /// Skips whitespace and comments, collecting doc comments on the way. An
+    /// unterminated block comment is left for `next_token` to report.
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = self.rest();
+            if let Some(ch) = rest.chars().next().filter(|ch| ch.is_whitespace()) {
+                self.line_start |= ch == '\n' || ch == '\r';
+                self.position += ch.len_utf8();
+            } else if rest.starts_with("//") {
+                let len = rest.find(['\n', '\r']).unwrap_or(rest.len());
+                let comment = &rest[..len];
+                // `//// comments` are not doc comments
+                let doc = comment
+                    .strip_prefix("///")
+                    .filter(|doc| !doc.starts_with('/'));
+                if let Some(doc) = doc.filter(|_| self.line_start) {
+                    self.doc.push(doc.strip_prefix(' ').unwrap_or(doc));
+                }
+                self.position += len;
+            } else if rest.starts_with("/*") {
+                let Some(len) = block_comment(rest) else {
+                    return;
+                };
+                self.position += len;
+                self.line_start = false;
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+/// Returns the length of the block comment at the start of `s`, if it is
+/// terminated. Block comments nest, so each `/*` needs its own `*/`.
+fn block_comment(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = 0;
+    while let Some(rest) = s.get(index..).filter(|rest| !rest.is_empty()) {
+        if rest.starts_with("/*") {
+            depth += 1;
+            index += 2;
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            index += 2;
+            if depth == 0 {
+                return Some(index);
+            }
+        } else {
+            index += rest.chars().next().unwrap().len_utf8();
+        }
+    }
+    None
+}
+
+/// Returns the length of the identifier at the start of `s`, or zero
+fn identifier(s: &str) -> usize {
+    if !s.starts_with(|ch: char| ch == '_' || ch.is_ascii_alphabetic()) {
+        return 0;
+    }
+    s.find(|ch: char| ch != '_' && !ch.is_ascii_alphanumeric())
+        .unwrap_or(s.len())
+}
+
+/// Lexes a number: only plain decimals, no exponents, hex floats, `inf` or
+/// `nan`, which `BigDecimal` either can't represent or would parse differently
+fn number(s: &str) -> (Token<'_>, usize) {
+    let digits = |s: &str| s.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(s.len());
+    let mut len = digits(s);
+    if let Some(fraction) = s[len..].strip_prefix('.') {
+        if digits(fraction) > 0 {
+            len += 1 + digits(fraction);
+        }
+    }
+    (Token::Number(&s[..len]), len)
+}
+
+/// Returns the length of the string literal at the start of `s`, if it is
+/// well-formed. Escapes are resolved later, but must be valid already. Byte
+/// strings only contain ASCII characters; other bytes are written as `\xNN`.
+fn quoted(s: &str, bytes: bool) -> Option<usize> {
+    let mut chars = s.char_indices().peekable();
+    chars.next().filter(|&(_, ch)| ch == '"')?;
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '"' => return Some(index + 1),
+            '\\' => match chars.next()?.1 {
+                // a line continuation may end in any kind of line break
+                '\\' | 'n' | 'r' | 't' | '"' | '\n' | '\r' => {}
+                'x' if bytes => {
+                    for _ in 0..2 {
+                        chars.next_if(|&(_, ch)| ch.is_ascii_hexdigit())?;
+                    }
+                }
+                _ => return None,
+            },
+            ch if bytes && !ch.is_ascii() => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Token::*;
+
+    fn tokens(input: &str) -> Vec<Token<'_>> {
+        let (tokens, _) = lex::<()>(input);
+        tokens.map(|token| token.unwrap().1).collect()
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(
+            tokens("foo _bar b2 fnord fn mut"),
+            [
+                Identifier("foo"),
+                Identifier("_bar"),
+                Identifier("b2"),
+                Identifier("fnord"),
+                Symbol("fn"),
+                Symbol("mut"),
+            ]
+        );
+        assert_eq!(
+            tokens("'outer: b"),
+            [Label("'outer"), Symbol(":"), Identifier("b")]
+        );
+        assert_eq!(tokens("'1"), [Invalid("'")]);
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(
+            tokens("0 42 1.50"),
+            [Number("0"), Number("42"), Number("1.50")]
+        );
+        // a trailing `.` is not part of the number, e.g. for field access
+        assert_eq!(tokens("1."), [Number("1"), Symbol(".")]);
+        assert_eq!(tokens("1e3"), [Number("1"), Identifier("e3")]);
+        assert_eq!(tokens(".5"), [Symbol("."), Number("5")]);
+    }
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(
+            tokens("a<<=b::c"),
+            [
+                Identifier("a"),
+                Symbol("<<"),
+                Symbol("="),
+                Identifier("b"),
+                Symbol("::"),
+                Identifier("c"),
+            ]
+        );
+        assert_eq!(tokens("a # b"), [Identifier("a"), Invalid("#")]);
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(tokens(r#""a\tb\"c""#), [String(r#""a\tb\"c""#)]);
+        assert_eq!(tokens(r#""{a}" b"#), [String(r#""{a}""#), Identifier("b")]);
+        for continuation in ["\"a\\\n b\"", "\"a\\\r\n b\"", "\"a\\\r b\""] {
+            assert_eq!(tokens(continuation), [String(continuation)]);
+        }
+        assert_eq!(tokens(r#""a\q""#), [Invalid(r#""a\q""#)]);
+        assert_eq!(tokens(r#""a"#), [Invalid(r#""a"#)]);
+
+        assert_eq!(tokens(r#"b"\x00\n""#), [Bytes(r#"b"\x00\n""#)]);
+        // a malformed byte string is an identifier and a string
+        assert_eq!(tokens(r#"b"ä""#), [Identifier("b"), String(r#""ä""#)]);
+        assert_eq!(tokens(r#"b"\x1""#), [Identifier("b"), Invalid(r#""\x1""#)]);
+    }
+
+    #[test]
+    fn test_comments() {
+        let (spanned, docs) = lex::<()>("/// doc\n//// not\n// plain\nfn /// trailing\nf");
+        let spanned: Vec<_> = spanned.map(Result::unwrap).collect();
+        assert_eq!(
+            spanned.iter().map(|token| token.1).collect::<Vec<_>>(),
+            [Symbol("fn"), Identifier("f")]
+        );
+        // only `///` comments on their own lines are doc comments
+        assert_eq!(docs.get(spanned[0].0), ["doc"]);
+        assert!(docs.get(spanned[1].0).is_empty());
+
+        assert_eq!(tokens("a /* b */ c"), [Identifier("a"), Identifier("c")]);
+        assert_eq!(
+            tokens("a /* /* b */ */ c"),
+            [Identifier("a"), Identifier("c")]
+        );
+        assert_eq!(tokens("a /* b"), [Identifier("a"), Invalid("/* b")]);
+        assert_eq!(
+            tokens("a /* /* b */"),
+            [Identifier("a"), Invalid("/* /* b */")]
+        );
+    }
+
+    #[test]
+    fn test_line_endings() {
+        for source in [
+            "a\nb",
+            "a\r\nb",
+            "a\rb",
+            "a // c\nb",
+            "a // c\r\nb",
+            "a // c\rb",
+        ] {
+            assert_eq!(
+                tokens(source),
+                [Identifier("a"), Identifier("b")],
+                "{source:?}"
+            );
+        }
+        for source in ["/// doc\nf", "/// doc\r\nf", "x\r/// doc\rf"] {
+            let (tokens, docs) = lex::<()>(source);
+            let (start, token, _) = tokens.last().unwrap().unwrap();
+            assert_eq!(token, Identifier("f"));
+            assert_eq!(docs.get(start), ["doc"], "{source:?}");
+        }
+    }
+}
@@ -3,22 +3,24 @@ lalrpop_mod!(
     pub grammar,
     "/parser/sprachli.rs"
 );
+mod lexer;
 mod string_literal;
 
-use lalrpop_util::lexer::Token;
 use lalrpop_util::ParseError;
 
 use crate::ast::SourceFile;
 use grammar::SourceFileParser;
 
-pub use string_literal::{string_from_literal, ParseStringError};
+pub use lexer::{lex, DocComments, Spanned, Token};
+pub use string_literal::{bytes_from_literal, string_from_literal, ParseStringError};
 
-pub type Error<'a> = ParseError<usize, Token<'a>, &'static str>;
+pub type Error<'a> = ParseError<usize, Token<'a>, ParseStringError>;
 pub type Result<'a, T> = std::result::Result<T, Error<'a>>;
 
-pub fn parse_source_file(source: &str) -> Result<SourceFile> {
+pub fn parse_source_file(source: &str) -> Result<'_, SourceFile<'_>> {
+    let (tokens, docs) = lex(source);
     let parser = SourceFileParser::new();
-    parser.parse(source)
+    parser.parse(&docs, tokens)
 }
 
 #[cfg(test)]
@@ -31,7 +33,7 @@ mod tests {
     trait ParsingFn<'input> {
         type Ast: 'input + fmt::Debug;
 
-        fn call<'a>(&'a self, input: &'input str) -> Result<Self::Ast>
+        fn call<'a>(&'a self, input: &'input str) -> Result<'a, Self::Ast>
         where
             'input: 'a;
     }
@@ -43,7 +45,7 @@ mod tests {
     {
         type Ast = Ast;
 
-        fn call<'a>(&'a self, input: &'input str) -> Result<Self::Ast>
+        fn call<'a>(&'a self, input: &'input str) -> Result<'a, Self::Ast>
         where
             'input: 'a,
         {
@@ -79,22 +81,143 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_empty_source_file() {
+        for source in [
+            "",
+            "   ",
+            "\n// comment\n",
+            "// comment",
+            "/* comment */\n\t",
+        ] {
+            let ast = parse_source_file(source).unwrap();
+            assert!(
+                ast.declarations.is_empty(),
+                "{source:?} contains declarations"
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let parse_eq = |source: &str, expected: &str| {
+            let ast = parse_source_file(source).unwrap();
+            assert_eq!(ast, parse_source_file(expected).unwrap(), "{source:?}");
+        };
+
+        // code-like tokens in a comment, including an unterminated string, are skipped
+        parse_eq(r#"/* fn foo() { " */ fn main() {}"#, "fn main() {}");
+        parse_eq(r#"fn main() { 1 /* } " fn */ + 2 }"#, "fn main() { 1 + 2 }");
+        parse_eq("/* ** / * */ fn main() {}", "fn main() {}");
+        parse_eq("/**/fn main() {}", "fn main() {}");
+        parse_eq("fn main() {\n/*\nlet x = 1;\n*/\n}", "fn main() {}");
+
+        // comment delimiters in a string literal don't start a comment
+        let ast = parse_source_file(r#"fn main() { "/* x */" }"#).unwrap();
+        assert!(format!("{ast:?}").contains(r#""/* x */""#));
+
+        // block comments nest, so an inner `*/` doesn't end the outer comment
+        parse_eq("/* a /* b */ c */ fn main() {}", "fn main() {}");
+        parse_eq("/* /* /**/ */ */ fn main() {}", "fn main() {}");
+        parse_eq("/*/ fn foo() {} */ fn main() {}", "fn main() {}");
+        assert!(parse_source_file("/* unterminated fn main() {}").is_err());
+        assert!(parse_source_file("/* /* */ fn main() {}").is_err());
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let parse_eq = |source: &str, expected: &str| {
+            let ast = parse_source_file(source).unwrap();
+            assert_eq!(ast, parse_source_file(expected).unwrap(), "{source:?}");
+        };
+
+        // every comma separated list accepts a single trailing comma
+        parse_eq("fn foo(a, b,) {}", "fn foo(a, b) {}");
+        parse_eq("struct Foo(a, b,);", "struct Foo(a, b);");
+        parse_eq("struct Foo { a, b, }", "struct Foo { a, b }");
+        parse_eq(
+            "fn main() { let (a, b,) = c; }",
+            "fn main() { let (a, b) = c; }",
+        );
+        for (trailing, plain) in [
+            ("foo(1, 2,)", "foo(1, 2)"),
+            ("a.foo(1, 2,)", "a.foo(1, 2)"),
+            ("(1, 2,)", "(1, 2)"),
+            ("[1, 2,]", "[1, 2]"),
+            ("[1,]", "[1]"),
+            (r#"{ "a": 1, "b": 2, }"#, r#"{ "a": 1, "b": 2 }"#),
+            (r#"{ "a": 1, }"#, r#"{ "a": 1 }"#),
+        ] {
+            parse_eq(
+                &format!("fn main() {{ {trailing} }}"),
+                &format!("fn main() {{ {plain} }}"),
+            );
+        }
+
+        // a lone or doubled comma is not accepted anywhere
+        for source in [
+            "fn foo(,) {}",
+            "fn foo(a,,) {}",
+            "struct Foo(,);",
+            "struct Foo { a,, }",
+            "fn main() { foo(,) }",
+            "fn main() { [,] }",
+            "fn main() { [1,,] }",
+            "fn main() { { , } }",
+            r#"fn main() { { "a": 1,, } }"#,
+        ] {
+            assert!(parse_source_file(source).is_err(), "{source:?}");
+        }
+    }
+
     #[test]
     fn test_declaration_parser() {
-        fn parse<'input>(input: &'input str) -> Result<crate::ast::Declaration<'input>> {
-            DeclarationParser::new().parse(input)
+        fn parse<'input>(input: &'input str) -> Result<'input, crate::ast::Declaration<'input>> {
+            let (tokens, docs) = lex(input);
+            DeclarationParser::new().parse(&docs, tokens)
         }
 
         let test = TestParser::new(parse);
 
         test.parse("fn foo() {}", "(fn foo (block ()))");
         test.parse("struct Foo;", "(struct empty Foo)");
+        test.parse("use foo;", "(use foo)");
+        test.parse("impl Foo {}", "(impl Foo)");
+        test.parse(
+            "impl Foo { fn bar(self) { self } }",
+            "(impl Foo (fn bar (self) (block self)))",
+        );
+        test.parse(
+            "impl Foo { fn bar(self) {} pub fn baz() {} }",
+            "(impl Foo (fn bar (self) (block ())) (fn pub baz (block ())))",
+        );
+        test.parse_err("impl Foo { struct Bar; }");
+    }
+
+    #[test]
+    fn test_use_parser() {
+        fn parse<'input>(input: &'input str) -> Result<'input, crate::ast::Use<'input>> {
+            let (tokens, docs) = lex(input);
+            UseParser::new().parse(&docs, tokens)
+        }
+
+        let test = TestParser::new(parse);
+
+        test.parse("use foo;", "(use foo)");
+        test.parse("pub use foo;", "(use pub foo)");
+        test.parse("use foo::bar;", "(use foo::bar)");
+        test.parse("use super::foo;", "(use super::foo)");
+        test.parse("use foo::bar as baz;", "(use foo::bar as baz)");
+        test.parse_err("use foo");
+        test.parse_err("use foo::;");
+        test.parse_err("use foo as;");
     }
 
     #[test]
     fn test_fn_declaration_parser() {
-        fn parse<'input>(input: &'input str) -> Result<crate::ast::FnDeclaration<'input>> {
-            FnDeclarationParser::new().parse(input)
+        fn parse<'input>(input: &'input str) -> Result<'input, crate::ast::FnDeclaration<'input>> {
+            let (tokens, docs) = lex(input);
+            FnDeclarationParser::new().parse(&docs, tokens)
         }
 
         let test = TestParser::new(parse);
@@ -108,10 +231,72 @@ mod tests {
         test.parse_err("fn foo(a, 1) {}");
     }
 
+    #[test]
+    fn test_doc_comments() {
+        let source = "/// Adds two numbers.\n///\n///  Indented\nfn add(a, b) { a + b }";
+        let (tokens, docs) = lex(source);
+        let decl = FnDeclarationParser::new().parse(&docs, tokens).unwrap();
+        assert_eq!(decl.doc, vec!["Adds two numbers.", "", " Indented"]);
+        assert_eq!(format!("{decl:?}"), "(fn add (a) (b) (block (+ a b)))");
+
+        let source = "// not a doc comment\n//// neither\npub fn foo() {}";
+        let (tokens, docs) = lex(source);
+        let decl = FnDeclarationParser::new().parse(&docs, tokens).unwrap();
+        assert!(decl.doc.is_empty());
+
+        let source = "/// A point.\nstruct Point { x, y }";
+        let (tokens, docs) = lex(source);
+        let decl = StructParser::new().parse(&docs, tokens).unwrap();
+        assert_eq!(decl.doc, vec!["A point."]);
+
+        let source = "/// Main.\nfn main() {}\n/// Ignored.\nimpl P {\n/// Method.\nfn m() {} }";
+        let file = parse_source_file(source).unwrap();
+        let [crate::ast::Declaration::Fn(main), crate::ast::Declaration::Impl(decl)] =
+            &file.declarations[..]
+        else {
+            panic!("unexpected declarations: {file:?}");
+        };
+        assert_eq!(main.doc, vec!["Main."]);
+        assert_eq!(decl.methods[0].doc, vec!["Method."]);
+
+        let source = "/// A.\n// skipped\n//// skipped\n/// B.\nfn a() {}\n/// C.\nfn c() {}";
+        let file = parse_source_file(source).unwrap();
+        let [crate::ast::Declaration::Fn(a), crate::ast::Declaration::Fn(c)] =
+            &file.declarations[..]
+        else {
+            panic!("unexpected declarations: {file:?}");
+        };
+        assert_eq!(a.doc, vec!["A.", "B."]);
+        assert_eq!(c.doc, vec!["C."]);
+
+        // only `///` comments on their own lines are doc comments; ones after code on the same
+        // line, or inside block comments and strings, are not
+        for source in [
+            "struct S { a, b } /// hmm\n\nfn f() {}",
+            "/* /// not a doc */ fn f() {}",
+            "fn a() { \"x///y\" }\nfn f() {}",
+        ] {
+            let file = parse_source_file(source).unwrap();
+            let Some(crate::ast::Declaration::Fn(f)) = file.declarations.last() else {
+                panic!("unexpected declarations: {file:?}");
+            };
+            assert!(f.doc.is_empty(), "{source:?}");
+        }
+
+        // doc comments that don't document a declaration are skipped like other comments
+        let source = "fn main() { /// stray\n let a = 1 /// doc\n; a /// doc\n}\n/// trailing";
+        let file = parse_source_file(source).unwrap();
+        assert_eq!(
+            format!("{file:?}"),
+            "(sprachli (fn main (block (let (a) 1) a)))"
+        );
+    }
+
     #[test]
     fn test_struct_parser() {
-        fn parse<'input>(input: &'input str) -> Result<crate::ast::Struct<'input>> {
-            StructParser::new().parse(input)
+        fn parse<'input>(input: &'input str) -> Result<'input, crate::ast::Struct<'input>> {
+            let (tokens, docs) = lex(input);
+            StructParser::new().parse(&docs, tokens)
         }
 
         let test = TestParser::new(parse);
@@ -126,13 +311,16 @@ mod tests {
         test.parse("struct Foo { a, }", "(struct named Foo a)");
         test.parse("struct Foo { a, b }", "(struct named Foo a b)");
         test.parse("struct Foo { a, b, }", "(struct named Foo a b)");
+        // duplicate fields are rejected by the compiler, not the parser
+        test.parse("struct Foo { a, a }", "(struct named Foo a a)");
         test.parse_err("struct Foo { a, 1 }");
     }
 
     #[test]
     fn test_expr_parser() {
-        fn parse<'input>(input: &'input str) -> Result<crate::ast::Expression<'input>> {
-            ExpressionParser::new().parse(input)
+        fn parse<'input>(input: &'input str) -> Result<'input, crate::ast::Expression<'input>> {
+            let (tokens, docs) = lex(input);
+            ExpressionParser::new().parse(&docs, tokens)
         }
 
         let test = TestParser::new(parse);
@@ -142,13 +330,25 @@ mod tests {
         test.parse("(22)", "22");
         test.parse_err("((22)");
         test.parse("{ 22 }", "(block 22)");
+        test.parse("{ 1; 22 }", "(block 1 22)");
+        test.parse("{ 1; 22; }", "(block 1 22 ())");
         test.parse("if a { b } else { c }", "(if a (block b) else (block c))");
         test.parse(
             "if a { b } else if c { d }",
             "(if a (block b) if c (block d))",
         );
 
+        test.parse("a ? b : c", "(if a (block b) else (block c))");
+        test.parse_err("a ? b");
+
         test.parse("-1", "(- 1)");
+        test.parse("1.5", "1.5");
+        test.parse("+42", "42");
+        test.parse("+1.5", "1.5");
+        test.parse("-+1", "(- 1)");
+        test.parse("1 + +1", "(+ 1 1)");
+        test.parse_err("+a");
+        test.parse_err("1.");
         test.parse("!true", "(! true)");
 
         test.parse("1 + 1", "(+ 1 1)");
@@ -164,12 +364,62 @@ mod tests {
         test.parse("1 == 1", "(== 1 1)");
         test.parse("1 != 1", "(!= 1 1)");
 
+        test.parse("()", "()");
+        test.parse("() == ()", "(== () ())");
+        test.parse("f(())", "(call f ())");
+        test.parse("(a, b)", "(tuple a b)");
+        test.parse("(a,)", "(tuple a)");
+        test.parse("(a, b + c,)", "(tuple a (+ b c))");
+        test.parse_err("(,)");
+
+        test.parse("[]", "(list)");
+        test.parse("[a, b + c,]", "(list a (+ b c))");
+        test.parse("a[0]", "(index a 0)");
+        test.parse("a[0][i]", "(index (index a 0) i)");
+        test.parse("a.b[0]", "(index (field a b) 0)");
+        test.parse("a[0].foo()", "(method-call (index a 0) foo)");
+        test.parse("f()[0]", "(index (call f) 0)");
+        test.parse_eq("-a[0]", "-(a[0])");
+        test.parse_err("a[]");
+        test.parse_err("a[0, 1]");
+
+        test.parse(r#"{ "a": 1 }"#, r#"(map "a" 1)"#);
+        test.parse(r#"{ "a": 1, "b": c + d, }"#, r#"(map "a" 1 "b" (+ c d))"#);
+        test.parse(r#"{ "a": 1 }["a"]"#, r#"(index (map "a" 1) "a")"#);
+        test.parse(r#"{ "a" }"#, r#"(block "a")"#);
+        test.parse("{ x }", "(block x)");
+        test.parse("{}", "(block ())");
+        test.parse("{ x: 1 }", "(map x 1)");
+        test.parse(
+            r#"{ "a" + b: c ? d : e }"#,
+            r#"(map (+ "a" b) (if c (block d) else (block e)))"#,
+        );
+        test.parse_err("{ x: 1; }");
+        test.parse_err(r#"{ "a": 1 "b": 2 }"#);
+
         test.parse("foo()", "(call foo)");
         test.parse("foo(1)", "(call foo 1)");
         test.parse("foo(1,)", "(call foo 1)");
         test.parse("foo(1, 2)", "(call foo 1 2)");
         test.parse("foo(1, 2,)", "(call foo 1 2)");
 
+        test.parse("a.foo()", "(method-call a foo)");
+        test.parse("a.foo(1, 2,)", "(method-call a foo 1 2)");
+        test.parse("a.foo().bar()", "(method-call (method-call a foo) bar)");
+        test.parse("foo().bar(1)", "(method-call (call foo) bar 1)");
+        test.parse_eq("-a.foo()", "-(a.foo())");
+
+        test.parse("a.foo", "(field a foo)");
+        test.parse("a.foo.bar", "(field (field a foo) bar)");
+        test.parse("a.foo().bar", "(field (method-call a foo) bar)");
+        test.parse("a.foo.bar()", "(method-call (field a foo) bar)");
+        test.parse("(a.foo)()", "(call (field a foo))");
+        test.parse("a.foo()()", "(call (method-call a foo))");
+        test.parse_eq("-a.foo", "-(a.foo)");
+        test.parse_eq("a.foo + b.bar()", "(a.foo) + (b.bar())");
+        test.parse_err("a.");
+        test.parse_err("a.1");
+
         test.parse_eq("-f()", "-(f())");
         test.parse_eq("--a", "-(-a)");
         test.parse_eq("-a * b", "(-a) * b");
@@ -179,16 +429,154 @@ mod tests {
         test.parse_eq("a + b + c", "(a + b) + c");
         test.parse_eq("a >= b + c", "a >= (b + c)");
         test.parse_eq("a + b >= c", "(a + b) >= c");
+        test.parse_eq("a ? b : c ? d : e", "a ? b : (c ? d : e)");
+        test.parse_eq(
+            "a ? b : c ? d : e",
+            "if a { b } else { if c { d } else { e } }",
+        );
+        test.parse_eq("a == b ? c + d : e", "(a == b) ? (c + d) : e");
         // test.parse_eq("a >= b >= c", "???");
         // test.parse_eq("a == b >= c", "???");
         // test.parse_eq("a >= b == c", "???");
         // test.parse_eq("a == b == c", "???");
     }
 
+    #[test]
+    fn test_number_literal() {
+        fn parse(input: &str) -> Result<'_, &str> {
+            let (tokens, docs) = lex(input);
+            NumberParser::new().parse(&docs, tokens)
+        }
+
+        let test = TestParser::new(parse);
+
+        test.parse("0", r#""0""#);
+        test.parse("1.50", r#""1.50""#);
+        for literal in ["inf", "nan", "0x1.8p3", "0x10", "1e3", "1.", ".5", "-1"] {
+            test.parse_err(literal);
+        }
+
+        // as expressions, these are identifiers or fail to parse
+        fn expr(input: &str) -> Result<'_, crate::ast::Expression<'_>> {
+            let (tokens, docs) = lex(input);
+            ExpressionParser::new().parse(&docs, tokens)
+        }
+
+        assert_eq!(format!("{:?}", expr("inf").unwrap()), "inf");
+        expr("0x1.8p3").unwrap_err();
+        expr("1e3").unwrap_err();
+    }
+
+    #[test]
+    fn test_string_literal() {
+        assert_eq!(string_from_literal(r#""a\tb""#).unwrap(), "a\tb");
+        assert_eq!(string_from_literal("\"foo\\\n    bar\"").unwrap(), "foobar");
+        assert_eq!(
+            string_from_literal("\"foo \\\r\n\tbar\"").unwrap(),
+            "foo bar"
+        );
+        assert_eq!(string_from_literal("\"foo\\\n\n  \\n\"").unwrap(), "foo\n");
+        assert_eq!(string_from_literal("\"foo\\\rbar\"").unwrap(), "foobar");
+
+        // the lexer must accept line continuations in string literals as well
+        for line_break in ["\n", "\r\n", "\r"] {
+            let source = format!("fn main() {{ \"foo\\{line_break}    bar\" }}");
+            parse_source_file(&source).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bytes_literal() {
+        assert_eq!(bytes_from_literal(r#"b"a\tb""#).unwrap(), b"a\tb");
+        assert_eq!(
+            bytes_from_literal(r#"b"\x00\xff\x7F""#).unwrap(),
+            [0, 255, 127]
+        );
+        assert!(matches!(
+            bytes_from_literal(r#"b"\x1""#),
+            Err(ParseStringError::IllegalEscapeSequence('x'))
+        ));
+        assert!(matches!(
+            bytes_from_literal(r#"b"ä""#),
+            Err(ParseStringError::NonAsciiByte('ä'))
+        ));
+
+        fn expr(input: &str) -> Result<'_, crate::ast::Expression<'_>> {
+            let (tokens, docs) = lex(input);
+            ExpressionParser::new().parse(&docs, tokens)
+        }
+
+        assert_eq!(format!("{:?}", expr(r#"b"\x00""#).unwrap()), r#"b"\x00""#);
+        assert_eq!(format!("{:?}", expr("b").unwrap()), "b");
+        for literal in [r#"b"ä""#, r#"b"\x1""#, r#"b"\xg0""#] {
+            expr(literal).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        fn parse(input: &str) -> Result<'_, crate::ast::Expression<'_>> {
+            let (tokens, docs) = lex(input);
+            ExpressionParser::new().parse(&docs, tokens)
+        }
+
+        let test = TestParser::new(parse);
+
+        test.parse(r#""x={v}""#, r#"(interpolate "x=" v)"#);
+        test.parse(r#""{v}""#, "(interpolate v)");
+        test.parse(r#""a\t{b + 1}c""#, r#"(interpolate "a\t" (+ b 1) "c")"#);
+        test.parse(r#""{{}}""#, r#"(interpolate "{}")"#);
+        test.parse(r#""{ {a} }""#, "(interpolate (block a))");
+        // strings without braces are not interpolated
+        test.parse(r#""x""#, r#""x""#);
+
+        for literal in [r#""{""#, r#""}""#, r#""{}""#, r#""{a}}""#, r#""{{a}""#] {
+            test.parse_err(literal);
+        }
+
+        // malformed braces are reported as such
+        let error = parse(r#""a{b""#).unwrap_err();
+        assert!(matches!(
+            error,
+            ParseError::User {
+                error: ParseStringError::UnclosedInterpolation
+            }
+        ));
+        assert!(
+            error.to_string().contains("unclosed '{' in string literal"),
+            "{error}"
+        );
+        let error = parse(r#""{a}}""#).unwrap_err();
+        assert!(matches!(
+            error,
+            ParseError::User {
+                error: ParseStringError::UnmatchedBrace
+            }
+        ));
+        assert!(
+            error
+                .to_string()
+                .contains("unmatched '}' in string literal"),
+            "{error}"
+        );
+
+        // errors in embedded expressions are located in the whole source
+        let source = r#""ab{1 2}""#;
+        let Err(ParseError::UnrecognizedToken {
+            token: (start, _, end),
+            ..
+        }) = parse(source)
+        else {
+            panic!("expected an unrecognized token");
+        };
+        assert_eq!(&source[start..end], "2");
+    }
+
     #[test]
     fn test_stmt_parser() {
-        fn parse<'input>(input: &'input str) -> Result<crate::ast::Statement<'input>> {
-            StatementParser::new().parse(input)
+        fn parse<'input>(input: &'input str) -> Result<'input, crate::ast::Statement<'input>> {
+            let (tokens, docs) = lex(input);
+            StatementParser::new().parse(&docs, tokens)
         }
 
         let test = TestParser::new(parse);
@@ -203,6 +591,65 @@ mod tests {
 
         test.parse("fn foo() {}", "(fn foo (block ()))");
 
+        test.parse("return a, b;", "(return (tuple a b))");
+        test.parse_eq("return a, b;", "return (a, b);");
+        test.parse("let (a, mut b) = f();", "(let ((a) (mut b)) (call f))");
+        test.parse("let (a,) = b;", "(let ((a)) b)");
+        test.parse_err("let (a, b);");
+
+        test.parse("break;", "(break)");
+        test.parse("break 'outer;", "(break 'outer)");
+        test.parse("break 'outer 1;", "(break 'outer 1)");
+        test.parse("continue 'outer;", "(continue 'outer)");
+        test.parse(
+            "'outer: loop { break 'outer; };",
+            "(loop 'outer (block (break 'outer) ()))",
+        );
+        test.parse_err("'outer loop {};");
+        test.parse_err("continue outer;");
+
+        // jumps with a value can be used in place of other values
+        test.parse("a + (return 1);", "(+ a (return 1))");
+        test.parse(
+            "let x = a ? b : break 'outer 0;",
+            "(let (x) (if a (block b) else (block (break 'outer 0))))",
+        );
+        test.parse_err("a + return 1;");
+        test.parse_err("f(return);");
+
         test.parse_err("22");
     }
+
+    #[test]
+    fn test_operator_display() {
+        use crate::ast::{BinaryOperator::*, UnaryOperator::*};
+
+        let binary = [
+            (Multiply, "*"),
+            (Divide, "/"),
+            (Modulo, "%"),
+            (Add, "+"),
+            (Subtract, "-"),
+            (RightShift, ">>"),
+            (LeftShift, "<<"),
+            (BitAnd, "&"),
+            (BitXor, "^"),
+            (BitOr, "|"),
+            (Equals, "=="),
+            (NotEquals, "!="),
+            (Greater, ">"),
+            (GreaterEquals, ">="),
+            (Less, "<"),
+            (LessEquals, "<="),
+        ];
+        for (operator, symbol) in binary {
+            assert_eq!(operator.to_string(), symbol);
+            assert_eq!(format!("{operator:?}"), symbol);
+        }
+
+        for (operator, symbol) in [(Negate, "-"), (Not, "!")] {
+            assert_eq!(operator.to_string(), symbol);
+            assert_eq!(format!("{operator:?}"), symbol);
+        }
+    }
 }
@@ -1,5 +1,17 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use lalrpop_util::ParseError;
+
+use super::grammar::ExpressionParser;
+use super::lex;
+use super::Result as ParseResult;
+use crate::ast;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseStringError {
+    #[error("byte string literal without leading `b`")]
+    MissingBytesPrefix,
     #[error("string literal without opening double quote")]
     MissingOpenQuote,
     #[error("unfinished escape sequence")]
@@ -10,6 +22,22 @@ pub enum ParseStringError {
     MissingClosedQuote,
     #[error("string literal with trailing content after the closing double quote")]
     TrailingContent,
+    #[error("non-ASCII character in byte string literal: {0:?}")]
+    NonAsciiByte(char),
+    #[error("unclosed '{{' in string literal; use '{{{{' for a literal brace")]
+    UnclosedInterpolation,
+    #[error("unmatched '}}' in string literal; use '}}}}' for a literal brace")]
+    UnmatchedBrace,
+}
+
+/// A part of an interpolated string literal, see [`split_interpolation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment<'a> {
+    /// Literal text, with escape sequences and doubled braces resolved
+    Literal(String),
+    /// The source of an embedded expression, and its byte offset in the
+    /// literal
+    Expression(usize, &'a str),
 }
 
 pub fn string_from_literal(literal: &str) -> Result<String, ParseStringError> {
@@ -17,28 +45,119 @@ pub fn string_from_literal(literal: &str) -> Result<String, ParseStringError> {
 
     let mut string = String::with_capacity(literal.len());
 
-    let mut iter = literal.chars();
+    let mut iter = literal.char_indices().peekable();
     iter.next()
-        .filter(|&ch| ch == '"')
+        .filter(|&(_, ch)| ch == '"')
         .ok_or(MissingOpenQuote)?;
-    while let Some(ch) = iter.next() {
+    while let Some((_, ch)) = iter.next() {
         match ch {
+            '\\' => unescape(&mut iter, &mut string)?,
+            '"' => {
+                if iter.next().is_some() {
+                    Err(TrailingContent)?;
+                }
+
+                return Ok(string);
+            }
+            _ => {
+                string.push(ch);
+            }
+        }
+    }
+
+    Err(MissingClosedQuote)
+}
+
+/// Converts a byte string literal such as `b"a\x00"` into its bytes. Besides
+/// the escape sequences of string literals, it may contain `\xNN` escapes for
+/// arbitrary bytes; all other characters must be ASCII.
+pub fn bytes_from_literal(literal: &str) -> Result<Vec<u8>, ParseStringError> {
+    use ParseStringError::*;
+
+    let mut bytes = Vec::with_capacity(literal.len());
+
+    let literal = literal.strip_prefix('b').ok_or(MissingBytesPrefix)?;
+    let mut iter = literal.char_indices().peekable();
+    iter.next()
+        .filter(|&(_, ch)| ch == '"')
+        .ok_or(MissingOpenQuote)?;
+    while let Some((_, ch)) = iter.next() {
+        match ch {
+            '\\' if iter.next_if(|&(_, ch)| ch == 'x').is_some() => {
+                let digits: String = iter.by_ref().take(2).map(|(_, ch)| ch).collect();
+                let byte = Some(digits)
+                    .filter(|digits| digits.len() == 2)
+                    .filter(|digits| digits.chars().all(|ch| ch.is_ascii_hexdigit()))
+                    .and_then(|digits| u8::from_str_radix(&digits, 16).ok());
+                bytes.push(byte.ok_or(IllegalEscapeSequence('x'))?);
+            }
             '\\' => {
-                let ch = iter.next().ok_or(UnfinishedEscapeSequence)?;
-                match ch {
-                    '\\' | '\"' => string.push(ch),
-                    'n' => string.push('\n'),
-                    'r' => string.push('\r'),
-                    't' => string.push('\t'),
-                    _ => Err(IllegalEscapeSequence(ch))?,
+                let mut string = String::new();
+                unescape(&mut iter, &mut string)?;
+                bytes.extend_from_slice(string.as_bytes());
+            }
+            '"' => {
+                if iter.next().is_some() {
+                    Err(TrailingContent)?;
+                }
+
+                return Ok(bytes);
+            }
+            _ if ch.is_ascii() => bytes.push(ch as u8),
+            _ => Err(NonAsciiByte(ch))?,
+        }
+    }
+
+    Err(MissingClosedQuote)
+}
+
+/// Splits a string literal such as `"x={x}"` into literal text and embedded
+/// expressions. Braces are escaped by doubling them, i.e. `{{` and `}}`; the
+/// embedded expressions are not parsed here.
+pub fn split_interpolation(literal: &str) -> Result<Vec<Segment<'_>>, ParseStringError> {
+    use ParseStringError::*;
+
+    let mut segments = Vec::new();
+    let mut string = String::new();
+
+    let mut iter = literal.char_indices().peekable();
+    iter.next()
+        .filter(|&(_, ch)| ch == '"')
+        .ok_or(MissingOpenQuote)?;
+    while let Some((index, ch)) = iter.next() {
+        match ch {
+            '\\' => unescape(&mut iter, &mut string)?,
+            '{' if iter.next_if(|&(_, ch)| ch == '{').is_some() => string.push('{'),
+            '}' if iter.next_if(|&(_, ch)| ch == '}').is_some() => string.push('}'),
+            '}' => Err(UnmatchedBrace)?,
+            '{' => {
+                if !string.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut string)));
                 }
+
+                // the expression may contain braces itself, e.g. for a block
+                let start = index + 1;
+                let mut depth = 0;
+                let end = loop {
+                    match iter.next() {
+                        Some((_, '{')) => depth += 1,
+                        Some((end, '}')) if depth == 0 => break end,
+                        Some((_, '}')) => depth -= 1,
+                        Some((_, '"')) | None => Err(UnclosedInterpolation)?,
+                        Some(_) => {}
+                    }
+                };
+                segments.push(Segment::Expression(start, &literal[start..end]));
             }
             '"' => {
                 if iter.next().is_some() {
                     Err(TrailingContent)?;
                 }
 
-                return Ok(string);
+                if !string.is_empty() {
+                    segments.push(Segment::Literal(string));
+                }
+                return Ok(segments);
             }
             _ => {
                 string.push(ch);
@@ -48,3 +167,58 @@ pub fn string_from_literal(literal: &str) -> Result<String, ParseStringError> {
 
     Err(MissingClosedQuote)
 }
+
+/// Turns a string literal token at `location` into an expression. Literals
+/// without braces stay plain strings, others become interpolations, whose
+/// embedded expressions are parsed with locations relative to the whole
+/// source.
+pub(super) fn string_expression(
+    location: usize,
+    literal: &str,
+) -> ParseResult<'_, ast::Expression<'_>> {
+    if !literal.contains(['{', '}']) {
+        return Ok(ast::Expression::String(literal));
+    }
+
+    let segments = split_interpolation(literal).map_err(|error| ParseError::User { error })?;
+    let parts = segments
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Literal(value) => Ok(ast::InterpolationPart::Literal(value)),
+            Segment::Expression(offset, source) => {
+                let (tokens, docs) = lex(source);
+                let expr = ExpressionParser::new()
+                    .parse(&docs, tokens)
+                    .map_err(|error| error.map_location(|l| location + offset + l))?;
+                Ok(ast::InterpolationPart::Expression(expr))
+            }
+        })
+        .collect::<ParseResult<_>>()?;
+    Ok(ast::Interpolation::new(parts).into())
+}
+
+/// Resolves the escape sequence following a backslash.
+fn unescape(
+    iter: &mut Peekable<CharIndices<'_>>,
+    string: &mut String,
+) -> Result<(), ParseStringError> {
+    use ParseStringError::*;
+
+    let (_, ch) = iter.next().ok_or(UnfinishedEscapeSequence)?;
+    match ch {
+        '\\' | '\"' => string.push(ch),
+        'n' => string.push('\n'),
+        'r' => string.push('\r'),
+        't' => string.push('\t'),
+        // a line continuation: like in Rust, skip the line break
+        // as well as the next line's leading whitespace
+        '\n' | '\r' => {
+            while iter
+                .next_if(|&(_, ch)| matches!(ch, ' ' | '\t' | '\n' | '\r'))
+                .is_some()
+            {}
+        }
+        _ => Err(IllegalEscapeSequence(ch))?,
+    }
+    Ok(())
+}
@@ -13,6 +13,7 @@ pub enum Constant {
     Number(Number),
     String(String),
     Function(Function),
+    Bytes(Vec<u8>),
 }
 
 impl From<Number> for Constant {
@@ -33,6 +34,12 @@ impl From<Function> for Constant {
     }
 }
 
+impl From<Vec<u8>> for Constant {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
 impl Constant {
     pub(crate) fn fmt_with<M: ModuleFormat>(
         &self,
@@ -46,6 +53,7 @@ impl Constant {
             Number(value) => fmt::Display::fmt(value, f),
             String(value) => value.fmt(f),
             Function(value) => value.fmt_with(f, module),
+            Bytes(value) => write!(f, "b\"{}\"", value.escape_ascii()),
         }
     }
 }
@@ -59,18 +67,30 @@ impl fmt::Debug for Constant {
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct Function {
     arity: usize,
+    max_stack: usize,
     body: Vec<Instruction>,
 }
 
 impl Function {
-    pub fn new(arity: usize, body: Vec<Instruction>) -> Self {
-        Self { arity, body }
+    pub fn new(arity: usize, max_stack: usize, body: Vec<Instruction>) -> Self {
+        Self {
+            arity,
+            max_stack,
+            body,
+        }
     }
 
     pub fn arity(&self) -> usize {
         self.arity
     }
 
+    /// The maximum height the function's stack frame reaches, including its
+    /// parameters, as computed from the instructions' stack effects while
+    /// compiling.
+    pub fn max_stack(&self) -> usize {
+        self.max_stack
+    }
+
     pub fn body(&self) -> &[Instruction] {
         &self.body
     }
@@ -89,7 +109,7 @@ impl Function {
         }
 
         if f.alternate() {
-            f.write_str(") {\n")?;
+            writeln!(f, ") {{ -- max stack {}", self.max_stack)?;
             self.fmt_body_with(f, module)?;
             f.write_str("\n           }")?;
         } else {
@@ -1,5 +1,6 @@
 use std::fmt;
 use std::io::Error as IoError;
+use std::path::PathBuf;
 
 use bigdecimal::ParseBigDecimalError;
 use lalrpop_util::ParseError as LalrpopParseError;
@@ -11,13 +12,31 @@ pub enum Error {
     #[error("IO Error: {0}")]
     Io(#[from] IoError),
     #[error("Parse Error: {0}")]
-    Parse(LalrpopParseError<usize, Token, &'static str>),
+    Parse(LalrpopParseError<usize, Token, ParseStringError>),
     #[error("Invalid assignment target")]
     InvalidAssignmentTarget,
     #[error("Assignment to immutable variable")]
     ImmutableVariable,
+    #[error("Use of possibly uninitialized variable {0}")]
+    UseBeforeInit(String),
     #[error("break/continue statement without enclosing loop")]
     NoLoopToExit,
+    #[error("break/continue statement with unknown label {0}")]
+    UnknownLabel(String),
+    #[error("Wrong field number for {0}; expected {1}, got {2}")]
+    WrongFieldNumber(String, usize, usize),
+    #[error("Wrong argument number for {0}; expected {1}, got {2}")]
+    WrongArgumentNumber(String, usize, usize),
+    #[error("Duplicate struct field: {0}")]
+    DuplicateField(String),
+    #[error("Cyclic import of {}", .0.display())]
+    CyclicImport(PathBuf),
+    #[error("Imported name not found or not public: {0}")]
+    UnresolvedImport(String),
+    #[error("Name defined more than once: {0}")]
+    DuplicateGlobal(String),
+    #[error("impl block for undeclared struct type {0}")]
+    UndeclaredStructType(String),
     #[error("Unsupported language construct: {0}")]
     Unsupported(&'static str),
     #[error("Internal Error: {0}")]
@@ -26,16 +45,16 @@ pub enum Error {
 
 impl From<ParseError<'_>> for Error {
     fn from(error: ParseError<'_>) -> Self {
-        Error::Parse(error.map_token(|t| Token(t.0, t.1.to_string())))
+        Error::Parse(error.map_token(|t| Token(t.to_string())))
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Token(pub usize, pub String);
+pub struct Token(pub String);
 
 impl fmt::Display for Token {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.1, formatter)
+        fmt::Display::fmt(&self.0, formatter)
     }
 }
 
@@ -3,10 +3,11 @@ mod error;
 mod instruction;
 mod writer;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::fs;
 use std::io::Write;
-use std::iter;
+use std::path::{Path, PathBuf};
 use std::slice::SliceIndex;
 use std::str::FromStr;
 
@@ -14,12 +15,16 @@ use sprachli_fmt::{FormatterExt, IteratorExt, ModuleFormat};
 
 use crate::ast;
 use crate::bytecode::instruction::{InlineConstant, Instruction, Offset};
-use crate::parser::{parse_source_file, string_from_literal};
-use constant::{Constant, Function, Number};
+use crate::parser::{bytes_from_literal, parse_source_file, string_from_literal};
+use constant::Number;
 use instruction::{InstructionItem, PlaceholderKind};
 
+pub use constant::{Constant, Function};
 pub use error::{Error, InternalError, Result};
-pub use writer::write_bytecode;
+pub use writer::{write_bytecode, write_bytecode_with_config};
+
+/// The minimum number of branches for compiling an if chain to a jump table
+const MIN_SWITCH_CASES: usize = 4;
 
 pub fn compile_source_file<W: Write>(w: &mut W, source: &str) -> Result<()> {
     let ast = parse_source_file(source)?;
@@ -37,6 +42,7 @@ pub struct Module {
     constants: Vec<Constant>,
     globals: BTreeMap<usize, usize>,
     struct_types: BTreeMap<usize, StructType>,
+    methods: BTreeMap<(usize, usize), usize>,
 }
 
 impl Module {
@@ -44,6 +50,39 @@ impl Module {
         Self::try_from(ast)
     }
 
+    /// Compiles the given source file, resolving `use` declarations to `.spr`
+    /// files relative to `base_dir`: `use foo;` compiles `foo.spr` into this
+    /// module, `use foo::bar;` additionally requires `bar` to be a `pub`
+    /// declaration of that file, and `use foo::bar as baz;` also makes it
+    /// available as `baz`.
+    ///
+    /// Since globals are resolved by name at runtime, all declarations of an
+    /// imported file end up in the module's global namespace; only `pub`
+    /// declarations may be named in a `use` declaration, though.
+    pub fn with_base_dir<P: AsRef<Path>>(ast: ast::SourceFile, base_dir: P) -> Result<Module> {
+        let base_dir = base_dir.as_ref();
+        let mut c = Compiler::new();
+        c.base_dir = Some(base_dir.to_path_buf());
+        c.root_dir = base_dir.canonicalize().ok();
+        c.visit_source_file(ast)?;
+        Ok(c.into())
+    }
+
+    /// Compiles the source file at the given path like
+    /// [`with_base_dir`](Self::with_base_dir), resolving `use` declarations
+    /// relative to the file's directory. The file counts as being imported, so
+    /// that an import cycle back to it is detected right away.
+    pub fn with_source_path<P: AsRef<Path>>(ast: ast::SourceFile, path: P) -> Result<Module> {
+        let path = path.as_ref().canonicalize()?;
+        let base_dir = path.parent().map(Path::to_path_buf);
+        let mut c = Compiler::new();
+        c.base_dir = base_dir.clone();
+        c.root_dir = base_dir;
+        c.importing.push(path);
+        c.visit_source_file(ast)?;
+        Ok(c.into())
+    }
+
     pub fn constants(&self) -> &[Constant] {
         &self.constants
     }
@@ -52,9 +91,118 @@ impl Module {
         &self.globals
     }
 
+    /// Returns the value of the named global, if there is one.
+    pub fn global(&self, name: &str) -> Option<&Constant> {
+        let (_, &index) = self.globals.iter().find(
+            |(&key, _)| matches!(&self.constants[key], Constant::String(key) if key == name),
+        )?;
+        self.constants.get(index)
+    }
+
     pub fn struct_types(&self) -> &BTreeMap<usize, StructType> {
         &self.struct_types
     }
+
+    pub fn methods(&self) -> &BTreeMap<(usize, usize), usize> {
+        &self.methods
+    }
+
+    /// Links a separately compiled module into this one. The other module's
+    /// constants are appended, and its globals, struct types and methods are
+    /// merged by name. References to globals are by name and resolved at
+    /// runtime, so a function may call one defined in the other module.
+    /// Defining the same name in both modules is an error.
+    pub fn link(mut self, other: Module) -> Result<Module> {
+        fn check(
+            mut names: impl Iterator<Item = String>,
+            existing: impl Iterator<Item = String>,
+        ) -> Result<()> {
+            let existing: HashSet<_> = existing.collect();
+            match names.find(|name| existing.contains(name)) {
+                Some(name) => Err(Error::DuplicateGlobal(name)),
+                None => Ok(()),
+            }
+        }
+
+        let name = |module: &Module, index: usize| match module.constants.get(index) {
+            Some(Constant::String(name)) => name.clone(),
+            _ => format!("#{index}"),
+        };
+        check(
+            other.globals.keys().map(|&key| name(&other, key)),
+            self.globals.keys().map(|&key| name(&self, key)),
+        )?;
+        check(
+            other.struct_types.keys().map(|&key| name(&other, key)),
+            self.struct_types.keys().map(|&key| name(&self, key)),
+        )?;
+        // globals and struct types share a namespace
+        check(
+            other.globals.keys().map(|&key| name(&other, key)),
+            self.struct_types.keys().map(|&key| name(&self, key)),
+        )?;
+        check(
+            other.struct_types.keys().map(|&key| name(&other, key)),
+            self.globals.keys().map(|&key| name(&self, key)),
+        )?;
+        let method_name = |module: &Module, (struct_type, key): (usize, usize)| {
+            format!("{}::{}", name(module, struct_type), name(module, key))
+        };
+        check(
+            other.methods.keys().map(|&key| method_name(&other, key)),
+            self.methods.keys().map(|&key| method_name(&self, key)),
+        )?;
+
+        let offset = self.constants.len();
+        for constant in other.constants {
+            let constant = match constant {
+                Constant::Function(function) => {
+                    let body = function
+                        .body()
+                        .iter()
+                        .map(|&ins| relocate(ins, offset))
+                        .collect();
+                    Constant::Function(Function::new(function.arity(), function.max_stack(), body))
+                }
+                constant => constant,
+            };
+            self.constants.push(constant);
+        }
+        for (key, value) in other.globals {
+            self.globals.insert(key + offset, value + offset);
+        }
+        for (key, struct_type) in other.struct_types {
+            let struct_type = match struct_type {
+                StructType::Named(fields) => {
+                    StructType::Named(fields.into_iter().map(|field| field + offset).collect())
+                }
+                struct_type => struct_type,
+            };
+            self.struct_types.insert(key + offset, struct_type);
+        }
+        for ((struct_type, key), value) in other.methods {
+            self.methods
+                .insert((struct_type + offset, key + offset), value + offset);
+        }
+
+        Ok(self)
+    }
+}
+
+/// Shifts the constant indices an instruction refers to by `offset`.
+fn relocate(ins: Instruction, offset: usize) -> Instruction {
+    use Instruction::*;
+
+    match ins {
+        Instruction::Constant(index) => Instruction::Constant(index + offset),
+        LoadFunction(index) => LoadFunction(index + offset),
+        LoadNamed(index) => LoadNamed(index + offset),
+        StoreNamed(index) => StoreNamed(index + offset),
+        LoadMethod(index) => LoadMethod(index + offset),
+        LoadNamedField(index) => LoadNamedField(index + offset),
+        StoreNamedField(index) => StoreNamedField(index + offset),
+        ins => ins,
+    }
 }
 
 impl TryFrom<ast::SourceFile<'_>> for Module {
@@ -73,12 +221,14 @@ impl From<Compiler> for Module {
             constants,
             globals,
             struct_types,
+            methods,
             ..
         } = compiler;
         Self {
             constants,
             globals,
             struct_types,
+            methods,
         }
     }
 }
@@ -94,6 +244,10 @@ impl ModuleFormat for Module {
         };
         Some((constant, string))
     }
+
+    fn constant_count(&self) -> usize {
+        self.constants.len()
+    }
 }
 
 impl fmt::Debug for Module {
@@ -130,6 +284,17 @@ impl fmt::Debug for Module {
                 f.write_str("\n")?;
             }
             f.write_str("    },\n")?;
+            f.write_str("    methods: {\n")?;
+            for ((struct_type, name), index) in &self.methods {
+                f.write_str("        ")?;
+                f.fmt_constant_ident(self, *struct_type)?;
+                f.write_str("::")?;
+                f.fmt_constant_ident(self, *name)?;
+                write!(f, ": {index} -- ")?;
+                f.fmt_constant(self, *index)?;
+                f.write_str("\n")?;
+            }
+            f.write_str("    },\n")?;
             f.write_str("}")?;
             Ok(())
         } else {
@@ -137,6 +302,7 @@ impl fmt::Debug for Module {
                 .field("constants", &self.constants)
                 .field("globals", &self.globals)
                 .field("struct_types", &self.struct_types)
+                .field("methods", &self.methods)
                 .finish()
         }
     }
@@ -148,6 +314,17 @@ struct Compiler {
     constants_map: HashMap<Constant, usize>,
     struct_types: BTreeMap<usize, StructType>,
     globals: BTreeMap<usize, usize>,
+    methods: BTreeMap<(usize, usize), usize>,
+    base_dir: Option<PathBuf>,
+    /// The canonical directory of the root file, which private names of
+    /// imported files are made unique with
+    root_dir: Option<PathBuf>,
+    importing: Vec<PathBuf>,
+    imported: HashMap<PathBuf, Vec<String>>,
+    private_names: HashMap<String, String>,
+    /// The global names of all functions declared so far, including ones that
+    /// are not compiled yet
+    declared_functions: HashSet<String>,
 }
 
 impl Compiler {
@@ -170,14 +347,80 @@ impl Compiler {
         add_constant(constant.into())
     }
 
-    fn add_global<C: Into<Constant>>(&mut self, name: String, value: C) {
-        let name = self.add_constant(name);
+    /// Returns the name under which the named global of the file being
+    /// compiled is stored. Private declarations of an imported file are
+    /// prefixed with the file's name, which can't be written in source code,
+    /// so that other files can't refer to them.
+    fn global_name(&self, name: &str) -> String {
+        match self.private_names.get(name) {
+            Some(name) => name.clone(),
+            None => name.to_string(),
+        }
+    }
+
+    /// Returns whether the name refers to a global or struct type, regardless
+    /// of whether a function of that name was already compiled.
+    fn is_global(&self, name: &str) -> bool {
+        let name = self.global_name(name);
+        if self.declared_functions.contains(&name) {
+            return true;
+        }
+        self.constants_map
+            .get(&Constant::from(name))
+            .is_some_and(|name| {
+                self.globals.contains_key(name) || self.struct_types.contains_key(name)
+            })
+    }
+
+    /// Returns whether the name refers to a function declared in a file being
+    /// compiled, regardless of whether it was already compiled.
+    fn is_function(&self, name: &str) -> bool {
+        self.declared_functions.contains(&self.global_name(name))
+    }
+
+    /// Returns the number of fields of the named struct type, if there is
+    /// one. Struct types are collected before any function is compiled, so
+    /// this doesn't depend on the order of declarations.
+    fn struct_field_count(&self, name: &str) -> Option<usize> {
+        use StructType::*;
+
+        let name = self
+            .constants_map
+            .get(&Constant::from(self.global_name(name)))?;
+        let count = match self.struct_types.get(name)? {
+            Empty => 0,
+            Positional(count) => *count,
+            Named(fields) => fields.len(),
+        };
+        Some(count)
+    }
+
+    fn add_global<C: Into<Constant>>(&mut self, name: String, value: C) -> Result<()> {
+        let index = self.add_constant(name.clone());
+        if self.globals.contains_key(&index) || self.struct_types.contains_key(&index) {
+            Err(Error::DuplicateGlobal(name))?;
+        }
         let value = self.add_constant(value);
-        self.globals.insert(name, value);
+        self.globals.insert(index, value);
+        Ok(())
     }
 
     pub fn visit_source_file(&mut self, ast: ast::SourceFile) -> Result<()> {
-        for declaration in ast.declarations {
+        // imports and struct types come first, so that all struct types are
+        // known when function bodies are compiled
+        let (types, rest): (Vec<_>, Vec<_>) =
+            ast.declarations.into_iter().partition(|declaration| {
+                matches!(
+                    declaration,
+                    ast::Declaration::Use(_) | ast::Declaration::Struct(_)
+                )
+            });
+        for declaration in &rest {
+            if let ast::Declaration::Fn(decl) = declaration {
+                self.declared_functions.insert(self.global_name(decl.name));
+            }
+        }
+        for declaration in types.into_iter().chain(rest) {
             self.visit_declaration(declaration)?;
         }
 
@@ -188,38 +431,177 @@ impl Compiler {
         use ast::Declaration::*;
 
         match declaration {
-            Use(_decl) => Err(Error::Unsupported("use declaration"))?,
+            Use(decl) => self.visit_use(decl)?,
             Fn(decl) => self.visit_fn(decl)?,
             Struct(decl) => self.visit_struct_type(decl)?,
             Mixin(_decl) => Err(Error::Unsupported("mixin"))?,
-            Impl(_decl) => Err(Error::Unsupported("impl block"))?,
+            Impl(decl) => self.visit_impl(decl)?,
         }
 
         Ok(())
     }
 
+    fn visit_use(&mut self, decl: ast::Use) -> Result<()> {
+        use ast::PathSegment::*;
+
+        let ast::Use { path, name, .. } = decl;
+        let base_dir = self
+            .base_dir
+            .clone()
+            .ok_or(Error::Unsupported("use declaration without base directory"))?;
+
+        match path.segments.as_slice() {
+            [Name(file)] => {
+                if name.is_some() {
+                    Err(Error::Unsupported("renaming an imported file"))?;
+                }
+                self.import_file(base_dir.join(format!("{file}.spr")))?;
+            }
+            [Name(file), Name(item)] => {
+                let public = self.import_file(base_dir.join(format!("{file}.spr")))?;
+                if !public.iter().any(|public| public == item) {
+                    Err(Error::UnresolvedImport(format!("{path:?}")))?;
+                }
+                if let Some(name) = name {
+                    self.add_alias(item, name)?;
+                }
+            }
+            _ => Err(Error::Unsupported(
+                "use path other than `file` or `file::name`",
+            ))?,
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the given file into this module, if that didn't already happen,
+    /// and returns the names of its public declarations.
+    fn import_file(&mut self, path: PathBuf) -> Result<Vec<String>> {
+        let path = path.canonicalize()?;
+        if self.importing.contains(&path) {
+            return Err(Error::CyclicImport(path));
+        }
+        if let Some(public) = self.imported.get(&path) {
+            return Ok(public.clone());
+        }
+
+        let source = fs::read_to_string(&path)?;
+        let ast = parse_source_file(&source)?;
+        // files are identified by their path, as different directories may
+        // contain files with the same name
+        let file = self
+            .root_dir
+            .as_deref()
+            .and_then(|root_dir| path.strip_prefix(root_dir).ok())
+            .unwrap_or(&path)
+            .with_extension("");
+        let file = file.display();
+        let mut public = Vec::new();
+        let mut private_names = HashMap::new();
+        for decl in &ast.declarations {
+            let (visibility, name) = match decl {
+                ast::Declaration::Fn(decl) => (decl.visibility, decl.name),
+                ast::Declaration::Struct(decl) => (decl.visibility, decl.name),
+                _ => continue,
+            };
+            match visibility {
+                ast::Visibility::Public => public.push(name.to_string()),
+                ast::Visibility::Private => {
+                    private_names.insert(name.to_string(), format!("{file}::{name}"));
+                }
+            }
+        }
+
+        // paths in the imported file are relative to that file
+        let base_dir = path.parent().map(Path::to_path_buf);
+        let base_dir = std::mem::replace(&mut self.base_dir, base_dir);
+        let private_names = std::mem::replace(&mut self.private_names, private_names);
+        self.importing.push(path.clone());
+        let result = self.visit_source_file(ast);
+        self.importing.pop();
+        self.private_names = private_names;
+        self.base_dir = base_dir;
+        result?;
+
+        self.imported.insert(path, public.clone());
+        Ok(public)
+    }
+
+    fn add_alias(&mut self, name: &str, alias: &str) -> Result<()> {
+        let name = self.add_constant(name.to_string());
+        let index = self.add_constant(alias.to_string());
+        if let Some(&value) = self.globals.get(&name) {
+            if self.globals.insert(index, value).is_some() {
+                Err(Error::DuplicateGlobal(alias.to_string()))?;
+            }
+        }
+        if let Some(struct_type) = self.struct_types.get(&name).cloned() {
+            if self.struct_types.insert(index, struct_type).is_some() {
+                Err(Error::DuplicateGlobal(alias.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
     fn visit_fn(&mut self, decl: ast::FnDeclaration) -> Result<()> {
         let ast::FnDeclaration { name, trunk, .. } = decl;
         let function = InstructionCompiler::new(self).visit_fn_trunk(trunk)?;
-        self.add_global(name.to_string(), function);
+        self.add_global(self.global_name(name), function)
+    }
+
+    fn visit_impl(&mut self, decl: ast::Impl) -> Result<()> {
+        let ast::Impl {
+            name,
+            inheritances,
+            methods,
+        } = decl;
+        if !inheritances.is_empty() {
+            Err(Error::Unsupported("impl block with inheritances"))?;
+        }
+
+        // struct types are visited first, so the struct type must be known
+        let struct_name = self.global_name(name);
+        let struct_type = self.add_constant(struct_name.clone());
+        if !self.struct_types.contains_key(&struct_type) {
+            Err(Error::UndeclaredStructType(struct_name.clone()))?;
+        }
+        for method in methods {
+            let ast::FnDeclaration { name, trunk, .. } = method;
+            let function = InstructionCompiler::new(self).visit_fn_trunk(trunk)?;
+            let key = (struct_type, self.add_constant(name.to_string()));
+            let function = self.add_constant(function);
+            if self.methods.insert(key, function).is_some() {
+                Err(Error::DuplicateGlobal(format!("{struct_name}::{name}")))?;
+            }
+        }
         Ok(())
     }
 
     fn visit_struct_type(&mut self, decl: ast::Struct) -> Result<()> {
         let ast::Struct { name, members, .. } = decl;
-        let name = self.add_constant(name.to_string());
+        let name = self.global_name(name);
+        let index = self.add_constant(name.clone());
+        if self.struct_types.contains_key(&index) || self.globals.contains_key(&index) {
+            Err(Error::DuplicateGlobal(name))?;
+        }
         let struct_type = match members {
             ast::StructMembers::Empty => StructType::Empty,
             ast::StructMembers::Positional(fields) => StructType::Positional(fields.len()),
             ast::StructMembers::Named(fields) => {
+                let mut seen = HashSet::new();
                 let fields = fields
                     .iter()
-                    .map(|field| self.add_constant(field.to_string()))
-                    .collect();
+                    .map(|&field| {
+                        if !seen.insert(field) {
+                            return Err(Error::DuplicateField(field.to_string()));
+                        }
+                        Ok(self.add_constant(field.to_string()))
+                    })
+                    .collect::<Result<_>>()?;
                 StructType::Named(fields)
             }
         };
-        self.struct_types.insert(name, struct_type);
+        self.struct_types.insert(index, struct_type);
         Ok(())
     }
 }
@@ -287,7 +669,16 @@ impl fmt::Debug for StructType {
 struct InstructionCompiler<'a, 'input> {
     compiler: &'a mut Compiler,
     stack: Vec<Option<ast::Variable<'input>>>,
-    jump_targets: Vec<JumpTarget>,
+    /// the highest the stack has been so far
+    max_stack: usize,
+    /// stack indices of locals that were declared without an initializer and
+    /// may not have been assigned yet at the current point of the code
+    uninitialized: BTreeSet<usize>,
+    /// stack indices of locals that were declared without an initializer and
+    /// may have been assigned at the current point of the code. An immutable
+    /// local can only be initialized while it's definitely unassigned
+    assigned: BTreeSet<usize>,
+    jump_targets: Vec<JumpTarget<'input>>,
     instructions: Vec<InstructionItem>,
 }
 
@@ -296,6 +687,9 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
         Self {
             compiler,
             stack: Default::default(),
+            max_stack: 0,
+            uninitialized: Default::default(),
+            assigned: Default::default(),
             jump_targets: Default::default(),
             instructions: Default::default(),
         }
@@ -304,11 +698,13 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
     pub fn visit_fn_trunk(mut self, trunk: ast::FnTrunk<'input>) -> Result<Function> {
         let ast::FnTrunk {
             formal_parameters,
-            body,
+            mut body,
         } = trunk;
 
+        Self::strip_trailing_return(&mut body);
         self.stack
             .extend(formal_parameters.iter().copied().map(Some));
+        self.max_stack = self.stack.len();
         self.visit_block(body)?;
 
         let instructions = self
@@ -317,7 +713,36 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
             .map(|ins| ins.real().ok_or(InternalError::InvalidBytecode))
             .collect::<std::result::Result<_, _>>()?;
 
-        Ok(Function::new(formal_parameters.len(), instructions))
+        Ok(Function::new(
+            formal_parameters.len(),
+            self.max_stack,
+            instructions,
+        ))
+    }
+
+    /// Replaces a `return` at the very end of a function body by its value.
+    /// Falling off the end of the body returns that value as well, so the
+    /// `Return` instruction would be redundant.
+    fn strip_trailing_return(body: &mut ast::Block<'input>) {
+        use ast::Expression::*;
+        use ast::Jump::*;
+
+        // `return x;` as the last statement is the same as a final `return x`
+        if body.expression.is_none()
+            && matches!(
+                body.statements.last(),
+                Some(ast::Statement::Expression(Jump(Return(_))))
+            )
+        {
+            if let Some(ast::Statement::Expression(expr)) = body.statements.pop() {
+                body.expression = Some(Box::new(expr));
+            }
+        }
+
+        body.expression = body.expression.take().map(|expr| match *expr {
+            Jump(Return(value)) => value.unwrap_or_else(|| Box::new(Unit)),
+            expr => Box::new(expr),
+        });
     }
 
     // statements
@@ -329,44 +754,53 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
             Declaration(_) => {
                 todo!("emit instructions");
             }
+            Expression(ast::Expression::Block(block)) => self.visit_discarded_block(block),
+            Expression(ast::Expression::Jump(jump)) => {
+                // there's no value to discard after a jump
+                self.visit_jump(jump)?;
+                self.apply_stack_effect(-1)
+            }
             Expression(expr) => {
                 self.visit_expression(expr)?;
                 self.push(Instruction::Pop)?;
                 Ok(())
             }
-            Jump(stmt) => self.visit_jump(stmt),
             VariableDeclaration(stmt) => self.visit_variable_declaration(stmt),
+            TupleDeclaration(stmt) => self.visit_tuple_declaration(stmt),
             Assignment(stmt) => self.visit_assignment(stmt),
         }
     }
 
-    fn visit_jump(&mut self, stmt: ast::Jump<'input>) -> Result<()> {
+    fn visit_jump(&mut self, expr: ast::Jump<'input>) -> Result<()> {
         use ast::Jump::*;
         use PlaceholderKind::*;
 
-        match stmt {
+        // code after a jump is unreachable, but it is still compiled as if the
+        // stack was unchanged and the jump's result was pushed onto it; that way
+        // a jump can take the place of any value
+        let stack = self.stack.clone();
+
+        match expr {
             Return(expr) => {
+                // unlike `break`, no `PopScope` is emitted: `Return` discards
+                // all locals of the function, no matter how deeply nested
                 let expr = expr.map(|expr| *expr);
                 self.visit_optional(expr)?;
                 self.push(Instruction::Return)?;
             }
-            Break(expr) => {
-                let jump_target = self.current_jump_target().ok_or(Error::NoLoopToExit)?;
-                let depth = jump_target.depth();
+            Break(label, expr) => {
+                let target = self.find_jump_target(label)?;
+                let depth = self.jump_targets[target].depth();
 
                 let expr = expr.map(|expr| *expr);
                 self.visit_optional(expr)?;
                 self.push(Instruction::PopScope(depth))?;
 
                 let jump = self.push_placeholder(Jump)?;
-                // if the compiler works correctly, this should be the same jump target as before
-                self.current_jump_target_mut().unwrap().push_end_jump(jump);
-
-                // despite pushing a value, break has a stack effect of zero, so negate that
-                self.apply_stack_effect(-1)?;
+                self.jump_targets[target].push_end_jump(jump, &self.uninitialized);
             }
-            Continue => {
-                let jump_target = self.current_jump_target().ok_or(Error::NoLoopToExit)?;
+            Continue(label) => {
+                let jump_target = &self.jump_targets[self.find_jump_target(label)?];
                 let depth = jump_target.depth();
                 let start = jump_target.start();
 
@@ -378,7 +812,11 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
             }
         }
 
-        Ok(())
+        self.stack = stack;
+        // the following code is unreachable, so no variable can be used there
+        // before being initialized
+        self.uninitialized.clear();
+        self.apply_stack_effect(1)
     }
 
     fn visit_variable_declaration(&mut self, stmt: ast::VariableDeclaration<'input>) -> Result<()> {
@@ -386,9 +824,32 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
             variable,
             initializer,
         } = stmt;
+        let initialized = initializer.is_some();
         self.visit_optional(initializer)?;
-        let var = self.stack.last_mut().unwrap();
-        *var = Some(variable);
+        let index = self.stack.len() - 1;
+        // locals that were at this index or above have gone out of scope
+        self.uninitialized.retain(|&i| i < index);
+        self.assigned.retain(|&i| i < index);
+        if !initialized {
+            self.uninitialized.insert(index);
+        }
+        self.stack[index] = Some(variable);
+        Ok(())
+    }
+
+    fn visit_tuple_declaration(&mut self, stmt: ast::TupleDeclaration<'input>) -> Result<()> {
+        let ast::TupleDeclaration {
+            variables,
+            initializer,
+        } = stmt;
+        self.visit_expression(initializer)?;
+        self.push(Instruction::Unpack(variables.len()))?;
+        let start = self.stack.len() - variables.len();
+        self.uninitialized.retain(|&i| i < start);
+        self.assigned.retain(|&i| i < start);
+        for (var, variable) in self.stack[start..].iter_mut().zip(variables) {
+            *var = Some(variable);
+        }
         Ok(())
     }
 
@@ -397,17 +858,39 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
 
         let ast::Assignment { left, right } = stmt;
 
-        let ast::Expression::Identifier(name) = left else {
-            return Err(Error::InvalidAssignmentTarget);
+        let name = match left {
+            ast::Expression::Identifier(name) => {
+                self.visit_expression(right)?;
+                name
+            }
+            // lists are values, so `list[i] = x` creates a modified copy that
+            // is then assigned to the variable `list`
+            ast::Expression::Index(ast::Index { receiver, index }) => {
+                let ast::Expression::Identifier(name) = *receiver else {
+                    return Err(Error::InvalidAssignmentTarget);
+                };
+                self.visit_identifier(name)?;
+                self.visit_expression(*index)?;
+                self.visit_expression(right)?;
+                self.push(IndexSet)?;
+                name
+            }
+            _ => return Err(Error::InvalidAssignmentTarget),
         };
 
-        self.visit_expression(right)?;
-
         if let Some((local, var)) = self.find_local(name) {
-            if !var.mutable {
+            // an immutable variable can be initialized once after its
+            // declaration, but not from a loop it was declared outside of, or
+            // after a branch that may have initialized it already
+            let in_loop = self.jump_targets.last().is_some_and(|t| local < t.depth());
+            let deferred_init =
+                self.uninitialized.contains(&local) && !self.assigned.contains(&local) && !in_loop;
+            if !var.mutable && !deferred_init {
                 Err(Error::ImmutableVariable)?;
             }
             self.push(StoreLocal(local))?;
+            self.uninitialized.remove(&local);
+            self.assigned.insert(local);
         } else {
             let name = self.compiler.add_constant(name.to_string());
             self.push(StoreNamed(name))?;
@@ -421,17 +904,27 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
         use ast::Expression::*;
 
         match expr {
+            Unit => self.push(Instruction::InlineConstant(InlineConstant::Unit)),
             Number(literal) => self.visit_number(literal),
             Bool(value) => self.visit_bool(value),
             String(literal) => self.visit_string(literal),
+            Bytes(literal) => self.visit_bytes(literal),
+            Interpolation(expr) => self.visit_interpolation(expr),
             Identifier(name) => self.visit_identifier(name),
             Binary(expr) => self.visit_binary(expr),
             Unary(expr) => self.visit_unary(expr),
             Call(call) => self.visit_call(call),
+            MethodCall(call) => self.visit_method_call(call),
+            FieldAccess(expr) => self.visit_field_access(expr),
+            Index(expr) => self.visit_index(expr),
+            Tuple(expr) => self.visit_tuple(expr),
+            List(expr) => self.visit_list(expr),
+            Map(expr) => self.visit_map(expr),
             Block(block) => self.visit_block(block),
             Fn(expr) => self.visit_fn(expr),
             If(expr) => self.visit_if(expr),
             Loop(expr) => self.visit_loop(expr),
+            Jump(expr) => self.visit_jump(expr),
         }
     }
 
@@ -447,6 +940,8 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
     fn visit_number(&mut self, literal: &str) -> Result<()> {
         use Instruction::*;
 
+        // the lexer only accepts plain decimals, so this can't fail for any
+        // literal in a source file
         let number = Number::from_str(literal).map_err(InternalError::from)?;
         let constant = self.compiler.add_constant(number);
         self.push(Constant(constant))?;
@@ -467,13 +962,51 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
         Ok(())
     }
 
+    fn visit_bytes(&mut self, literal: &str) -> Result<()> {
+        use Instruction::*;
+
+        let bytes = bytes_from_literal(literal).map_err(InternalError::from)?;
+        let constant = self.compiler.add_constant(bytes);
+        self.push(Constant(constant))?;
+        Ok(())
+    }
+
+    fn visit_interpolation(&mut self, expr: ast::Interpolation<'input>) -> Result<()> {
+        use Instruction::*;
+
+        if expr.parts.is_empty() {
+            let constant = self.compiler.add_constant(String::new());
+            self.push(Constant(constant))?;
+        }
+        // convert each part to a string, and concatenate it with the parts before it
+        for (i, part) in expr.parts.into_iter().enumerate() {
+            match part {
+                ast::InterpolationPart::Literal(value) => {
+                    let constant = self.compiler.add_constant(value);
+                    self.push(Constant(constant))?;
+                }
+                ast::InterpolationPart::Expression(expr) => {
+                    self.visit_expression(expr)?;
+                    self.push(Stringify)?;
+                }
+            }
+            if i > 0 {
+                self.push(Binary(ast::BinaryOperator::Add))?;
+            }
+        }
+        Ok(())
+    }
+
     fn visit_identifier(&mut self, name: &str) -> Result<()> {
         use Instruction::*;
 
-        if let Some((local, _)) = self.find_local(name) {
+        if let Some((local, var)) = self.find_local(name) {
+            if self.uninitialized.contains(&local) {
+                Err(Error::UseBeforeInit(var.name.to_string()))?;
+            }
             self.push(LoadLocal(local))?;
         } else {
-            let name = self.compiler.add_constant(name.to_string());
+            let name = self.compiler.add_constant(self.compiler.global_name(name));
             self.push(LoadNamed(name))?;
         }
         Ok(())
@@ -499,8 +1032,18 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
     fn visit_call(&mut self, call: ast::Call<'input>) -> Result<()> {
         use Instruction::*;
 
-        self.visit_expression(*call.function)?;
         let arity = call.actual_parameters.len();
+        if let Some((name, print)) = self.print_instruction(&call.function) {
+            let [expr]: [_; 1] = call
+                .actual_parameters
+                .try_into()
+                .map_err(|_| Error::WrongArgumentNumber(name.to_string(), 1, arity))?;
+            self.visit_expression(expr)?;
+            self.push(print)?;
+            return Ok(());
+        }
+        self.check_construction(&call.function, arity)?;
+        self.visit_callee(*call.function)?;
         for expr in call.actual_parameters {
             self.visit_expression(expr)?;
         }
@@ -508,16 +1051,167 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
         Ok(())
     }
 
-    fn visit_block(&mut self, block: ast::Block<'input>) -> Result<()> {
-        let depth = self.stack.len();
+    /// `print` and `println` are built into the language and compile to
+    /// instructions, unless they refer to a local variable or a global.
+    fn print_instruction(
+        &mut self,
+        callee: &ast::Expression<'input>,
+    ) -> Option<(&'input str, Instruction)> {
+        let ast::Expression::Identifier(name) = *callee else {
+            return None;
+        };
+        if self.find_local(name).is_some() || self.compiler.is_global(name) {
+            return None;
+        }
+        match name {
+            "print" => Some((name, Instruction::Print)),
+            "println" => Some((name, Instruction::PrintLine)),
+            _ => None,
+        }
+    }
+
+    /// If the callee is a struct type known at this point, checks that the
+    /// number of arguments matches its fields. Other calls are only checked
+    /// at runtime.
+    fn check_construction(&mut self, callee: &ast::Expression<'input>, arity: usize) -> Result<()> {
+        let ast::Expression::Identifier(name) = *callee else {
+            return Ok(());
+        };
+        if self.find_local(name).is_some() {
+            return Ok(());
+        }
+        if let Some(field_count) = self.compiler.struct_field_count(name) {
+            if arity != field_count {
+                Err(Error::WrongFieldNumber(
+                    name.to_string(),
+                    field_count,
+                    arity,
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_method_call(&mut self, call: ast::MethodCall<'input>) -> Result<()> {
+        use Instruction::*;
+
+        self.visit_expression(*call.receiver)?;
+        let method = self.compiler.add_constant(call.method.to_string());
+        self.push(LoadMethod(method))?;
+        // the receiver is passed as the first parameter
+        let arity = call.actual_parameters.len() + 1;
+        for expr in call.actual_parameters {
+            self.visit_expression(expr)?;
+        }
+        self.push(Call(arity))?;
+        Ok(())
+    }
+
+    fn visit_tuple(&mut self, expr: ast::Tuple<'input>) -> Result<()> {
+        let len = expr.elements.len();
+        for expr in expr.elements {
+            self.visit_expression(expr)?;
+        }
+        self.push(Instruction::MakeTuple(len))?;
+        Ok(())
+    }
+
+    fn visit_list(&mut self, expr: ast::List<'input>) -> Result<()> {
+        let len = expr.elements.len();
+        for expr in expr.elements {
+            self.visit_expression(expr)?;
+        }
+        self.push(Instruction::MakeList(len))?;
+        Ok(())
+    }
+
+    fn visit_map(&mut self, expr: ast::Map<'input>) -> Result<()> {
+        let len = expr.entries.len();
+        for (key, value) in expr.entries {
+            self.visit_expression(key)?;
+            self.visit_expression(value)?;
+        }
+        self.push(Instruction::MakeMap(len))?;
+        Ok(())
+    }
+
+    fn visit_index(&mut self, expr: ast::Index<'input>) -> Result<()> {
+        self.visit_expression(*expr.receiver)?;
+        self.visit_expression(*expr.index)?;
+        self.push(Instruction::IndexGet)?;
+        Ok(())
+    }
+
+    fn visit_field_access(&mut self, expr: ast::FieldAccess<'input>) -> Result<()> {
+        use Instruction::*;
+
+        self.visit_expression(*expr.receiver)?;
+        let field = self.compiler.add_constant(expr.field.to_string());
+        self.push(LoadNamedField(field))?;
+        Ok(())
+    }
+
+    /// Loads the function to be called. If it is statically known to be a
+    /// global function of this module, it is loaded via `LoadFunction` instead
+    /// of a generic load. Like `LoadNamed`, that refers to the global by name,
+    /// so that the host can still override it (see `Vm::set_global`).
+    fn visit_callee(&mut self, expr: ast::Expression<'input>) -> Result<()> {
+        use ast::Expression::*;
+
+        match expr {
+            Identifier(name)
+                if self.find_local(name).is_none() && self.compiler.is_function(name) =>
+            {
+                let name = self.compiler.add_constant(self.compiler.global_name(name));
+                self.push(Instruction::LoadFunction(name))
+            }
+            expr => self.visit_expression(expr),
+        }
+    }
+
+    /// Visits the statements of a block and returns the number of local
+    /// variables they declared.
+    fn visit_statements(&mut self, statements: Vec<ast::Statement<'input>>) -> Result<usize> {
         let mut locals = 0;
 
-        for stmt in block.statements {
-            if matches!(stmt, ast::Statement::VariableDeclaration(_)) {
-                locals += 1;
+        for stmt in statements {
+            match &stmt {
+                ast::Statement::VariableDeclaration(_) => locals += 1,
+                ast::Statement::TupleDeclaration(stmt) => locals += stmt.variables.len(),
+                _ => {}
             }
             self.visit_statement(stmt)?;
         }
+        Ok(locals)
+    }
+
+    /// Visits a block whose result is not used, leaving nothing on the stack.
+    /// Unlike [`visit_block`](Self::visit_block) followed by a `Pop`, a block
+    /// without a trailing expression and locals doesn't push a `Unit` only to
+    /// discard it again.
+    fn visit_discarded_block(&mut self, block: ast::Block<'input>) -> Result<()> {
+        if block.expression.is_some() {
+            self.visit_block(block)?;
+            self.push(Instruction::Pop)?;
+            return Ok(());
+        }
+
+        let depth = self.stack.len();
+        let locals = self.visit_statements(block.statements)?;
+        assert!(self.stack.len() == depth + locals);
+        if locals > 0 {
+            // PopScope keeps the top value, so there needs to be one to discard
+            self.push(Instruction::InlineConstant(InlineConstant::Unit))?;
+            self.push(Instruction::PopScope(depth))?;
+            self.push(Instruction::Pop)?;
+        }
+        Ok(())
+    }
+
+    fn visit_block(&mut self, block: ast::Block<'input>) -> Result<()> {
+        let depth = self.stack.len();
+        let locals = self.visit_statements(block.statements)?;
+
         if let Some(expr) = block.expression {
             self.visit_expression(*expr)?;
         } else {
@@ -541,28 +1235,52 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
     fn visit_fn(&mut self, expr: ast::Fn<'input>) -> Result<()> {
         use Instruction::*;
 
-        let function = InstructionCompiler::new(self.compiler).visit_fn_trunk(expr.trunk)?;
-        let constant = self.compiler.add_constant(function);
+        let constant = self.compile_fn(expr)?;
         self.push(Constant(constant))?;
         Ok(())
     }
 
-    fn visit_if(&mut self, expr: ast::If<'input>) -> Result<()> {
+    /// Compiles a function expression into a constant and returns its index.
+    fn compile_fn(&mut self, expr: ast::Fn<'input>) -> Result<usize> {
+        let function = InstructionCompiler::new(self.compiler).visit_fn_trunk(expr.trunk)?;
+        Ok(self.compiler.add_constant(function))
+    }
+
+    fn visit_if(&mut self, mut expr: ast::If<'input>) -> Result<()> {
         use ast::UnaryOperator::*;
         use Instruction::*;
 
+        Self::fold_constant_conditions(&mut expr);
+        if expr.then_branches.is_empty() {
+            let else_branch = expr.else_branch.map(ast::Expression::Block);
+            return self.visit_optional(else_branch);
+        }
+        if let Some((scrutinee, cases)) = Self::switch_cases(&expr) {
+            return self.visit_switch(expr, scrutinee, cases);
+        }
+
         let mut end_jumps = Vec::new();
+        // a variable is initialized after the if if it is in every branch,
+        // and may have been assigned if it was in any branch
+        let mut uninitialized = BTreeSet::new();
+        let mut assigned = BTreeSet::new();
 
         for (condition, then_branch) in expr.then_branches {
-            // jump if the condition is false
+            // jump if the condition is false. If the condition jumps away,
+            // e.g. `if (break) {}`, the stack is still accounted for as if it
+            // had pushed a value, see `visit_jump`
             self.visit_expression(condition)?;
             self.push(Unary(Not))?;
             let cond = self.push_placeholder(PlaceholderKind::JumpIf)?;
 
             let depth = self.stack.len();
+            let before = self.uninitialized.clone();
+            let assigned_before = self.assigned.clone();
 
             // do the then branch unless jumped
             self.visit_block(then_branch)?;
+            uninitialized.append(&mut std::mem::replace(&mut self.uninitialized, before));
+            assigned.append(&mut std::mem::replace(&mut self.assigned, assigned_before));
             end_jumps.push(self.push_placeholder(PlaceholderKind::Jump)?);
             cond.jump_fwd_to_current(self);
 
@@ -576,18 +1294,140 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
         }
         let else_branch = expr.else_branch.map(ast::Expression::Block);
         self.visit_optional(else_branch)?;
+        self.uninitialized.append(&mut uninitialized);
+        self.assigned.append(&mut assigned);
         for end_jump in end_jumps {
             end_jump.jump_fwd_to_current(self);
         }
         Ok(())
     }
 
-    fn visit_loop(&mut self, expr: ast::Loop<'input>) -> Result<()> {
-        use Instruction::*;
+    /// Removes the branches of an if chain whose conditions are literal bools:
+    /// a `false` branch is never taken, and a `true` branch is always taken if
+    /// it's reached, so it replaces the else branch and all branches after it.
+    ///
+    /// The removed branches are not compiled at all, so compile errors in
+    /// them, such as assigning to an immutable variable, are not reported.
+    fn fold_constant_conditions(expr: &mut ast::If<'input>) {
+        use ast::Expression::Bool;
+
+        expr.then_branches
+            .retain(|(condition, _)| !matches!(condition, Bool(false)));
+        let taken = expr
+            .then_branches
+            .iter()
+            .position(|(condition, _)| matches!(condition, Bool(true)));
+        if let Some(taken) = taken {
+            let (_, then_branch) = expr.then_branches.drain(taken..).next().unwrap();
+            expr.else_branch = Some(then_branch);
+        }
+    }
+
+    /// Checks whether all conditions of the if chain compare the same
+    /// variable to distinct integer literals, dense enough for a jump table.
+    /// Returns the variable and the literals' values in branch order.
+    fn switch_cases(expr: &ast::If<'input>) -> Option<(&'input str, Vec<usize>)> {
+        use ast::Expression::*;
+
+        let mut scrutinee = None;
+        let mut cases = Vec::new();
+        for (condition, _) in &expr.then_branches {
+            let Binary(ast::Binary {
+                operator: ast::BinaryOperator::Equals,
+                left,
+                right,
+            }) = condition
+            else {
+                return None;
+            };
+            let (name, literal) = match (left.as_ref(), right.as_ref()) {
+                (Identifier(name), Number(literal)) | (Number(literal), Identifier(name)) => {
+                    (*name, *literal)
+                }
+                _ => return None,
+            };
+            let value = literal.parse::<usize>().ok()?;
+            if *scrutinee.get_or_insert(name) != name || cases.contains(&value) {
+                return None;
+            }
+            cases.push(value);
+        }
+
+        // at least half of the table's entries should lead to a branch
+        let len = cases.iter().max()? + 1;
+        let dense =
+            cases.len() >= MIN_SWITCH_CASES && len <= 2 * cases.len() && len <= u8::MAX as usize;
+        dense.then(|| (scrutinee.unwrap(), cases))
+    }
+
+    fn visit_switch(
+        &mut self,
+        expr: ast::If<'input>,
+        scrutinee: &'input str,
+        cases: Vec<usize>,
+    ) -> Result<()> {
+        let len = cases.iter().max().unwrap() + 1;
+
+        self.visit_identifier(scrutinee)?;
+        self.push(Instruction::Switch(len))?;
+        let mut table = Vec::with_capacity(len);
+        for _ in 0..len {
+            table.push(Some(self.push_placeholder(PlaceholderKind::Jump)?));
+        }
+
+        let depth = self.stack.len();
+        let mut end_jumps = Vec::new();
+        // as in `visit_if`, the uninitialized variables of all branches are merged
+        let before = self.uninitialized.clone();
+        let mut uninitialized = BTreeSet::new();
+        let assigned_before = self.assigned.clone();
+        let mut assigned = BTreeSet::new();
+
+        // values that aren't in the table skip it and land in the else branch,
+        // as do the table's gaps
+        for (value, entry) in table.iter_mut().enumerate() {
+            if !cases.contains(&value) {
+                entry.take().unwrap().jump_fwd_to_current(self);
+            }
+        }
+        let else_branch = expr.else_branch.map(ast::Expression::Block);
+        self.visit_optional(else_branch)?;
+        end_jumps.push(self.push_placeholder(PlaceholderKind::Jump)?);
+        // as in `visit_if`, only one branch's result is on the stack in the end
+        self.apply_stack_effect(-1)?;
+
+        let count = expr.then_branches.len();
+        let branches = expr.then_branches.into_iter().zip(cases);
+        for (i, ((_, then_branch), value)) in branches.enumerate() {
+            table[value].take().unwrap().jump_fwd_to_current(self);
+            uninitialized.append(&mut std::mem::replace(
+                &mut self.uninitialized,
+                before.clone(),
+            ));
+            assigned.append(&mut std::mem::replace(
+                &mut self.assigned,
+                assigned_before.clone(),
+            ));
+            self.visit_block(then_branch)?;
+            assert!(self.stack.len() == depth + 1);
 
-        let start = self.push_jump_target().start();
-        self.visit_block(expr.body)?;
-        self.push(Pop)?;
+            // the last branch doesn't need to jump to the end
+            if i + 1 < count {
+                end_jumps.push(self.push_placeholder(PlaceholderKind::Jump)?);
+                self.apply_stack_effect(-1)?;
+            }
+        }
+        self.uninitialized.append(&mut uninitialized);
+        self.assigned.append(&mut assigned);
+        for end_jump in end_jumps {
+            end_jump.jump_fwd_to_current(self);
+        }
+        Ok(())
+    }
+
+    fn visit_loop(&mut self, expr: ast::Loop<'input>) -> Result<()> {
+        let start = self.push_jump_target(expr.label).start();
+        self.visit_discarded_block(expr.body)?;
         self.push_placeholder(PlaceholderKind::Jump)?
             .jump_back_to_index(self, start);
         self.pop_jump_target().unwrap();
@@ -635,7 +1475,8 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
 
     fn apply_stack_effect(&mut self, effect: isize) -> Result<()> {
         if let Ok(effect) = usize::try_from(effect) {
-            self.stack.extend(iter::repeat(None).take(effect));
+            self.stack.extend(std::iter::repeat_n(None, effect));
+            self.max_stack = self.max_stack.max(self.stack.len());
         } else if let Ok(effect) = usize::try_from(-effect) {
             let len = self
                 .stack
@@ -664,25 +1505,37 @@ impl<'a, 'input> InstructionCompiler<'a, 'input> {
 
     // jump target helpers
 
-    fn push_jump_target(&mut self) -> &JumpTarget {
+    fn push_jump_target(&mut self, label: Option<&'input str>) -> &JumpTarget<'input> {
         let depth = self.stack.len();
         let start = self.instructions.len();
-        self.jump_targets.push(JumpTarget::new(depth, start));
+        self.jump_targets.push(JumpTarget::new(label, depth, start));
         self.jump_targets.last().unwrap()
     }
 
     fn pop_jump_target(&mut self) -> Option<()> {
-        let jump_target = self.jump_targets.pop()?;
+        let mut jump_target = self.jump_targets.pop()?;
+        // the loop is only left through its end jumps, so after it the
+        // variables are uninitialized that were at any of these jumps
+        self.uninitialized = std::mem::take(&mut jump_target.uninitialized);
         jump_target.fill_end_jumps(self);
         Some(())
     }
 
-    fn current_jump_target(&self) -> Option<&JumpTarget> {
-        self.jump_targets.last()
-    }
-
-    fn current_jump_target_mut(&mut self) -> Option<&mut JumpTarget> {
-        self.jump_targets.last_mut()
+    /// Returns the index of the jump target with the given label, or of the
+    /// innermost one if there is no label.
+    fn find_jump_target(&self, label: Option<&str>) -> Result<usize> {
+        match label {
+            Some(label) => self
+                .jump_targets
+                .iter()
+                .rposition(|target| target.label() == Some(label))
+                .ok_or_else(|| Error::UnknownLabel(label.to_string())),
+            None => self
+                .jump_targets
+                .len()
+                .checked_sub(1)
+                .ok_or(Error::NoLoopToExit),
+        }
     }
 }
 
@@ -732,21 +1585,29 @@ impl Placeholder {
 }
 
 #[derive(Debug)]
-struct JumpTarget {
+struct JumpTarget<'input> {
+    label: Option<&'input str>,
     depth: usize,
     start: usize,
     end_jumps: Vec<Placeholder>,
+    uninitialized: BTreeSet<usize>,
 }
 
-impl JumpTarget {
-    pub fn new(depth: usize, start: usize) -> Self {
+impl<'input> JumpTarget<'input> {
+    pub fn new(label: Option<&'input str>, depth: usize, start: usize) -> Self {
         Self {
+            label,
             depth,
             start,
             end_jumps: Default::default(),
+            uninitialized: Default::default(),
         }
     }
 
+    pub fn label(&self) -> Option<&'input str> {
+        self.label
+    }
+
     pub fn depth(&self) -> usize {
         self.depth
     }
@@ -755,8 +1616,9 @@ impl JumpTarget {
         self.start
     }
 
-    pub fn push_end_jump(&mut self, jump: Placeholder) {
+    pub fn push_end_jump(&mut self, jump: Placeholder, uninitialized: &BTreeSet<usize>) {
         self.end_jumps.push(jump);
+        self.uninitialized.extend(uninitialized);
     }
 
     pub fn fill_end_jumps(self, instructions: &mut InstructionCompiler) {
@@ -1,185 +1,525 @@
-use std::collections::BTreeMap;
-use std::io::{Result, Write};
+use std::io::{Error, ErrorKind, Result, Write};
 
-use super::constant::{Constant, Function};
+use super::constant::Constant;
 use super::{Module, StructType};
-use crate::bytecode::instruction;
-use crate::bytecode::{ConstantKind, Number, StructTypeKind};
+use crate::bytecode::instruction::Instruction;
+use crate::bytecode::writer::{RawConstant, RawModule, RawStructType};
+use crate::bytecode::BytecodeConfig;
 
+/// Writes the given module in the sprachli bytecode format.
+///
+/// All multi-byte integers (lengths, indices, arities) are encoded big-endian,
+/// matching what [`parse_bytecode`](crate::bytecode::parser::parse_bytecode) expects.
 pub fn write_bytecode<W: Write>(w: &mut W, module: &Module) -> Result<()> {
-    header(w)?;
-    constants(w, module.constants())?;
-    globals(w, module.globals())?;
-    struct_types(w, module.struct_types())?;
-
-    Ok(())
+    write_bytecode_with_config(w, module, &BytecodeConfig::default())
 }
 
-fn header<W: Write>(w: &mut W) -> Result<()> {
-    w.write_all(b"sprachli")?;
-    w.write_all(&0u16.to_be_bytes())?;
-    Ok(())
+/// Writes the given module like [`write_bytecode`], but with the given
+/// config's magic string and version, e.g. for a fork of the format.
+pub fn write_bytecode_with_config<W: Write>(
+    w: &mut W,
+    module: &Module,
+    config: &BytecodeConfig,
+) -> Result<()> {
+    // function bodies are encoded up front, so that the raw module can borrow
+    // them; other constants get an empty (non-allocating) placeholder
+    let bodies = module
+        .constants()
+        .iter()
+        .map(|constant| match constant {
+            Constant::Function(function) => encode_body(function.body()),
+            _ => Ok(Vec::new()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let constants = module
+        .constants()
+        .iter()
+        .zip(&bodies)
+        .map(|(constant, body)| match constant {
+            Constant::Number(value) => RawConstant::Number(value),
+            Constant::String(value) => RawConstant::String(value),
+            Constant::Function(value) => RawConstant::Function(value.arity(), body),
+            Constant::Bytes(value) => RawConstant::Bytes(value),
+        })
+        .collect();
+    let struct_types = module
+        .struct_types()
+        .iter()
+        .map(|(&name, struct_type)| {
+            let struct_type = match struct_type {
+                StructType::Empty => RawStructType::Empty,
+                StructType::Positional(count) => RawStructType::Positional(*count),
+                StructType::Named(fields) => RawStructType::Named(fields),
+            };
+            (name, struct_type)
+        })
+        .collect();
+
+    let module = RawModule {
+        constants,
+        globals: module.globals().clone(),
+        struct_types,
+        methods: module.methods().clone(),
+    };
+    module.write(w, config)
 }
 
-fn constants<W: Write>(w: &mut W, constants: &[Constant]) -> Result<()> {
-    let len = constants.len() as u16;
-    w.write_all(&len.to_be_bytes())?;
-    for value in constants {
-        constant(w, value)?;
+fn encode_body(body: &[Instruction]) -> Result<Vec<u8>> {
+    let mut encoded = Vec::with_capacity(body.len());
+    for ins in body {
+        ins.encode(&mut encoded)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
     }
-    Ok(())
+    // jump offsets are computed in bytes by the compiler, so they are written
+    // unchanged; see `test_round_trip_jumps`
+    Ok(encoded)
 }
 
-fn constant<W: Write>(w: &mut W, value: &Constant) -> Result<()> {
-    use Constant::*;
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use bigdecimal::num_bigint::BigInt;
+
+    use super::*;
+    use crate::ast::{BinaryOperator, UnaryOperator};
+    use crate::bytecode::instruction::{InlineConstant as Inline, Instruction, Offset};
+    use crate::bytecode::{self, parser::parse_bytecode};
+    use crate::compiler::{Function, Number};
+
+    /// A small deterministic PRNG (xorshift64*), so that every generated module
+    /// can be reproduced from its seed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Self {
+            // xorshift must not be seeded with zero
+            Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
 
-    match value {
-        Number(value) => number(w, value)?,
-        String(value) => string(w, value)?,
-        Function(value) => function(w, value)?,
+        fn operand(&mut self) -> usize {
+            self.below(u8::MAX as usize + 1)
+        }
     }
 
-    Ok(())
-}
+    fn gen_identifier(rng: &mut Rng) -> String {
+        const CHARS: &[u8] = b"_abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
 
-fn number<W: Write>(w: &mut W, value: &Number) -> Result<()> {
-    let value = value.to_string();
-    let len = value.len() as u16;
-    w.write_all(&[ConstantKind::Number.into()])?;
-    w.write_all(&len.to_be_bytes())?;
-    w.write_all(value.as_bytes())?;
-    Ok(())
-}
+        let len = 1 + rng.below(12);
+        (0..len)
+            .map(|i| {
+                // identifiers don't start with a digit
+                let range = if i == 0 {
+                    CHARS.len() - 10
+                } else {
+                    CHARS.len()
+                };
+                CHARS[rng.below(range)] as char
+            })
+            .collect()
+    }
 
-fn string<W: Write>(w: &mut W, value: &str) -> Result<()> {
-    let len = value.len() as u16;
-    w.write_all(&[ConstantKind::String.into()])?;
-    w.write_all(&len.to_be_bytes())?;
-    w.write_all(value.as_bytes())?;
-    Ok(())
-}
+    fn gen_string(rng: &mut Rng) -> String {
+        const CHARS: &[char] = &['a', 'Z', '0', ' ', '"', '\\', '\n', 'ä', '€', '🦀'];
 
-fn function<W: Write>(w: &mut W, value: &Function) -> Result<()> {
-    use instruction::InlineConstant as Const;
-    use instruction::Instruction as In;
-    use instruction::Offset::*;
-    use instruction::Opcode as Op;
+        let len = rng.below(20);
+        (0..len).map(|_| CHARS[rng.below(CHARS.len())]).collect()
+    }
+
+    fn gen_bytes(rng: &mut Rng) -> Vec<u8> {
+        let len = rng.below(20);
+        (0..len).map(|_| rng.below(256) as u8).collect()
+    }
+
+    fn gen_number(rng: &mut Rng) -> Number {
+        let digits = BigInt::from(rng.next() as i64 >> rng.below(64));
+        // negative scales are written without an exponent, e.g. `1e3` as `1000`
+        let scale = rng.below(12) as i64 - 4;
+        Number::new(digits, scale)
+    }
 
-    fn push_opcode(body: &mut Vec<u8>, opcode: Op) {
-        body.push(opcode.into());
+    fn gen_instruction(rng: &mut Rng) -> Instruction {
+        use Instruction::*;
+
+        let offset = |rng: &mut Rng| {
+            let offset = rng.operand();
+            if rng.below(2) == 0 {
+                Offset::Forward(offset)
+            } else {
+                Offset::Backward(offset)
+            }
+        };
+
+        match rng.below(33) {
+            0 => Constant(rng.operand()),
+            1 => LoadFunction(rng.operand()),
+            2 => InlineConstant(Inline::Unit),
+            3 => InlineConstant(Inline::Bool(rng.below(2) == 0)),
+            4 => Unary(UnaryOperator::try_from(rng.below(2) as u8).unwrap()),
+            5 => Binary(BinaryOperator::try_from(rng.below(16) as u8).unwrap()),
+            6 => LoadLocal(rng.operand()),
+            7 => StoreLocal(rng.operand()),
+            8 => LoadNamed(rng.operand()),
+            9 => StoreNamed(rng.operand()),
+            10 => LoadMethod(rng.operand()),
+            11 => LoadPositionalField(rng.operand()),
+            12 => StorePositionalField(rng.operand()),
+            13 => LoadNamedField(rng.operand()),
+            14 => StoreNamedField(rng.operand()),
+            15 => Pop,
+            16 => PopScope(rng.operand()),
+            17 => Call(rng.operand()),
+            18 => Return,
+            19 => Jump(offset(rng)),
+            20 => JumpIf(offset(rng)),
+            21 => MakeTuple(rng.operand()),
+            22 => Unpack(rng.operand()),
+            23 => MakeList(rng.operand()),
+            24 => IndexGet,
+            25 => IndexSet,
+            26 => Print,
+            27 => PrintLine,
+            28 => Switch(rng.operand()),
+            29 => MakeMap(rng.operand()),
+            30 => Stringify,
+            31 => Nop,
+            32 => Drop,
+            _ => unreachable!(),
+        }
     }
 
-    fn push_opcode_u8(body: &mut Vec<u8>, opcode: Op, param: u8) {
-        body.push(opcode.into());
-        body.push(param);
+    fn gen_function(rng: &mut Rng) -> Function {
+        let arity = rng.below(5);
+        let len = rng.below(30);
+        let body = (0..len).map(|_| gen_instruction(rng)).collect();
+        let max_stack = arity + rng.below(10);
+        Function::new(arity, max_stack, body)
     }
 
-    let mut body = Vec::with_capacity(value.body().len());
-    for ins in value.body() {
-        match *ins {
-            In::Constant(index) => push_opcode_u8(&mut body, Op::Constant, index as u8),
-            In::InlineConstant(value) => {
-                let opcode = match value {
-                    Const::Unit => Op::Unit,
-                    Const::Bool(true) => Op::True,
-                    Const::Bool(false) => Op::False,
+    fn gen_module(rng: &mut Rng) -> Module {
+        // identifiers are deduplicated, like the compiler's constant pool does it
+        let identifiers: BTreeSet<_> = (0..1 + rng.below(10))
+            .map(|_| gen_identifier(rng))
+            .collect();
+        let mut constants: Vec<_> = identifiers.into_iter().map(Constant::String).collect();
+        let identifiers = constants.len();
+
+        for _ in 0..rng.below(10) {
+            let constant = match rng.below(4) {
+                0 => Constant::Number(gen_number(rng)),
+                1 => Constant::String(gen_string(rng)),
+                2 => Constant::Bytes(gen_bytes(rng)),
+                _ => Constant::Function(gen_function(rng)),
+            };
+            constants.push(constant);
+        }
+
+        let globals = (0..rng.below(identifiers + 1))
+            .map(|_| (rng.below(identifiers), rng.below(constants.len())))
+            .collect();
+
+        let struct_types = (0..rng.below(identifiers + 1))
+            .map(|_| {
+                let struct_type = match rng.below(3) {
+                    0 => StructType::Empty,
+                    1 => StructType::Positional(rng.below(10)),
+                    _ => {
+                        let fields = (0..rng.below(5)).map(|_| rng.below(identifiers));
+                        StructType::Named(fields.collect())
+                    }
                 };
-                push_opcode(&mut body, opcode);
+                (rng.below(identifiers), struct_type)
+            })
+            .collect();
+
+        let methods = (0..rng.below(identifiers + 1))
+            .map(|_| {
+                let key = (rng.below(identifiers), rng.below(identifiers));
+                (key, rng.below(constants.len()))
+            })
+            .collect();
+
+        Module {
+            constants,
+            globals,
+            struct_types,
+            methods,
+        }
+    }
+
+    /// Checks that `parsed` is the decoding of `module`, returning a description
+    /// of the first difference.
+    fn compare(module: &Module, parsed: &bytecode::Module) -> std::result::Result<(), String> {
+        let name = |index: usize| match &module.constants[index] {
+            Constant::String(name) => name.as_str(),
+            _ => unreachable!("names are always string constants"),
+        };
+
+        if module.constants.len() != parsed.constants().len() {
+            return Err(format!(
+                "constant count: {} != {}",
+                module.constants.len(),
+                parsed.constants().len()
+            ));
+        }
+
+        for (i, (expected, actual)) in module.constants.iter().zip(parsed.constants()).enumerate() {
+            let equal = match (expected, actual) {
+                (Constant::Number(expected), bytecode::Constant::Number(actual)) => {
+                    expected == actual
+                }
+                (Constant::String(expected), bytecode::Constant::String(actual)) => {
+                    expected == actual
+                }
+                (Constant::Bytes(expected), bytecode::Constant::Bytes(actual)) => {
+                    expected == actual
+                }
+                (Constant::Function(expected), bytecode::Constant::Function(actual)) => {
+                    let body: std::result::Result<Vec<_>, _> = actual.body().iter().collect();
+                    expected.arity() == actual.arity()
+                        && body.ok().as_deref() == Some(expected.body())
+                }
+                _ => false,
+            };
+            if !equal {
+                return Err(format!("constant #{i}: {expected:?} != {actual:?}"));
             }
-            In::Unary(op) => push_opcode_u8(&mut body, Op::Unary, op.into()),
-            In::Binary(op) => push_opcode_u8(&mut body, Op::Binary, op.into()),
-            In::LoadLocal(index) => push_opcode_u8(&mut body, Op::LoadLocal, index as u8),
-            In::StoreLocal(index) => push_opcode_u8(&mut body, Op::StoreLocal, index as u8),
-            In::LoadNamed(index) => push_opcode_u8(&mut body, Op::LoadNamed, index as u8),
-            In::StoreNamed(index) => push_opcode_u8(&mut body, Op::StoreNamed, index as u8),
-            In::LoadPositionalField(index) => {
-                push_opcode_u8(&mut body, Op::LoadPositionalField, index as u8)
+        }
+
+        let globals: BTreeMap<_, _> = module
+            .globals
+            .iter()
+            .map(|(&key, &value)| (name(key), value))
+            .collect();
+        if &globals != parsed.globals() {
+            return Err(format!("globals: {globals:?} != {:?}", parsed.globals()));
+        }
+
+        for (&key, expected) in &module.struct_types {
+            let actual = parsed.struct_type(name(key));
+            let equal = match (expected, actual) {
+                (StructType::Empty, Some(bytecode::StructType::Empty)) => true,
+                (
+                    StructType::Positional(expected),
+                    Some(bytecode::StructType::Positional(actual)),
+                ) => expected == actual,
+                (StructType::Named(expected), Some(bytecode::StructType::Named(actual))) => {
+                    expected
+                        .iter()
+                        .map(|&field| name(field))
+                        .eq(actual.iter().copied())
+                }
+                _ => false,
+            };
+            if !equal {
+                return Err(format!(
+                    "struct type {}: {expected:?} != {actual:?}",
+                    name(key)
+                ));
             }
-            In::StorePositionalField(index) => {
-                push_opcode_u8(&mut body, Op::StorePositionalField, index as u8)
+        }
+        if module.struct_types.len() != parsed.struct_types().len() {
+            return Err(format!(
+                "struct type count: {} != {}",
+                module.struct_types.len(),
+                parsed.struct_types().len()
+            ));
+        }
+
+        let methods: BTreeMap<_, _> = module
+            .methods
+            .iter()
+            .map(|(&(struct_type, key), &value)| ((name(struct_type), name(key)), value))
+            .collect();
+        if &methods != parsed.methods() {
+            return Err(format!("methods: {methods:?} != {:?}", parsed.methods()));
+        }
+
+        Ok(())
+    }
+
+    fn round_trip(module: &Module) -> std::result::Result<(), String> {
+        let mut bytes = Vec::new();
+        write_bytecode(&mut bytes, module).map_err(|error| error.to_string())?;
+        let parsed = parse_bytecode(&bytes).map_err(|error| error.to_string())?;
+        compare(module, &parsed)
+    }
+
+    /// Makes a failing module smaller by removing globals, struct types, methods
+    /// and function instructions as long as the round trip still fails.
+    fn shrink(mut module: Module) -> Module {
+        let mut progress = true;
+        while progress {
+            progress = false;
+
+            for key in module.globals.keys().copied().collect::<Vec<_>>() {
+                let mut candidate = module.clone();
+                candidate.globals.remove(&key);
+                if round_trip(&candidate).is_err() {
+                    module = candidate;
+                    progress = true;
+                }
             }
-            In::LoadNamedField(index) => push_opcode_u8(&mut body, Op::LoadNamedField, index as u8),
-            In::StoreNamedField(index) => {
-                push_opcode_u8(&mut body, Op::StoreNamedField, index as u8)
+
+            for key in module.struct_types.keys().copied().collect::<Vec<_>>() {
+                let mut candidate = module.clone();
+                candidate.struct_types.remove(&key);
+                if round_trip(&candidate).is_err() {
+                    module = candidate;
+                    progress = true;
+                }
             }
-            In::Pop => push_opcode(&mut body, Op::Pop),
-            In::PopScope(depth) => push_opcode_u8(&mut body, Op::PopScope, depth as u8),
-            In::Call(arity) => push_opcode_u8(&mut body, Op::Call, arity as u8),
-            In::Return => push_opcode(&mut body, Op::Return),
-            In::Jump(offset) => {
-                let (opcode, offset) = match offset {
-                    Forward(offset) => (Op::JumpForward, offset),
-                    Backward(offset) => (Op::JumpBackward, offset),
-                };
-                push_opcode_u8(&mut body, opcode, offset as u8);
+
+            for key in module.methods.keys().copied().collect::<Vec<_>>() {
+                let mut candidate = module.clone();
+                candidate.methods.remove(&key);
+                if round_trip(&candidate).is_err() {
+                    module = candidate;
+                    progress = true;
+                }
             }
-            In::JumpIf(offset) => {
-                let (opcode, offset) = match offset {
-                    Forward(offset) => (Op::JumpForwardIf, offset),
-                    Backward(offset) => (Op::JumpBackwardIf, offset),
+
+            for i in 0..module.constants.len() {
+                let Constant::Function(function) = &module.constants[i] else {
+                    continue;
                 };
-                push_opcode_u8(&mut body, opcode, offset as u8);
+                for j in (0..function.body().len()).rev() {
+                    let Constant::Function(function) = &module.constants[i] else {
+                        unreachable!();
+                    };
+                    let mut body = function.body().to_vec();
+                    if j >= body.len() {
+                        continue;
+                    }
+                    body.remove(j);
+
+                    let mut candidate = module.clone();
+                    let function = Function::new(function.arity(), function.max_stack(), body);
+                    candidate.constants[i] = function.into();
+                    if round_trip(&candidate).is_err() {
+                        module = candidate;
+                        progress = true;
+                    }
+                }
             }
         }
+        module
     }
 
-    let arity = value.arity() as u16;
-    let len = body.len() as u16;
+    #[test]
+    fn test_operand_too_large() {
+        // every literal is a distinct constant, so the last ones have indices above 255
+        let literals: Vec<_> = (0..300).map(|i| i.to_string()).collect();
+        let source = format!("fn main() {{ {}; }}", literals.join("; "));
+        let module = Module::new(crate::parser::parse_source_file(&source).unwrap()).unwrap();
 
-    w.write_all(&[ConstantKind::Function.into()])?;
-    w.write_all(&arity.to_be_bytes())?;
-    w.write_all(&len.to_be_bytes())?;
-    // TODO jump offsets must be translated from instruction-wise to byte-wise
-    w.write_all(&body)?;
-    Ok(())
-}
+        let mut bytes = Vec::new();
+        let error = write_bytecode(&mut bytes, &module).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_string_too_long() {
+        let source = format!("fn main() {{ \"{}\" }}", "a".repeat(70_000));
+        let module = Module::new(crate::parser::parse_source_file(&source).unwrap()).unwrap();
 
-fn globals<W: Write>(w: &mut W, globals: &BTreeMap<usize, usize>) -> Result<()> {
-    let len = globals.len() as u16;
-    w.write_all(&len.to_be_bytes())?;
-    for (key, value) in globals.iter() {
-        let (key, value) = (*key as u16, *value as u16);
-        w.write_all(&key.to_be_bytes())?;
-        w.write_all(&value.to_be_bytes())?;
+        let mut bytes = Vec::new();
+        let error = write_bytecode(&mut bytes, &module).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("length of string"));
     }
-    Ok(())
-}
 
-fn struct_types<W: Write>(w: &mut W, structs: &BTreeMap<usize, StructType>) -> Result<()> {
-    let len = structs.len() as u16;
-    w.write_all(&len.to_be_bytes())?;
-    for (name, decl) in structs {
-        struct_type(w, *name, decl)?;
+    #[test]
+    fn test_round_trip_numbers() {
+        use std::str::FromStr;
+
+        // the written form, which is also what's read back including the scale
+        let cases = [
+            ("42", "42"),
+            ("42.0", "42.0"),
+            ("0.1", "0.1"),
+            ("-2.50", "-2.50"),
+            ("1e3", "1000"),
+            ("1.5e-3", "0.0015"),
+            (
+                "123456789012345678901234567890.5",
+                "123456789012345678901234567890.5",
+            ),
+        ];
+        for (literal, written) in cases {
+            let number = Number::from_str(literal).unwrap();
+            let module = Module {
+                constants: vec![Constant::Number(number.clone())],
+                globals: Default::default(),
+                struct_types: Default::default(),
+                methods: Default::default(),
+            };
+            let mut bytes = Vec::new();
+            write_bytecode(&mut bytes, &module).unwrap();
+            let contains_written = bytes
+                .windows(written.len())
+                .any(|window| window == written.as_bytes());
+            assert!(contains_written, "{literal}");
+            let parsed = parse_bytecode(&bytes).unwrap();
+            let Some(bytecode::Constant::Number(actual)) = parsed.constant(0) else {
+                panic!("{literal} was not read back as a number");
+            };
+            assert_eq!(actual, &number, "{literal}");
+            assert_eq!(actual.to_string(), written, "{literal}");
+        }
+    }
+
+    #[test]
+    fn test_round_trip_jumps() {
+        let source = "\
+            fn main() {
+                let mut i = 0;
+                loop {
+                    if i == 3 { break; } else { i = i + 1; };
+                };
+                loop {
+                    if i == 0 { break; };
+                    i = i - 1;
+                };
+                i
+            }
+        ";
+        let module = Module::new(crate::parser::parse_source_file(source).unwrap()).unwrap();
+        round_trip(&module).unwrap();
+
+        // the offsets read back are byte-wise: all jumps land on instructions
+        let mut bytes = Vec::new();
+        write_bytecode(&mut bytes, &module).unwrap();
+        let parsed = parse_bytecode(&bytes).unwrap();
+        let (_, main) = parsed.functions().next().unwrap();
+        let jumps = main
+            .instructions()
+            .filter(|ins| matches!(ins, Ok(Instruction::Jump(_) | Instruction::JumpIf(_))));
+        assert!(jumps.count() >= 3);
+        parsed.validate().unwrap();
     }
-    Ok(())
-}
 
-fn struct_type<W: Write>(w: &mut W, name: usize, decl: &StructType) -> Result<()> {
-    use StructType::*;
-
-    let name = name as u16;
-    w.write_all(&name.to_be_bytes())?;
-
-    match decl {
-        Empty => {
-            w.write_all(&[StructTypeKind::Empty.into()])?;
-            Ok(())
-        }
-        Positional(count) => {
-            let count = *count as u16;
-            w.write_all(&[StructTypeKind::Positional.into()])?;
-            w.write_all(&count.to_be_bytes())?;
-            Ok(())
-        }
-        Named(fields) => {
-            let len = fields.len() as u16;
-            w.write_all(&[StructTypeKind::Named.into()])?;
-            w.write_all(&len.to_be_bytes())?;
-            for field in fields {
-                let field = *field as u16;
-                w.write_all(&field.to_be_bytes())?;
+    #[test]
+    fn test_round_trip_random_modules() {
+        for seed in 0..1000 {
+            let module = gen_module(&mut Rng::new(seed));
+            if round_trip(&module).is_err() {
+                let module = shrink(module);
+                let error = round_trip(&module).unwrap_err();
+                panic!("round trip failed for seed {seed}: {error}\nshrunk module: {module:#?}");
             }
-            Ok(())
         }
     }
 }
@@ -1,4 +1,6 @@
 pub mod compiler;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 pub mod vm;
 
 pub use sprachli_bytecode as bytecode;
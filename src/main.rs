@@ -2,10 +2,12 @@ use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use clap::{ArgGroup, CommandFactory, ErrorKind, Parser};
+use clap::{ArgGroup, CommandFactory, ErrorKind, Parser, ValueEnum};
 
 use sprachli::bytecode::{parser::parse_bytecode, Error as BytecodeError};
 use sprachli::compiler::{write_bytecode, Error as CompilerError, Module};
+#[cfg(feature = "mmap")]
+use sprachli::mmap::{map_bytecode_file, Mmap};
 use sprachli::parser::parse_source_file;
 use sprachli::vm::{Error as RuntimeError, Vm};
 
@@ -23,6 +25,10 @@ enum Args {
         /// defaults to `<file-without-spr-extension>.sprb`
         #[clap(short, long, value_parser)]
         out_file: Option<PathBuf>,
+
+        /// Intermediate representation to print while compiling
+        #[clap(long, value_enum, default_value_t = Emit::None)]
+        emit: Emit,
     },
     /// Run a given source or bytecode file;
     /// by default, the kind of file is determined by its extension (`.spr` or `.sprb`)
@@ -48,9 +54,24 @@ enum Args {
         /// Output the generated bytecode to `<file-without-spr-extension>.sprb`; implies `--source`
         #[clap(long = "out", value_parser)]
         output: bool,
+
+        /// Intermediate representation to print before running
+        #[clap(long, value_enum, default_value_t = Emit::None)]
+        emit: Emit,
     },
 }
 
+/// What to print in addition to the program's own output
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Emit {
+    /// The parsed syntax tree, as s-expressions
+    Ast,
+    /// The bytecode module, with disassembled function bodies
+    Bytecode,
+    /// Nothing
+    None,
+}
+
 #[test]
 fn verify_args() {
     use clap::CommandFactory;
@@ -106,18 +127,31 @@ fn read_source_from_file<P: AsRef<Path>>(path: P) -> Result<String, CompilerErro
     Ok(source)
 }
 
+#[cfg(not(feature = "mmap"))]
 fn read_bytecode_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, CompilerError> {
     let source = fs::read(path)?;
     Ok(source)
 }
 
-fn compile_source(source: &str) -> Result<Module, CompilerError> {
-    let ast = parse_source_file(source).map_err(CompilerError::from)?;
-    println!("{ast:#?}");
+#[cfg(feature = "mmap")]
+fn read_bytecode_from_file<P: AsRef<Path>>(path: P) -> Result<Mmap, CompilerError> {
+    // SAFETY: the file is only mapped for the duration of the run; modifying
+    // it concurrently is the user's responsibility, as with any input file
+    let source = unsafe { map_bytecode_file(path)? };
+    Ok(source)
+}
 
-    let module = Module::new(ast)?;
-    println!("{module:#?}");
+fn compile_source<P: AsRef<Path>>(
+    source: &str,
+    path: P,
+    emit: Emit,
+) -> Result<Module, CompilerError> {
+    let ast = parse_source_file(source).map_err(CompilerError::from)?;
+    if emit == Emit::Ast {
+        println!("{ast:#?}");
+    }
 
+    let module = Module::with_source_path(ast, path)?;
     Ok(module)
 }
 
@@ -155,10 +189,17 @@ fn main() -> Result<(), anyhow::Error> {
     let args = Args::parse();
 
     match args {
-        Compile { file, out_file } => {
+        Compile {
+            file,
+            out_file,
+            emit,
+        } => {
             let out_file = derive_out_filename_or_exit(out_file, &file);
             let source = read_source_from_file(&file)?;
-            let module = compile_source(&source)?;
+            let module = compile_source(&source, &file, emit)?;
+            if emit == Emit::Bytecode {
+                println!("{module:#?}");
+            }
             write_bytecode_to_file(out_file, &module)?;
             Ok(())
         }
@@ -168,6 +209,7 @@ fn main() -> Result<(), anyhow::Error> {
             bytecode,
             out_file,
             output,
+            emit,
         } => {
             let kind = match (source || output || out_file.is_some(), bytecode) {
                 (true, false) => Source,
@@ -176,7 +218,11 @@ fn main() -> Result<(), anyhow::Error> {
                 _ => unreachable!("source and bytecode are mutually exclusive due to clap's argument validation"),
             };
 
-            let bytecode = match kind {
+            // the bytecode is either compiled in memory or read from the file;
+            // keep both alive in the outer scope so that `bytecode` can borrow them
+            let compiled;
+            let read;
+            let bytecode: &[u8] = match kind {
                 Source => {
                     let out_file = match (out_file, output) {
                         (None, false) => None,
@@ -184,7 +230,7 @@ fn main() -> Result<(), anyhow::Error> {
                     };
 
                     let source = read_source_from_file(&file)?;
-                    let module = compile_source(&source)?;
+                    let module = compile_source(&source, &file, emit)?;
 
                     if let Some(out_file) = out_file {
                         write_bytecode_to_file(out_file, &module)?;
@@ -192,17 +238,21 @@ fn main() -> Result<(), anyhow::Error> {
 
                     let mut bytecode = Vec::new();
                     write_bytecode(&mut bytecode, &module).map_err(CompilerError::from)?;
-                    bytecode
+                    compiled = bytecode;
+                    &compiled
+                }
+                Bytecode => {
+                    read = read_bytecode_from_file(&file)?;
+                    &read
                 }
-                Bytecode => read_bytecode_from_file(&file)?,
             };
 
-            println!("{bytecode:?}");
-
-            let module = parse_bytecode(&bytecode)?;
-            println!("{module:#?}");
+            let module = parse_bytecode(bytecode)?;
+            if emit == Emit::Bytecode {
+                println!("{module:#?}");
+            }
 
-            let result = Vm::new(module).run()?;
+            let result = Vm::new(&module)?.run()?;
 
             println!("{result:?}");
 
@@ -0,0 +1,22 @@
+//! Memory-mapped bytecode files
+//!
+//! Since a [`Module`](crate::bytecode::Module) borrows its bytecode, a mapped
+//! file can be parsed and run directly, without first copying it into memory.
+
+use std::fs::File;
+use std::io::Result;
+use std::path::Path;
+
+pub use memmap2::Mmap;
+
+/// Maps the bytecode file at `path` into memory. The map derefs to `[u8]` and
+/// can be passed to [`parse_bytecode`](crate::bytecode::parser::parse_bytecode).
+///
+/// # Safety
+///
+/// The file must not be modified or truncated while it is mapped; see
+/// [`Mmap::map`].
+pub unsafe fn map_bytecode_file<P: AsRef<Path>>(path: P) -> Result<Mmap> {
+    let file = File::open(path)?;
+    Mmap::map(&file)
+}
@@ -0,0 +1,120 @@
+//! Native functions that are available to every program
+
+use std::collections::HashMap;
+
+use super::value::ValueRef;
+use super::{Error, Native, Result, Value};
+
+pub fn natives<'b>() -> HashMap<&'b str, Native<'b>> {
+    let natives = [
+        Native::new("len", 1, len),
+        Native::new("push", 2, push),
+        Native::new("get", 2, get),
+        Native::new("map_get", 2, map_get),
+        Native::new("map_set", 3, map_set),
+        Native::new("map_has", 2, map_has),
+        Native::new("assert", 1, assert),
+        Native::new("panic", 1, panic),
+        Native::new("cmp", 2, cmp),
+    ];
+    natives
+        .into_iter()
+        .map(|native| (native.name(), native))
+        .collect()
+}
+
+/// Converts `index` to a position in `list`, failing if it is out of bounds.
+pub fn list_index(list: &[Value<'_>], index: &Value<'_>) -> Result<usize> {
+    checked_index(index, list.len(), "list")
+}
+
+/// Converts `index` to a position in `bytes`, failing if it is out of bounds.
+pub fn bytes_index(bytes: &[u8], index: &Value<'_>) -> Result<usize> {
+    checked_index(index, bytes.len(), "bytes")
+}
+
+fn checked_index(index: &Value<'_>, len: usize, kind: &str) -> Result<usize> {
+    index
+        .as_i64()
+        .ok()
+        .and_then(|index| usize::try_from(index).ok())
+        .filter(|&index| index < len)
+        .ok_or_else(|| {
+            Error::ValueError(format!(
+                "index {index:?} out of bounds for {kind} of length {len}",
+            ))
+        })
+}
+
+/// `len(list)`: the number of elements of the list.
+fn len<'b>(args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    let len = args[0].as_list()?.len();
+    Ok(Value::from(len as i64))
+}
+
+/// `push(list, value)`: a copy of the list with the value appended.
+fn push<'b>(mut args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    let value = args.pop().unwrap();
+    let mut elements = args[0].as_list()?.to_vec();
+    elements.push(value);
+    Ok(Value::list(elements))
+}
+
+/// `get(list, index)`: the element of the list at the index.
+fn get<'b>(args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    let list = args[0].as_list()?;
+    let index = list_index(list, &args[1])?;
+    Ok(list[index].clone())
+}
+
+/// `map_get(map, key)`: the value for the key, or unit if the map doesn't
+/// contain the key. Use `map_has` to tell a missing key from a unit value.
+fn map_get<'b>(args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    let map = args[0].as_map()?;
+    let value = map.get(args[1].as_string()?).cloned();
+    Ok(value.unwrap_or_else(Value::unit))
+}
+
+/// `map_set(map, key, value)`: a copy of the map with the key set to the
+/// value.
+fn map_set<'b>(mut args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    let value = args.pop().unwrap();
+    let key = args[1].as_string()?.to_string();
+    let mut entries = args[0].as_map()?.clone();
+    entries.insert(key, value);
+    Ok(Value::map(entries))
+}
+
+/// `map_has(map, key)`: whether the map contains the key.
+fn map_has<'b>(args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    let map = args[0].as_map()?;
+    Ok(Value::bool(map.contains_key(args[1].as_string()?)))
+}
+
+/// `assert(condition)`: fails with [`Error::AssertionFailed`] if the condition
+/// is false.
+fn assert<'b>(args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    if !args[0].as_bool()? {
+        Err(Error::AssertionFailed)?;
+    }
+    Ok(Value::unit())
+}
+
+/// `cmp(a, b)`: `-1`, `0` or `1` if `a` is less than, equal to or greater
+/// than `b`. Numbers are compared by value like with `<`, strings
+/// lexicographically.
+fn cmp<'b>(args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    use ValueRef::*;
+
+    let ordering = match (args[0].get_ref(), args[1].get_ref()) {
+        (Some(Number(left)), Some(Number(right))) => left.cmp(right),
+        (Some(String(left)), Some(String(right))) => left.cmp(right),
+        _ => Err(Error::TypeError("two numbers or two strings".to_string()))?,
+    };
+    Ok(Value::from(ordering as i64))
+}
+
+/// `panic(message)`: fails with [`Error::Panic`], carrying the message.
+fn panic<'b>(args: Vec<Value<'b>>) -> Result<Value<'b>> {
+    Err(Error::Panic(args[0].to_string()))
+}
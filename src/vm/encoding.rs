@@ -0,0 +1,204 @@
+//! A stable binary encoding of values, for exchanging data between a host and
+//! a script
+//!
+//! The encoding is independent of the bytecode format. Each value starts with
+//! a tag byte; lengths and counts are big-endian `u32`s. Numbers are encoded
+//! as decimal strings, so that they round-trip exactly.
+
+use std::collections::BTreeMap;
+
+use super::value::BoxedValue;
+use super::{Error, Result, Value};
+use crate::bytecode::{Constant, Number};
+
+const UNIT: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const NUMBER: u8 = 3;
+const STRING: u8 = 4;
+const LIST: u8 = 5;
+const STRUCT: u8 = 6;
+const MAP: u8 = 7;
+const BYTES: u8 = 8;
+
+/// How deeply lists, maps and structs may be nested in decoded input. Decoding
+/// is recursive, so deeper input would overflow the stack.
+pub const MAX_DECODING_DEPTH: usize = 256;
+
+impl<'b> Value<'b> {
+    /// Encodes this value. Unit, bools, numbers, strings, byte strings,
+    /// lists, maps and structs (including tuples) can be encoded; functions
+    /// and struct types can't.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.encode_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn encode_into(&self, bytes: &mut Vec<u8>) -> Result<()> {
+        use self::Constant as C;
+        use Value::*;
+
+        match self {
+            Unit => bytes.push(UNIT),
+            Bool(false) => bytes.push(FALSE),
+            Bool(true) => bytes.push(TRUE),
+            Constant(C::Number(value)) => encode_number(bytes, value)?,
+            Boxed(value) => match value.as_ref() {
+                BoxedValue::Number(value) => encode_number(bytes, value)?,
+                BoxedValue::String(value) => encode_str(bytes, STRING, value)?,
+            },
+            Constant(C::String(value)) => encode_str(bytes, STRING, value)?,
+            Constant(C::Bytes(value)) => encode_bytes(bytes, value)?,
+            Bytes(value) => encode_bytes(bytes, value)?,
+            List(elements) => {
+                bytes.push(LIST);
+                encode_values(bytes, elements)?;
+            }
+            Struct(value) => {
+                encode_str(bytes, STRUCT, value.struct_type())?;
+                encode_values(bytes, value.fields())?;
+            }
+            Map(entries) => {
+                bytes.push(MAP);
+                encode_len(bytes, entries.len())?;
+                for (key, value) in entries.iter() {
+                    encode_len(bytes, key.len())?;
+                    bytes.extend_from_slice(key.as_bytes());
+                    value.encode_into(bytes)?;
+                }
+            }
+            Constant(C::Function(_)) | StructType(_) | Native(_) | Droppable(_) => {
+                Err(Error::ValueError(format!("{self:?} can't be encoded")))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes a value produced by [`encode`](Self::encode). Struct type names
+    /// are borrowed from the input. The input must contain exactly one value,
+    /// nested at most [`MAX_DECODING_DEPTH`] levels deep.
+    pub fn decode(bytes: &'b [u8]) -> Result<Self> {
+        let mut decoder = Decoder { bytes, depth: 0 };
+        let value = decoder.value()?;
+        if !decoder.bytes.is_empty() {
+            Err(invalid("trailing bytes"))?;
+        }
+        Ok(value)
+    }
+}
+
+fn encode_len(bytes: &mut Vec<u8>, len: usize) -> Result<()> {
+    let len = u32::try_from(len).map_err(|_| Error::ValueError("value too large".to_string()))?;
+    bytes.extend_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+fn encode_str(bytes: &mut Vec<u8>, tag: u8, value: &str) -> Result<()> {
+    bytes.push(tag);
+    encode_len(bytes, value.len())?;
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn encode_bytes(bytes: &mut Vec<u8>, value: &[u8]) -> Result<()> {
+    bytes.push(BYTES);
+    encode_len(bytes, value.len())?;
+    bytes.extend_from_slice(value);
+    Ok(())
+}
+
+fn encode_number(bytes: &mut Vec<u8>, value: &Number) -> Result<()> {
+    encode_str(bytes, NUMBER, &value.to_string())
+}
+
+fn encode_values(bytes: &mut Vec<u8>, values: &[Value<'_>]) -> Result<()> {
+    encode_len(bytes, values.len())?;
+    for value in values {
+        value.encode_into(bytes)?;
+    }
+    Ok(())
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::ValueError(format!("invalid value encoding: {reason}"))
+}
+
+struct Decoder<'b> {
+    bytes: &'b [u8],
+    depth: usize,
+}
+
+impl<'b> Decoder<'b> {
+    fn take(&mut self, len: usize) -> Result<&'b [u8]> {
+        if self.bytes.len() < len {
+            Err(invalid("unexpected end of input"))?;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(u32::from_be_bytes(bytes) as usize)
+    }
+
+    fn str(&mut self) -> Result<&'b str> {
+        let len = self.len()?;
+        std::str::from_utf8(self.take(len)?).map_err(|_| invalid("invalid utf8 string"))
+    }
+
+    fn values(&mut self) -> Result<Vec<Value<'b>>> {
+        let len = self.len()?;
+        // every value takes at least one byte; don't trust the length for allocating
+        let mut values = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            values.push(self.value()?);
+        }
+        Ok(values)
+    }
+
+    fn value(&mut self) -> Result<Value<'b>> {
+        if self.depth == MAX_DECODING_DEPTH {
+            Err(invalid("too deeply nested"))?;
+        }
+        self.depth += 1;
+        let value = self.value_inner();
+        self.depth -= 1;
+        value
+    }
+
+    fn value_inner(&mut self) -> Result<Value<'b>> {
+        let value = match self.take(1)?[0] {
+            UNIT => Value::unit(),
+            FALSE => Value::bool(false),
+            TRUE => Value::bool(true),
+            NUMBER => {
+                let value = self.str()?.parse().map_err(|_| invalid("invalid number"))?;
+                Value::number(value)
+            }
+            STRING => Value::string(self.str()?.to_string()),
+            BYTES => {
+                let len = self.len()?;
+                Value::bytes(self.take(len)?.to_vec())
+            }
+            LIST => Value::list(self.values()?),
+            STRUCT => {
+                let struct_type = self.str()?;
+                Value::new_struct(struct_type, self.values()?)
+            }
+            MAP => {
+                let len = self.len()?;
+                let mut entries = BTreeMap::new();
+                for _ in 0..len {
+                    let key = self.str()?.to_string();
+                    entries.insert(key, self.value()?);
+                }
+                Value::map(entries)
+            }
+            tag => Err(invalid(&format!("unknown tag {tag}")))?,
+        };
+        Ok(value)
+    }
+}
@@ -1,5 +1,6 @@
 use bigdecimal::ParseBigDecimalError;
 
+use crate::bytecode::instruction::Instruction;
 use crate::bytecode::Error as BytecodeError;
 use crate::parser::ParseStringError;
 
@@ -7,10 +8,30 @@ use crate::parser::ParseStringError;
 pub enum Error {
     #[error("Name not known: {0}")]
     NameError(String),
+    #[error("No method {1} on struct {0}")]
+    MissingMethod(String, String),
+    #[error("No field {1} on struct {0}")]
+    MissingField(String, String),
     #[error("Type error, expected: {0}")]
     TypeError(String),
+    #[error("Type error: cannot use bool in arithmetic")]
+    BoolArithmetic,
     #[error("Value error: {0}")]
     ValueError(String),
+    #[error("Assertion failed")]
+    AssertionFailed,
+    #[error("Panic: {0}")]
+    Panic(String),
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid main signature: takes {0} parameter(s); call it with call_function instead")]
+    MainSignature(usize),
+    #[error("Out of gas: the instruction budget was exhausted")]
+    OutOfGas,
+    #[error("Step limit exceeded: a single call of {0} executed too many instructions")]
+    CallStepLimit(String),
+    #[error("Cancelled: the run was interrupted by the host")]
+    Cancelled,
     #[error("Unsupported language construct: {0}")]
     Unsupported(&'static str),
     #[error("Internal Error: {0}")]
@@ -23,6 +44,8 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum InternalError {
     #[error("Constant #{0} not in constant table of len {1}")]
     InvalidConstant(usize, usize),
+    #[error("Global {0} refers to constant #{1}, which is a name and not a value")]
+    InvalidGlobal(String, usize),
     #[error("Invalid local variable #{0}")]
     InvalidLocal(usize),
     #[error("Constant #{0} was not a {1}")]
@@ -37,4 +60,8 @@ pub enum InternalError {
     InvalidBytecode(#[from] BytecodeError),
     #[error("Tried to jump to nonexistent instruction")]
     InvalidJump,
+    #[error("Instruction {0:?} did not change the stack height by its stack effect")]
+    InvalidStackEffect(Instruction),
+    #[error("Function did not leave exactly its {0} parameter(s) and one result on the stack")]
+    InvalidStackFrame(usize),
 }
@@ -1,36 +1,358 @@
+mod builtins;
+mod encoding;
 mod error;
+mod owned;
+mod precision;
 mod stack;
 mod value;
 
-use bigdecimal::num_bigint::{BigInt, ToBigInt};
-use bigdecimal::num_traits::ToPrimitive;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use bigdecimal::num_bigint::{BigInt, Sign, ToBigInt};
+use bigdecimal::num_traits::{ToPrimitive, Zero};
 use bigdecimal::BigDecimal;
 
 use crate::ast::{BinaryOperator, UnaryOperator};
 use crate::bytecode::instruction::{InlineConstant, Instruction, Offset};
-use crate::bytecode::{Constant, InstructionIter, Module};
+use crate::bytecode::{Constant, Function, InstructionIter, Module};
 use stack::Stack;
 
+pub use encoding::MAX_DECODING_DEPTH;
 pub use error::*;
-pub use value::Value;
+pub use owned::OwnedVm;
+pub use precision::{PrecisionContext, RoundingMode};
+pub use value::{DestructorFn, Droppable, Native, NativeFn, Struct, Value};
+
+/// The default for [`Vm::with_max_integer_bits`].
+pub const DEFAULT_MAX_INTEGER_BITS: u64 = 1 << 16;
+
+/// What dividing by zero results in, see [`Vm::with_div_by_zero`]. This
+/// applies to both `/` and `%`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DivByZero {
+    /// Fail with a [`Error::ValueError`]
+    #[default]
+    Error,
+    /// Numbers can't be infinite, so this results in the sentinel
+    /// [`DIV_BY_ZERO_SENTINEL`] with the sign of the dividend instead; zero
+    /// divided by zero is zero.
+    Infinity,
+    /// Result in unit
+    Unit,
+}
+
+/// How many instructions are executed between checks of the flag set with
+/// [`Vm::with_cancellation`].
+const CANCELLATION_CHECK_INTERVAL: u64 = 1024;
+
+/// The result of dividing a positive number by zero under
+/// [`DivByZero::Infinity`].
+pub const DIV_BY_ZERO_SENTINEL: i64 = i64::MAX;
+
+/// Where `print` and `println` write to, see [`Vm::with_output`].
+#[derive(Clone)]
+struct Output(Arc<Mutex<dyn Write + Send>>);
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Output")
+    }
+}
+
+/// The destructors of droppable values by kind, see [`Vm::with_destructor`].
+#[derive(Clone, Default)]
+struct Destructors<'b>(HashMap<&'b str, Arc<DestructorFn<'b>>>);
+
+impl fmt::Debug for Destructors<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct Vm<'b> {
-    module: Module<'b>,
+pub struct Vm<'m, 'b> {
+    module: &'m Module<'b>,
     stack: Stack<'b>,
+    globals: HashMap<&'b str, Value<'b>>,
+    /// The values loaded by `LoadFunction`, indexed by its operand: the
+    /// functions named by string constants, or the host's overrides of them
+    functions: Vec<Option<Value<'b>>>,
+    natives: HashMap<&'b str, Native<'b>>,
+    destructors: Destructors<'b>,
+    max_integer_bits: u64,
+    truthy_conditions: bool,
+    strict_equality: bool,
+    precision: Option<PrecisionContext>,
+    div_by_zero: DivByZero,
+    output: Output,
+    verify: bool,
+    gas: Option<u64>,
+    consumed_gas: u64,
+    call_step_limit: Option<u64>,
+    cancellation: Option<Arc<AtomicBool>>,
+    depth: usize,
+    peak_depth: usize,
 }
 
-impl<'b> Vm<'b> {
-    pub fn new(module: Module<'b>) -> Self {
-        Self {
+impl<'m, 'b> Vm<'m, 'b> {
+    /// Creates a VM for the given module. This fails if a global doesn't
+    /// refer to a value, but to the name of a global, struct type or method. The module is borrowed,
+    /// so that any number of VMs can run the same module.
+    pub fn new(module: &'m Module<'b>) -> Result<Self> {
+        Self::check_globals(module)?;
+        let globals = module
+            .globals()
+            .iter()
+            .map(|(&name, &index)| {
+                // `check_globals` made sure that the constant exists
+                let value = module.constant(index).unwrap().clone();
+                (name, Value::constant(value))
+            })
+            .collect();
+        let functions = module
+            .constants()
+            .iter()
+            .map(|constant| match constant {
+                Constant::String(name) => module
+                    .function(name)
+                    .map(|function| Value::constant(Constant::Function(function.clone()))),
+                _ => None,
+            })
+            .collect();
+        Ok(Self {
             module,
             stack: Stack::new(),
+            globals,
+            functions,
+            natives: builtins::natives(),
+            destructors: Destructors::default(),
+            max_integer_bits: DEFAULT_MAX_INTEGER_BITS,
+            truthy_conditions: false,
+            strict_equality: false,
+            precision: None,
+            div_by_zero: DivByZero::Error,
+            output: Output(Arc::new(Mutex::new(io::stdout()))),
+            verify: false,
+            gas: None,
+            consumed_gas: 0,
+            call_step_limit: None,
+            cancellation: None,
+            depth: 0,
+            peak_depth: 0,
+        })
+    }
+
+    fn check_globals(module: &Module<'b>) -> Result<()> {
+        for (&name, &index) in module.globals() {
+            let constant = module
+                .constant(index)
+                .ok_or_else(|| InternalError::InvalidConstant(index, module.constants().len()))?;
+            // names are string constants as well; a global referring to one
+            // was most likely meant to refer to the named value
+            let is_name = |name: &str| {
+                module.globals().contains_key(name)
+                    || module.struct_types().contains_key(name)
+                    || module.methods().keys().any(|&(_, method)| method == name)
+            };
+            if matches!(constant, Constant::String(value) if is_name(value)) {
+                Err(InternalError::InvalidGlobal(name.to_string(), index))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Limits the bit width of integers produced by shifting, so that e.g.
+    /// `1 << 1000000` results in an error instead of a huge allocation.
+    pub fn with_max_integer_bits(mut self, max_integer_bits: u64) -> Self {
+        self.max_integer_bits = max_integer_bits;
+        self
+    }
+
+    /// Adds a global binding, or overrides one of the module, so that the
+    /// program can read a value provided by the host.
+    pub fn set_global(&mut self, name: &'b str, value: Value<'b>) {
+        let constants = self.module.constants();
+        for (function, constant) in self.functions.iter_mut().zip(constants) {
+            if function.is_some() && matches!(constant, Constant::String(n) if *n == name) {
+                *function = Some(value.clone());
+            }
         }
+        self.globals.insert(name, value);
+    }
+
+    /// Makes a native function available to the program under the given
+    /// name. Globals take precedence over natives, and natives replace
+    /// built-in ones such as `len` or `assert`.
+    pub fn with_native<F>(mut self, name: &'b str, arity: usize, function: F) -> Self
+    where
+        F: Fn(Vec<Value<'b>>) -> Result<Value<'b>> + Send + Sync + 'b,
+    {
+        self.natives
+            .insert(name, Native::new(name, arity, function));
+        self
+    }
+
+    /// Registers the cleanup for droppable values of the given kind (see
+    /// [`Value::droppable`]). It receives the wrapped value once the VM drops
+    /// the last reference to it, e.g. when the scope of a local ends or a list
+    /// containing it is discarded.
+    pub fn with_destructor<F>(mut self, kind: &'b str, destructor: F) -> Self
+    where
+        F: Fn(Value<'b>) -> Result<()> + Send + Sync + 'b,
+    {
+        self.destructors.0.insert(kind, Arc::new(destructor));
+        self
     }
 
+    /// Lets conditions and `!` accept any value, coercing it according to
+    /// [`Value::is_truthy`]. By default, only booleans are accepted.
+    pub fn with_truthy_conditions(mut self, truthy_conditions: bool) -> Self {
+        self.truthy_conditions = truthy_conditions;
+        self
+    }
+
+    /// Makes `==` and `!=` fail with a [`Error::TypeError`] when comparing
+    /// values of different types, e.g. `1 == "1"`. By default, such values
+    /// are simply unequal.
+    pub fn with_strict_equality(mut self, strict_equality: bool) -> Self {
+        self.strict_equality = strict_equality;
+        self
+    }
+
+    /// Rounds the results of arithmetic operations that have more fractional
+    /// digits than the given context allows, e.g. so that `1 / 3` is `0.33`
+    /// instead of a hundred digits. By default, results are exact,
+    /// except for non-terminating divisions.
+    pub fn with_precision(mut self, precision: PrecisionContext) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Determines what dividing by zero results in. By default, it is an
+    /// error.
+    pub fn with_div_by_zero(mut self, div_by_zero: DivByZero) -> Self {
+        self.div_by_zero = div_by_zero;
+        self
+    }
+
+    /// Redirects the output of `print` and `println`, e.g. to capture it in a
+    /// buffer. By default, they write to stdout.
+    pub fn with_output(mut self, output: Arc<Mutex<dyn Write + Send>>) -> Self {
+        self.output = Output(output);
+        self
+    }
+
+    /// Limits the number of instructions a single function call may execute,
+    /// not counting those of the functions it calls in turn. Unlike gas, this
+    /// doesn't bound the total work, but catches a single runaway function:
+    /// exceeding the limit fails with [`Error::CallStepLimit`], naming it.
+    pub fn with_call_step_limit(mut self, limit: u64) -> Self {
+        self.call_step_limit = Some(limit);
+        self
+    }
+
+    /// Lets the host interrupt a run, e.g. from another thread after a
+    /// timeout: once the flag is set, the VM fails with [`Error::Cancelled`].
+    /// The flag is only checked every few instructions, so the VM may run a
+    /// little longer after it was set.
+    pub fn with_cancellation(mut self, cancellation: Arc<AtomicBool>) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Enables checks that help when developing the compiler or bytecode
+    /// tools: the module is [validated](Module::validate) before running any
+    /// code, and each instruction is checked to change the stack height by its
+    /// [`stack_effect`](Instruction::stack_effect).
+    pub fn with_verification(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Runs `main`, which must not take any parameters; otherwise, this fails
+    /// with [`Error::MainSignature`].
     pub fn run(mut self) -> Result<Value<'b>> {
-        self.load_named_by_name("main")?;
-        self.call(0)?;
+        self.invoke_main()
+    }
+
+    /// Runs `main` like [`run`](Self::run), but without consuming the VM, so
+    /// that its [`stack`](Self::stack) can be inspected afterwards, e.g. in a
+    /// REPL. The stack is cleared before running.
+    pub fn run_in_place(&mut self) -> Result<Value<'b>> {
+        self.stack = Stack::new();
+        self.invoke_main()
+    }
+
+    /// Runs `main`, executing at most `gas` instructions before failing with
+    /// [`Error::OutOfGas`]. Unlike [`run`](Self::run), this doesn't consume
+    /// the VM, so that [`consumed_gas`](Self::consumed_gas) and
+    /// [`peak_depth`](Self::peak_depth) can be inspected afterwards, even if
+    /// the run failed.
+    pub fn run_with_gas(&mut self, gas: u64) -> Result<Value<'b>> {
+        self.gas = Some(gas);
+        self.consumed_gas = 0;
+        self.depth = 0;
+        self.peak_depth = 0;
+        self.invoke_main()
+    }
+
+    /// The number of instructions executed by the last
+    /// [`run_with_gas`](Self::run_with_gas).
+    pub fn consumed_gas(&self) -> u64 {
+        self.consumed_gas
+    }
+
+    /// The deepest nesting of function calls reached by the last
+    /// [`run_with_gas`](Self::run_with_gas); calling `main` is depth one.
+    pub fn peak_depth(&self) -> usize {
+        self.peak_depth
+    }
+
+    /// The values remaining on the stack after the last
+    /// [`run_in_place`](Self::run_in_place) or
+    /// [`run_with_gas`](Self::run_with_gas). After a successful run, the
+    /// result has been popped and the stack is empty; anything left means
+    /// that values were leaked. After a failed run, this is the stack at the
+    /// point of failure.
+    pub fn stack(&self) -> &[Value<'b>] {
+        self.stack.as_slice()
+    }
+
+    pub fn call_function<I>(mut self, name: &str, args: I) -> Result<Value<'b>>
+    where
+        I: IntoIterator<Item = Value<'b>>,
+    {
+        self.invoke(name, args)
+    }
+
+    fn invoke_main(&mut self) -> Result<Value<'b>> {
+        // check this before calling, so that the error is not a generic arity mismatch
+        if let Some(Constant::Function(main)) = self.module.global("main") {
+            if main.arity() != 0 {
+                Err(Error::MainSignature(main.arity()))?;
+            }
+        }
+        self.invoke("main", [])
+    }
+
+    fn invoke<I>(&mut self, name: &str, args: I) -> Result<Value<'b>>
+    where
+        I: IntoIterator<Item = Value<'b>>,
+    {
+        if self.verify {
+            self.module.validate().map_err(InternalError::from)?;
+        }
+        self.load_named_by_name(name)?;
+        let mut arity = 0;
+        for arg in args {
+            self.stack.push(arg)?;
+            arity += 1;
+        }
+        self.call(arity)?;
 
         // the call opcode checks that only one value remains on the stack
         self.stack.pop()
@@ -52,15 +374,7 @@ impl<'b> Vm<'b> {
         Ok(value)
     }
 
-    fn get_global(&self, name: &str) -> Result<&Constant<'b>> {
-        let value = self
-            .module
-            .global(name)
-            .ok_or_else(|| Error::NameError(name.to_string()))?;
-        Ok(value)
-    }
-
-    fn get_local(&mut self, offset: usize, index: usize) -> Result<&Value<'b>> {
+    fn get_local(&self, offset: usize, index: usize) -> Result<&Value<'b>> {
         let value = self
             .stack
             .get(offset + index)
@@ -83,6 +397,17 @@ impl<'b> Vm<'b> {
         self.stack.push(Value::constant(value))
     }
 
+    /// Loads a global function of the module, which was resolved when
+    /// creating the VM, so that no name needs to be looked up.
+    fn load_function(&mut self, index: usize) -> Result<()> {
+        let value = self
+            .functions
+            .get(index)
+            .and_then(Option::clone)
+            .ok_or(InternalError::InvalidConstantType(index, "function name"))?;
+        self.stack.push(value)
+    }
+
     fn inline_constant(&mut self, constant: InlineConstant) -> Result<()> {
         use InlineConstant::*;
 
@@ -107,13 +432,175 @@ impl<'b> Vm<'b> {
     fn store_local(&mut self, offset: usize, index: usize) -> Result<()> {
         let value = self.stack.pop()?;
         let var = self.get_local_mut(offset, index)?;
-        *var = value;
-        Ok(())
+        let value = std::mem::replace(var, value);
+        self.drop_value(value)
     }
 
     fn load_named_by_name(&mut self, name: &str) -> Result<()> {
-        let value = self.get_global(name).cloned()?;
-        self.stack.push(Value::constant(value))
+        if let Some(value) = self.globals.get(name).cloned() {
+            return self.stack.push(value);
+        }
+
+        // struct types are not globals, but can be called to construct a struct
+        if let Some((&name, _)) = self.module.struct_types().get_key_value(name) {
+            return self.stack.push(Value::struct_type(name));
+        }
+
+        if let Some(native) = self.natives.get(name) {
+            return self.stack.push(Value::Native(native.name()));
+        }
+
+        Err(Error::NameError(name.to_string()))
+    }
+
+    fn load_method(&mut self, index: usize) -> Result<()> {
+        let name = self.get_string_constant(index)?;
+        let receiver = self.stack.pop()?;
+        let struct_type = receiver.as_struct()?.struct_type();
+        let method = self
+            .module
+            .method(struct_type, name)
+            .cloned()
+            .ok_or_else(|| Error::MissingMethod(struct_type.to_string(), name.to_string()))?;
+
+        // the receiver becomes the first argument, so it goes above the method
+        self.stack.push(Value::constant(method))?;
+        self.stack.push(receiver)
+    }
+
+    fn load_positional_field(&mut self, index: usize) -> Result<()> {
+        let receiver = self.stack.pop()?;
+        let receiver = receiver.as_struct()?;
+        let value = receiver.fields().get(index).cloned().ok_or_else(|| {
+            Error::MissingField(receiver.struct_type().to_string(), format!("_{index}"))
+        })?;
+        self.stack.push(value)
+    }
+
+    fn load_named_field(&mut self, index: usize) -> Result<()> {
+        use crate::bytecode::StructType::*;
+
+        let name = self.get_string_constant(index)?;
+        let receiver = self.stack.pop()?;
+        let receiver = receiver.as_struct()?;
+        let struct_type = receiver.struct_type();
+        let missing_field = || Error::MissingField(struct_type.to_string(), name.to_string());
+
+        let index = match self.module.struct_type(struct_type) {
+            Some(Named(fields)) => fields.iter().position(|field| *field == name),
+            _ => None,
+        }
+        .ok_or_else(missing_field)?;
+        let value = receiver
+            .fields()
+            .get(index)
+            .cloned()
+            .ok_or_else(missing_field)?;
+        self.stack.push(value)
+    }
+
+    fn make_tuple(&mut self, len: usize) -> Result<()> {
+        let elements = self.stack.pop_multiple(len)?.collect();
+        self.stack.push(Value::tuple(elements))
+    }
+
+    fn unpack(&mut self, len: usize) -> Result<()> {
+        let value = self.stack.pop()?;
+        let value = value.as_struct()?;
+        if value.fields().len() != len {
+            Err(Error::ValueError(format!(
+                "can't unpack {} values into {len} variables",
+                value.fields().len(),
+            )))?;
+        }
+        for element in value.fields() {
+            self.stack.push(element.clone())?;
+        }
+        Ok(())
+    }
+
+    fn make_list(&mut self, len: usize) -> Result<()> {
+        let elements = self.stack.pop_multiple(len)?.collect();
+        self.stack.push(Value::list(elements))
+    }
+
+    fn make_map(&mut self, len: usize) -> Result<()> {
+        let mut map = BTreeMap::new();
+        {
+            let mut entries = self.stack.pop_multiple(2 * len)?;
+            while let Some(key) = entries.next() {
+                // an even number of values was popped, so every key has a value
+                let value = entries.next().unwrap();
+                map.insert(key.as_string()?.to_string(), value);
+            }
+        }
+        self.stack.push(Value::map(map))
+    }
+
+    fn stringify(&mut self) -> Result<()> {
+        let value = self.stack.pop()?;
+        // don't copy values that already are strings
+        if matches!(value.get_ref(), Some(value::ValueRef::String(_))) {
+            return self.stack.push(value);
+        }
+        self.stack.push(Value::string(value.to_string()))
+    }
+
+    fn index_get(&mut self) -> Result<()> {
+        let index = self.stack.pop()?;
+        let list = self.stack.pop()?;
+        // indexing a byte string results in the byte's numeric value
+        if let Some(value::ValueRef::Bytes(bytes)) = list.get_ref() {
+            let byte = bytes[builtins::bytes_index(bytes, &index)?];
+            return self.stack.push(Value::from(i64::from(byte)));
+        }
+        let list = list.as_list()?;
+        let value = list[builtins::list_index(list, &index)?].clone();
+        self.stack.push(value)
+    }
+
+    fn index_set(&mut self) -> Result<()> {
+        let value = self.stack.pop()?;
+        let index = self.stack.pop()?;
+        let Value::List(mut list) = self.stack.pop()? else {
+            return Err(Error::TypeError("list".to_string()));
+        };
+        let index = builtins::list_index(&list, &index)?;
+        // this only copies the list if it is shared
+        Arc::make_mut(&mut list)[index] = value;
+        self.stack.push(Value::List(list))
+    }
+
+    fn print(&mut self, line: bool) -> Result<()> {
+        let value = self.stack.pop()?;
+        let mut output = self.output.0.lock().unwrap_or_else(PoisonError::into_inner);
+        write!(output, "{value}")?;
+        if line {
+            writeln!(output)?;
+        }
+        self.stack.push(Value::unit())
+    }
+
+    fn construct(&mut self, name: &'b str, arity: usize) -> Result<()> {
+        use crate::bytecode::StructType::*;
+
+        let struct_type = self
+            .module
+            .struct_type(name)
+            .ok_or_else(|| Error::NameError(name.to_string()))?;
+        let field_count = match struct_type {
+            Empty => 0,
+            Positional(count) => *count,
+            Named(fields) => fields.len(),
+        };
+        if arity != field_count {
+            Err(Error::ValueError(format!(
+                "wrong field number for {name}; expected {field_count}, got {arity}",
+            )))?;
+        }
+
+        let fields = self.stack.pop_multiple(arity)?.collect();
+        self.stack.push(Value::new_struct(name, fields))
     }
 
     fn unary(&mut self, operator: UnaryOperator) -> Result<()> {
@@ -123,14 +610,14 @@ impl<'b> Vm<'b> {
 
         let value = match operator {
             Negate => Value::number(-right.as_number()?.clone()),
-            Not => Value::bool(!right.as_bool()?),
+            Not => Value::bool(!self.condition(&right)?),
         };
 
         self.stack.push(value)
     }
 
     fn binary(&mut self, operator: BinaryOperator) -> Result<()> {
-        use value::ValueRef::*;
+        use value::ValueRef::{self as R, *};
         use BinaryOperator::*;
         use Value::*;
 
@@ -155,38 +642,129 @@ impl<'b> Vm<'b> {
             [ops.next().unwrap(), ops.next().unwrap()]
         };
 
+        /// Bools are never implicitly numeric; using one in arithmetic gets a
+        /// more helpful error than other non-numbers.
+        fn to_operand<'a>(value: &'a Value<'_>) -> Result<&'a BigDecimal> {
+            match value {
+                Bool(_) => Err(Error::BoolArithmetic),
+                value => value.as_number(),
+            }
+        }
+
+        let precision = self.precision;
         let arithmetic = |op: fn(&BigDecimal, &BigDecimal) -> BigDecimal| {
-            let result = op(left.as_number()?, right.as_number()?);
+            let (left, right) = (to_operand(&left)?, to_operand(&right)?);
+            let result = op(left, right);
+            let result = match precision {
+                Some(precision) => precision.round(result),
+                None => result,
+            };
             Ok(Value::number(result))
         };
 
-        let bitshift = |op: fn(BigInt, isize) -> BigInt| {
+        // dividing by zero would give a non-finite result, see `value::finite`
+        let div_by_zero = self.div_by_zero;
+        let division = |op: fn(&BigDecimal, &BigDecimal) -> BigDecimal| {
+            if to_operand(&right)?.is_zero() {
+                return match div_by_zero {
+                    DivByZero::Error => Err(Error::ValueError("division by zero".to_string())),
+                    DivByZero::Infinity => {
+                        let sentinel = BigDecimal::from(DIV_BY_ZERO_SENTINEL);
+                        let result = match to_operand(&left)?.sign() {
+                            Sign::Plus => sentinel,
+                            Sign::Minus => -sentinel,
+                            Sign::NoSign => BigDecimal::zero(),
+                        };
+                        Ok(Value::number(result))
+                    }
+                    DivByZero::Unit => Ok(Value::unit()),
+                };
+            }
+            arithmetic(op)
+        };
+
+        let max_integer_bits = self.max_integer_bits;
+        let bitshift = |op: fn(BigInt, isize) -> BigInt, grows: bool| {
             let left = left.as_number().and_then(to_integer)?;
             let right = right.as_number().and_then(to_isize)?;
+            if right < 0 {
+                Err(Error::ValueError("negative shift amount".to_string()))?;
+            }
+
+            // check the result's size before shifting, so it is never allocated
+            if grows && left.bits().saturating_add(right as u64) > max_integer_bits {
+                Err(Error::ValueError("result too large".to_string()))?;
+            }
+
             let result = op(left, right);
             Ok(Value::number(result.into()))
         };
 
-        let bitwise = |op: fn(BigInt, BigInt) -> BigInt| {
+        let bitwise = |op: fn(BigInt, BigInt) -> BigInt, bool_op: fn(bool, bool) -> bool| {
+            // on two bools, the bitwise operators act as non-short-circuiting logical operators
+            if let (Bool(left), Bool(right)) = (&left, &right) {
+                return Ok(Value::bool(bool_op(*left, *right)));
+            }
+
             let left = left.as_number().and_then(to_integer)?;
             let right = right.as_number().and_then(to_integer)?;
             let result = op(left, right);
             Ok(Value::number(result.into()))
         };
 
-        let equality_comparison = |eq: bool| -> Result<Value> {
-            let result = match (&left, &right) {
+        /// Compares the elements of two lists or maps pairwise, stopping at the
+        /// first unequal pair.
+        fn all_equal<'a, 'b: 'a>(
+            pairs: impl Iterator<Item = (&'a Value<'b>, &'a Value<'b>)>,
+            strict: bool,
+        ) -> Result<bool> {
+            for (left, right) in pairs {
+                if !equals(left, right, strict)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+
+        fn equals(left: &Value<'_>, right: &Value<'_>, strict: bool) -> Result<bool> {
+            let result = match (left, right) {
                 (Unit, Unit) => true,
                 (Bool(left), Bool(right)) => left == right,
-                _ => match (left.get_ref().unwrap(), right.get_ref().unwrap()) {
-                    (Number(left), Number(right)) => left == right,
-                    (String(left), String(right)) => left == right,
+                (Value::StructType(left), Value::StructType(right)) => left == right,
+                // structs are compared by identity, lists and maps element-wise
+                (Value::Struct(left), Value::Struct(right)) => Arc::ptr_eq(left, right),
+                (Value::List(left), Value::List(right)) => {
+                    Arc::ptr_eq(left, right)
+                        || left.len() == right.len()
+                            && all_equal(left.iter().zip(right.iter()), strict)?
+                }
+                (Value::Map(left), Value::Map(right)) => {
+                    Arc::ptr_eq(left, right)
+                        || left.len() == right.len()
+                            && left.keys().eq(right.keys())
+                            && all_equal(left.values().zip(right.values()), strict)?
+                }
+                (Value::Native(left), Value::Native(right)) => left == right,
+                (Value::Droppable(left), Value::Droppable(right)) => Arc::ptr_eq(left, right),
+                _ => match (left.get_ref(), right.get_ref()) {
+                    // compare by value, so that numbers of different scale (e.g. `3` and `3.0`) are equal
+                    (Some(Number(left)), Some(Number(right))) => left.cmp(right).is_eq(),
+                    (Some(String(left)), Some(String(right))) => left == right,
+                    (Some(R::Bytes(left)), Some(R::Bytes(right))) => left == right,
                     // functions are always constants, so two values referring to the same function contain the same reference
-                    (Function(left), Function(right)) => std::ptr::eq(left, right),
+                    (Some(Function(left)), Some(Function(right))) => std::ptr::eq(left, right),
+                    // all remaining combinations are values of different types
+                    _ if strict => Err(Error::TypeError("operands of the same type".to_string()))?,
                     _ => false,
                 },
             };
 
+            Ok(result)
+        }
+
+        let strict_equality = self.strict_equality;
+        let equality_comparison = |eq: bool| -> Result<Value> {
+            let result = equals(&left, &right, strict_equality)?;
             Ok(Value::bool(result == eq))
         };
 
@@ -197,15 +775,22 @@ impl<'b> Vm<'b> {
 
         let value = match operator {
             Multiply => arithmetic(|a, b| a * b),
-            Divide => arithmetic(|a, b| a / b),
-            Modulo => arithmetic(|a, b| a % b),
-            Add => arithmetic(|a, b| a + b),
+            // normalize the result, so that e.g. `84.0 / 2` is `42`, not `42.0`
+            Divide => division(|a, b| normalize(a / b)),
+            Modulo => division(|a, b| a % b),
+            Add => match (left.get_ref(), right.get_ref()) {
+                // adding two strings concatenates them
+                (Some(String(left)), Some(String(right))) => {
+                    Ok(Value::string(left.to_owned() + right))
+                }
+                _ => arithmetic(|a, b| a + b),
+            },
             Subtract => arithmetic(|a, b| a - b),
-            RightShift => bitshift(|a, b| a >> b),
-            LeftShift => bitshift(|a, b| a << b),
-            BitAnd => bitwise(|a, b| a & b),
-            BitXor => bitwise(|a, b| a ^ b),
-            BitOr => bitwise(|a, b| a | b),
+            RightShift => bitshift(|a, b| a >> b, false),
+            LeftShift => bitshift(|a, b| a << b, true),
+            BitAnd => bitwise(|a, b| a & b, |a, b| a & b),
+            BitXor => bitwise(|a, b| a ^ b, |a, b| a ^ b),
+            BitOr => bitwise(|a, b| a | b, |a, b| a | b),
             Equals => equality_comparison(true),
             NotEquals => equality_comparison(false),
             Greater => number_comparison(|a, b| a > b),
@@ -224,70 +809,266 @@ impl<'b> Vm<'b> {
         Ok(())
     }
 
+    fn condition(&self, value: &Value) -> Result<bool> {
+        if self.truthy_conditions {
+            Ok(value.is_truthy())
+        } else {
+            value.as_bool()
+        }
+    }
+
     fn jump_if(&mut self, iter: &mut InstructionIter, offset: Offset) -> Result<()> {
         use InternalError::*;
 
-        let condition = self.stack.pop()?.as_bool()?;
-        if condition {
+        let condition = self.stack.pop()?;
+        if self.condition(&condition)? {
             iter.jump(offset).map_err(|_| InvalidJump)?;
         }
         Ok(())
     }
 
+    fn switch(&mut self, iter: &mut InstructionIter, len: usize) -> Result<()> {
+        use InternalError::*;
+
+        let value = self.stack.pop()?;
+        // like comparing with `==`, other types only fail with strict equality
+        let case = match value.as_number() {
+            Ok(number) => number
+                .to_usize()
+                .filter(|&case| number.is_integer() && case < len),
+            Err(_) if self.strict_equality => {
+                Err(Error::TypeError("operands of the same type".to_string()))?
+            }
+            Err(_) => None,
+        };
+
+        // execute the case's jump in the table, or skip the whole table
+        let entry_len = Instruction::Jump(Offset::Forward(0)).encoded_len();
+        let offset = Offset::Forward(case.unwrap_or(len) * entry_len);
+        iter.jump(offset).map_err(|_| InvalidJump)?;
+        Ok(())
+    }
+
+    /// Discards the values from the given index up to, but excluding, the top
+    /// of the stack, dropping each of them.
+    fn pop_scope(&mut self, index: usize) -> Result<()> {
+        let values: Vec<_> = self.stack.pop_all_under(index)?.collect();
+        for value in values {
+            self.drop_value(value)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the destructor of a droppable value if this was its last
+    /// reference. The elements of lists, maps and structs are dropped the same
+    /// way when the VM drops the last reference to the containing value;
+    /// other values are simply discarded.
+    fn drop_value(&self, value: Value<'b>) -> Result<()> {
+        let values: Vec<_> = match value {
+            Value::Droppable(value) => {
+                let Some(value) = Arc::into_inner(value) else {
+                    return Ok(());
+                };
+                match self.destructors.0.get(value.kind()) {
+                    Some(destructor) => return destructor(value.into_value()),
+                    None => vec![value.into_value()],
+                }
+            }
+            Value::List(list) => Arc::into_inner(list).unwrap_or_default(),
+            Value::Map(map) => Arc::into_inner(map)
+                .unwrap_or_default()
+                .into_values()
+                .collect(),
+            Value::Struct(value) => Arc::into_inner(value)
+                .map(|value| value.into_fields())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        for value in values {
+            self.drop_value(value)?;
+        }
+        Ok(())
+    }
+
+    fn verify_stack_effect(ins: Instruction, before: usize, after: usize) -> Result<()> {
+        // `PopScope`'s effect depends on the stack height, so it can't be checked
+        let Some(effect) = ins.stack_effect() else {
+            return Ok(());
+        };
+        if after as isize - before as isize != effect {
+            Err(InternalError::InvalidStackEffect(ins))?;
+        }
+        Ok(())
+    }
+
+    fn consume_gas(&mut self) -> Result<()> {
+        if matches!(self.gas, Some(gas) if self.consumed_gas >= gas) {
+            return Err(Error::OutOfGas);
+        }
+        self.consumed_gas += 1;
+        Ok(())
+    }
+
+    fn check_cancellation(&self) -> Result<()> {
+        // the executed instructions are counted even without a gas limit
+        if !self
+            .consumed_gas
+            .is_multiple_of(CANCELLATION_CHECK_INTERVAL)
+        {
+            return Ok(());
+        }
+        let cancelled = self.cancellation.as_ref();
+        if cancelled.is_some_and(|cancelled| cancelled.load(Ordering::Relaxed)) {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
     fn call(&mut self, arity: usize) -> Result<()> {
-        use Instruction::*;
+        self.depth += 1;
+        self.peak_depth = self.peak_depth.max(self.depth);
+        let result = self.call_frame(arity);
+        self.depth -= 1;
+        result
+    }
 
+    fn call_frame(&mut self, arity: usize) -> Result<()> {
         // the function & parameters are still on top of the stack
         // find the offset where this stack frame begins
         let offset = self.stack.len().checked_sub(arity + 1);
         let offset = self.stack.checked_index(offset)?;
 
-        let function = self.stack.pop_deep(offset)?;
-        let function = function.as_function()?;
-        if arity != function.arity() {
+        let callee = self.stack.pop_deep(offset)?;
+
+        // all kinds of callees are called through the same opcode
+        match callee {
+            Value::StructType(name) => self.construct(name, arity),
+            Value::Native(name) => self.call_native(name, arity),
+            callee => self.interpret(callee.as_function()?, offset, arity),
+        }
+    }
+
+    /// The name of the global the function is bound to, for error messages.
+    /// Function values are copies of their constant, so they are compared
+    /// structurally; equal functions are deduplicated into one constant anyway.
+    fn function_name(&self, function: &Function<'_>) -> String {
+        self.module
+            .functions()
+            .find(|(_, global)| *global == function)
+            .map_or_else(
+                || "<anonymous fn>".to_string(),
+                |(name, _)| name.to_string(),
+            )
+    }
+
+    fn check_arity(expected: usize, actual: usize) -> Result<()> {
+        if actual != expected {
             Err(Error::ValueError(format!(
-                "wrong parameter number; expected {}, got {}",
-                function.arity(),
-                arity,
+                "wrong parameter number; expected {expected}, got {actual}",
             )))?;
         }
+        Ok(())
+    }
+
+    fn call_native(&mut self, name: &str, arity: usize) -> Result<()> {
+        let native = self
+            .natives
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::NameError(name.to_string()))?;
+        Self::check_arity(native.arity(), arity)?;
+        let args = self.stack.pop_multiple(arity)?.collect();
+        let result = native.call(args)?;
+        self.stack.push(result)
+    }
+
+    /// Executes the body of a bytecode function. Its parameters start at
+    /// `offset` on the stack.
+    fn interpret(&mut self, function: &Function<'_>, offset: usize, arity: usize) -> Result<()> {
+        use Instruction::*;
+
+        Self::check_arity(function.arity(), arity)?;
 
         let mut instructions = function.body().iter();
+        let mut steps = 0;
         while let Some(ins) = instructions.next() {
-            match ins.map_err(InternalError::from)? {
+            self.consume_gas()?;
+            self.check_cancellation()?;
+            steps += 1;
+            if self.call_step_limit.is_some_and(|limit| steps > limit) {
+                Err(Error::CallStepLimit(self.function_name(function)))?;
+            }
+            let ins = ins.map_err(InternalError::from)?;
+            let height = self.stack.len();
+            match ins {
                 Constant(index) => self.constant(index)?,
+                LoadFunction(index) => self.load_function(index)?,
                 InlineConstant(constant) => self.inline_constant(constant)?,
                 Unary(operator) => self.unary(operator)?,
                 Binary(operator) => self.binary(operator)?,
                 LoadLocal(index) => self.load_local(offset, index)?,
                 StoreLocal(index) => self.store_local(offset, index)?,
                 LoadNamed(index) => self.load_named(index)?,
+                LoadMethod(index) => self.load_method(index)?,
                 StoreNamed(_index) => Err(Error::Unsupported(
                     "Tried to mutate a binding in the global scope",
                 ))?,
-                LoadPositionalField(_index) => todo!(),
+                LoadPositionalField(index) => self.load_positional_field(index)?,
                 StorePositionalField(_index) => todo!(),
-                LoadNamedField(_index) => todo!(),
+                LoadNamedField(index) => self.load_named_field(index)?,
                 StoreNamedField(_index) => todo!(),
-                Pop => self.stack.pop().map(|_| ())?,
-                PopScope(depth) => drop(self.stack.pop_all_under(offset + depth)?),
+                MakeTuple(len) => self.make_tuple(len)?,
+                Unpack(len) => self.unpack(len)?,
+                MakeList(len) => self.make_list(len)?,
+                MakeMap(len) => self.make_map(len)?,
+                Stringify => self.stringify()?,
+                Nop => {}
+                Pop | Drop => {
+                    let value = self.stack.pop()?;
+                    self.drop_value(value)?;
+                }
+                IndexGet => self.index_get()?,
+                IndexSet => self.index_set()?,
+                Print => self.print(false)?,
+                PrintLine => self.print(true)?,
+                PopScope(depth) => self.pop_scope(offset + depth)?,
                 Call(arity) => self.call(arity)?,
                 Return => {
-                    drop(self.stack.pop_all_under(offset + arity)?);
+                    // this discards the locals of all nested scopes at once;
+                    // the compiler relies on that and doesn't emit `PopScope`s
+                    self.pop_scope(offset + arity)?;
                     break;
                 }
                 Jump(offset) => self.jump(&mut instructions, offset)?,
                 JumpIf(offset) => self.jump_if(&mut instructions, offset)?,
+                Switch(len) => self.switch(&mut instructions, len)?,
+            }
+
+            if self.verify {
+                Self::verify_stack_effect(ins, height, self.stack.len())?;
             }
         }
 
-        // here the body block has finished, meaning all local variables
-        // except for parameters are gone, and only the result is on top
-        assert_eq!(self.stack.len(), offset + arity + 1);
+        // here the body block has finished, either by a `Return` or by running
+        // out of instructions. In both cases, all local variables except for
+        // parameters are gone, and only the result is on top
+        if self.stack.len() != offset + arity + 1 {
+            Err(InternalError::InvalidStackFrame(arity))?;
+        }
 
         // pop the parameters from under the return value
-        drop(self.stack.pop_all_under(offset)?);
+        self.pop_scope(offset)?;
 
         Ok(())
     }
 }
+
+/// Removes trailing zeros from a number, and the fractional part of an
+/// integer, so that e.g. `42.0` becomes `42`.
+fn normalize(value: BigDecimal) -> BigDecimal {
+    if value.is_integer() {
+        value.with_scale(0)
+    } else {
+        value.normalized()
+    }
+}
@@ -0,0 +1,37 @@
+use super::{InternalError, Result, Value, Vm};
+use crate::bytecode::LoadedModule;
+
+/// A VM that owns the bytecode it runs, for when there's no separate buffer
+/// the [`Module`](crate::bytecode::Module) could borrow from, e.g. when
+/// loading a bytecode file.
+///
+/// The bytecode is kept as a [`LoadedModule`], so it is only parsed once. The
+/// bytecode and module are validated on construction, so runs only fail with
+/// runtime errors.
+#[derive(Debug, Clone)]
+pub struct OwnedVm {
+    module: LoadedModule,
+}
+
+impl OwnedVm {
+    pub fn new(bytecode: Vec<u8>) -> Result<Self> {
+        let module = LoadedModule::parse(bytecode).map_err(InternalError::from)?;
+        Vm::new(module.module())?;
+        Ok(Self { module })
+    }
+
+    pub fn bytecode(&self) -> &[u8] {
+        self.module.bytecode()
+    }
+
+    pub fn run(&self) -> Result<Value<'_>> {
+        Vm::new(self.module.module())?.run()
+    }
+
+    pub fn call_function<'b, I>(&'b self, name: &str, args: I) -> Result<Value<'b>>
+    where
+        I: IntoIterator<Item = Value<'b>>,
+    {
+        Vm::new(self.module.module())?.call_function(name, args)
+    }
+}
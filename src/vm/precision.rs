@@ -0,0 +1,83 @@
+//! Rounding of decimal results to a fixed number of fractional digits
+
+use bigdecimal::num_bigint::{BigInt, Sign};
+use bigdecimal::BigDecimal;
+
+/// How a result is rounded when it has more fractional digits than allowed by
+/// a [`PrecisionContext`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Away from zero
+    Up,
+    /// Towards zero, i.e. truncating
+    Down,
+    /// Towards positive infinity
+    Ceiling,
+    /// Towards negative infinity
+    Floor,
+    /// To the nearest neighbor; ties away from zero
+    HalfUp,
+    /// To the nearest neighbor; ties towards zero
+    HalfDown,
+    /// To the nearest neighbor; ties to the even neighbor
+    HalfEven,
+}
+
+/// Limits the fractional digits of the results of arithmetic operations, see [`Vm::with_precision`](super::Vm::with_precision).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionContext {
+    digits: u32,
+    rounding: RoundingMode,
+}
+
+impl PrecisionContext {
+    pub fn new(digits: u32, rounding: RoundingMode) -> Self {
+        Self { digits, rounding }
+    }
+
+    pub fn digits(&self) -> u32 {
+        self.digits
+    }
+
+    pub fn rounding(&self) -> RoundingMode {
+        self.rounding
+    }
+
+    /// Rounds the value to at most `digits` fractional digits. Values that
+    /// are already precise enough are returned unchanged.
+    pub fn round(&self, value: BigDecimal) -> BigDecimal {
+        use RoundingMode::*;
+
+        let (int_val, scale) = value.as_bigint_and_exponent();
+        let digits = i64::from(self.digits);
+        if scale <= digits {
+            return value;
+        }
+
+        let divisor = BigInt::from(10).pow((scale - digits) as u32);
+        // both truncate towards zero, so the remainder has the sign of the value
+        let mut quotient = &int_val / &divisor;
+        let remainder = &int_val % &divisor;
+
+        if remainder.sign() != Sign::NoSign {
+            let negative = int_val.sign() == Sign::Minus;
+            let half = (remainder.magnitude() * 2u32).cmp(divisor.magnitude());
+            let away_from_zero = match self.rounding {
+                Up => true,
+                Down => false,
+                Ceiling => !negative,
+                Floor => negative,
+                HalfUp => half.is_ge(),
+                HalfDown => half.is_gt(),
+                HalfEven => {
+                    half.is_gt() || (half.is_eq() && (&quotient % 2u32).sign() != Sign::NoSign)
+                }
+            };
+            if away_from_zero {
+                quotient += if negative { -1 } else { 1 };
+            }
+        }
+
+        super::normalize(BigDecimal::new(quotient, digits))
+    }
+}
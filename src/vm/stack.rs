@@ -13,13 +13,13 @@ impl<'b> Stack<'b> {
         Ok(())
     }
 
-    pub fn checked_index(&mut self, index: Option<usize>) -> Result<usize> {
+    pub fn checked_index(&self, index: Option<usize>) -> Result<usize> {
         index
             .filter(|index| *index < self.len())
             .ok_or_else(|| InternalError::EmptyStack.into())
     }
 
-    pub fn get(&mut self, index: usize) -> Option<&Value<'b>> {
+    pub fn get(&self, index: usize) -> Option<&Value<'b>> {
         self.0.get(index)
     }
 
@@ -51,11 +51,31 @@ impl<'b> Stack<'b> {
         Ok(self.0.drain(index..len - 1))
     }
 
+    pub fn as_slice(&self) -> &[Value<'b>] {
+        &self.0
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_get() {
+        let mut stack = Stack::new();
+        stack.push(Value::bool(true)).unwrap();
+        stack.push(Value::unit()).unwrap();
 
-    pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        // both references are live at the same time, which requires `get(&self)`
+        let first = stack.get(0).unwrap();
+        let second = stack.get(1).unwrap();
+        assert!(matches!(first, Value::Bool(true)));
+        assert!(matches!(second, Value::Unit));
+        assert_eq!(stack.as_slice().len(), 2);
+        assert!(stack.get(2).is_none());
     }
 }
@@ -1,5 +1,8 @@
+use std::collections::BTreeMap;
 use std::{fmt, sync::Arc};
 
+use bigdecimal::num_traits::{ToPrimitive, Zero};
+
 use super::{Error, Result};
 use crate::bytecode::Constant;
 
@@ -11,6 +14,22 @@ pub enum Value<'b> {
     Bool(bool),
     Constant(Constant<'b>),
     Boxed(Arc<BoxedValue>),
+    StructType(&'b str),
+    Struct(Arc<Struct<'b>>),
+    /// Lists are immutable; operations that change a list return a modified
+    /// copy, so that a list value can be shared freely.
+    List(Arc<Vec<Value<'b>>>),
+    /// Maps from strings to values, ordered by key. Like lists, maps are
+    /// immutable.
+    Map(Arc<BTreeMap<String, Value<'b>>>),
+    /// Binary data created at runtime; byte string literals are constants.
+    /// Like lists, byte strings are immutable.
+    Bytes(Arc<Vec<u8>>),
+    /// A native function registered with the VM, referred to by name.
+    Native(&'b str),
+    /// A value provided by the host that needs cleanup, e.g. a file handle.
+    /// Droppable values have identity, like structs.
+    Droppable(Arc<Droppable<'b>>),
 }
 
 #[derive(Clone)]
@@ -19,10 +38,99 @@ pub enum BoxedValue {
     String(String),
 }
 
+/// An instance of a struct type. Structs have identity: two structs are only
+/// equal if they are the same instance.
+#[derive(Clone)]
+pub struct Struct<'b> {
+    struct_type: &'b str,
+    fields: Vec<Value<'b>>,
+}
+
+impl<'b> Struct<'b> {
+    pub fn struct_type(&self) -> &'b str {
+        self.struct_type
+    }
+
+    pub fn fields(&self) -> &[Value<'b>] {
+        &self.fields
+    }
+
+    pub fn into_fields(self) -> Vec<Value<'b>> {
+        self.fields
+    }
+}
+
+/// A value flagged as needing cleanup. When its last reference is dropped
+/// by the VM, the destructor registered for its kind is run, see
+/// [`Vm::with_destructor`](super::Vm::with_destructor).
+#[derive(Clone)]
+pub struct Droppable<'b> {
+    kind: &'b str,
+    value: Value<'b>,
+}
+
+impl<'b> Droppable<'b> {
+    pub fn kind(&self) -> &'b str {
+        self.kind
+    }
+
+    pub fn value(&self) -> &Value<'b> {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Value<'b> {
+        self.value
+    }
+}
+
+/// The implementation of a native function. It receives the arguments of a
+/// call, after the VM has checked their number.
+pub type NativeFn<'b> = dyn Fn(Vec<Value<'b>>) -> Result<Value<'b>> + Send + Sync + 'b;
+
+/// The cleanup of droppable values of one kind. It receives the value that
+/// was wrapped by [`Value::droppable`].
+pub type DestructorFn<'b> = dyn Fn(Value<'b>) -> Result<()> + Send + Sync + 'b;
+
+/// A function implemented in Rust, made available to programs through
+/// [`Vm::with_native`](super::Vm::with_native).
+#[derive(Clone)]
+pub struct Native<'b> {
+    name: &'b str,
+    arity: usize,
+    function: Arc<NativeFn<'b>>,
+}
+
+impl<'b> Native<'b> {
+    pub fn new<F>(name: &'b str, arity: usize, function: F) -> Self
+    where
+        F: Fn(Vec<Value<'b>>) -> Result<Value<'b>> + Send + Sync + 'b,
+    {
+        let function = Arc::new(function);
+        Self {
+            name,
+            arity,
+            function,
+        }
+    }
+
+    pub fn name(&self) -> &'b str {
+        self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn call(&self, args: Vec<Value<'b>>) -> Result<Value<'b>> {
+        (self.function)(args)
+    }
+}
+
 #[derive(Clone)]
 pub enum ValueRef<'a, 'b> {
     Number(&'a Number),
     String(&'a str),
+    Bytes(&'a [u8]),
     Function(&'a Function<'b>),
 }
 
@@ -51,6 +159,40 @@ impl<'b> Value<'b> {
         Self::boxed(BoxedValue::String(value))
     }
 
+    pub fn bytes(value: Vec<u8>) -> Self {
+        Self::Bytes(Arc::new(value))
+    }
+
+    pub fn struct_type(name: &'b str) -> Self {
+        Self::StructType(name)
+    }
+
+    /// Creates a tuple. Tuples are represented as structs of an anonymous
+    /// type, so they can be unpacked the same way as positional structs.
+    pub fn tuple(elements: Vec<Value<'b>>) -> Self {
+        Self::new_struct("", elements)
+    }
+
+    pub fn new_struct(struct_type: &'b str, fields: Vec<Value<'b>>) -> Self {
+        Self::Struct(Arc::new(Struct {
+            struct_type,
+            fields,
+        }))
+    }
+
+    /// Flags a value as needing cleanup by the destructor of the given kind.
+    pub fn droppable(kind: &'b str, value: Value<'b>) -> Self {
+        Self::Droppable(Arc::new(Droppable { kind, value }))
+    }
+
+    pub fn list(elements: Vec<Value<'b>>) -> Self {
+        Self::List(Arc::new(elements))
+    }
+
+    pub fn map(entries: BTreeMap<String, Value<'b>>) -> Self {
+        Self::Map(Arc::new(entries))
+    }
+
     pub fn get_ref<'a>(&'a self) -> Option<ValueRef<'a, 'b>>
     where
         'a: 'b,
@@ -64,6 +206,8 @@ impl<'b> Value<'b> {
             Constant(C::Number(value)) => R::Number(value),
             Constant(C::String(value)) => R::String(value),
             Constant(C::Function(value)) => R::Function(value),
+            Constant(C::Bytes(value)) => R::Bytes(value),
+            Bytes(value) => R::Bytes(value),
             Boxed(arc) => match arc.as_ref() {
                 B::Number(value) => R::Number(value),
                 B::String(value) => R::String(value),
@@ -78,6 +222,27 @@ impl<'b> Value<'b> {
         matches!(self, Self::Unit)
     }
 
+    /// Whether this value counts as true when coerced to a boolean: `false`,
+    /// unit, numeric zero of any scale (including `-0.0`), the empty string,
+    /// empty byte strings and empty lists and maps are falsy; everything else
+    /// is truthy.
+    pub fn is_truthy(&self) -> bool {
+        use ValueRef::*;
+
+        match self {
+            Value::Unit => false,
+            Value::Bool(value) => *value,
+            Value::List(value) => !value.is_empty(),
+            Value::Map(value) => !value.is_empty(),
+            _ => match self.get_ref() {
+                Some(Number(value)) => !value.is_zero(),
+                Some(String(value)) => !value.is_empty(),
+                Some(Bytes(value)) => !value.is_empty(),
+                _ => true,
+            },
+        }
+    }
+
     pub fn as_bool(&self) -> Result<bool> {
         use Value::*;
 
@@ -96,6 +261,27 @@ impl<'b> Value<'b> {
         Ok(value)
     }
 
+    /// Converts a number to an `i64`. Fails if the number is not an integer
+    /// or out of range, instead of truncating or saturating it.
+    pub fn as_i64(&self) -> Result<i64> {
+        let value = self.as_number()?;
+        value
+            .is_integer()
+            .then(|| value.to_i64())
+            .flatten()
+            .ok_or_else(|| Error::ValueError(format!("{value} is not representable as i64")))
+    }
+
+    /// Converts a number to the nearest `f64`. Fails if the number is out of
+    /// range, instead of saturating it to infinity.
+    pub fn as_f64(&self) -> Result<f64> {
+        let value = self.as_number()?;
+        value
+            .to_f64()
+            .ok_or_else(|| Error::ValueError(format!("{value} is not representable as f64")))
+            .and_then(finite)
+    }
+
     pub fn as_string(&self) -> Result<&str> {
         use ValueRef::*;
 
@@ -105,7 +291,37 @@ impl<'b> Value<'b> {
         Ok(value)
     }
 
-    pub fn as_function(&self) -> Result<&Function> {
+    pub fn as_bytes(&self) -> Result<&[u8]> {
+        use ValueRef::*;
+
+        let Some(Bytes(value)) = self.get_ref() else {
+            return Err(Error::TypeError("bytes".to_string()));
+        };
+        Ok(value)
+    }
+
+    pub fn as_struct(&self) -> Result<&Struct<'b>> {
+        let Value::Struct(value) = self else {
+            return Err(Error::TypeError("struct".to_string()));
+        };
+        Ok(value)
+    }
+
+    pub fn as_list(&self) -> Result<&[Value<'b>]> {
+        let Value::List(value) = self else {
+            return Err(Error::TypeError("list".to_string()));
+        };
+        Ok(value)
+    }
+
+    pub fn as_map(&self) -> Result<&BTreeMap<String, Value<'b>>> {
+        let Value::Map(value) = self else {
+            return Err(Error::TypeError("map".to_string()));
+        };
+        Ok(value)
+    }
+
+    pub fn as_function(&self) -> Result<&Function<'_>> {
         use ValueRef::*;
 
         let Some(Function(value)) = self.get_ref() else {
@@ -115,6 +331,45 @@ impl<'b> Value<'b> {
     }
 }
 
+/// Numbers have no NaN or infinity, so any operation that would produce a
+/// non-finite value fails with a [`Error::ValueError`] instead.
+pub(super) fn finite(value: f64) -> Result<f64> {
+    if !value.is_finite() {
+        Err(Error::ValueError(format!("{value} is not a finite number")))?;
+    }
+    Ok(value)
+}
+
+impl From<bool> for Value<'_> {
+    fn from(value: bool) -> Self {
+        Self::bool(value)
+    }
+}
+
+impl From<i64> for Value<'_> {
+    fn from(value: i64) -> Self {
+        Self::number(value.into())
+    }
+}
+
+impl From<&str> for Value<'_> {
+    fn from(value: &str) -> Self {
+        Self::string(value.to_string())
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(value: String) -> Self {
+        Self::string(value)
+    }
+}
+
+impl From<Vec<u8>> for Value<'_> {
+    fn from(value: Vec<u8>) -> Self {
+        Self::bytes(value)
+    }
+}
+
 impl fmt::Debug for Value<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Value::*;
@@ -124,17 +379,55 @@ impl fmt::Debug for Value<'_> {
             Bool(value) => fmt::Display::fmt(value, f),
             Constant(value) => value.fmt(f),
             Boxed(value) => value.fmt(f),
+            Value::StructType(name) => f.write_str(name),
+            Value::Struct(value) => value.fmt(f),
+            Value::List(value) => f.debug_list().entries(value.iter()).finish(),
+            Value::Map(value) => f.debug_map().entries(value.iter()).finish(),
+            Value::Bytes(value) => write!(f, "b\"{}\"", value.escape_ascii()),
+            Value::Native(name) => write!(f, "<native {name}>"),
+            Value::Droppable(value) => write!(f, "<{} {:?}>", value.kind, value.value),
         }
     }
 }
 
+/// Like `Debug`, except that strings are written as is, without quotes and
+/// escapes.
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get_ref() {
+            Some(ValueRef::String(value)) => f.write_str(value),
+            _ => fmt::Debug::fmt(self, f),
+        }
+    }
+}
+
+impl fmt::Debug for Struct<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_tuple(self.struct_type);
+        for field in &self.fields {
+            f.field(field);
+        }
+        f.finish()
+    }
+}
+
+impl fmt::Debug for Native<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
 impl fmt::Debug for BoxedValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use BoxedValue::*;
 
         match self {
             Number(value) => fmt::Display::fmt(value, f),
-            String(value) => fmt::Display::fmt(value, f),
+            // escape strings like constant strings, so that control characters are visible
+            String(value) => fmt::Debug::fmt(value, f),
         }
     }
 }
@@ -0,0 +1,31 @@
+use std::path::Path;
+use std::process::Command;
+
+fn run_cli(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_sprachli"))
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_emit() {
+    let stdout = run_cli(&["run", "tests/programs/bool.spr"]);
+    assert_eq!(stdout, "42\n");
+
+    let stdout = run_cli(&["run", "--emit", "none", "tests/programs/bool.spr"]);
+    assert_eq!(stdout, "42\n");
+
+    let stdout = run_cli(&["run", "--emit", "ast", "tests/programs/bool.spr"]);
+    assert!(stdout.starts_with("(sprachli\n (fn main\n"), "{stdout}");
+    assert!(stdout.ends_with("\n42\n"), "{stdout}");
+
+    let stdout = run_cli(&["run", "--emit", "bytecode", "tests/programs/bool.spr"]);
+    assert!(stdout.starts_with("Module {\n"), "{stdout}");
+    // the conditions are literals, so only the taken branch is compiled
+    assert!(stdout.contains("CONST #0        -- 42"), "{stdout}");
+    assert!(!stdout.contains("JUMP_IF"), "{stdout}");
+}
@@ -1,8 +1,12 @@
+use std::fs;
+use std::path::Path;
+
 use bigdecimal::BigDecimal;
 
 use sprachli::bytecode::{parser::parse_bytecode, Error as BytecodeError};
-use sprachli::compiler::{compile_source_file, Error as CompilerError};
-use sprachli::vm::{Error as RuntimeError, Value, Vm};
+use sprachli::compiler::{compile_source_file, write_bytecode, Error as CompilerError, Module};
+use sprachli::parser::parse_source_file;
+use sprachli::vm::{Error as RuntimeError, OwnedVm, Value, Vm};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -14,6 +18,13 @@ pub enum Error {
     Runtime(#[from] RuntimeError),
 }
 
+/// Compiles a source that is expected to be valid.
+fn compile(source: &str) -> Vec<u8> {
+    let mut bytecode = Vec::new();
+    compile_source_file(&mut bytecode, source).unwrap();
+    bytecode
+}
+
 fn run_and_check_result<F>(source: &str, f: F)
 where
     F: FnOnce(Result<Value, Error>) -> Result<(), Error>,
@@ -28,7 +39,64 @@ where
             Ok(value) => value,
             Err(e) => return f(Err(e.into())),
         };
-        let result = match Vm::new(module).run() {
+        let result = match Vm::new(&module).and_then(Vm::run) {
+            Ok(value) => value,
+            Err(e) => return f(Err(e.into())),
+        };
+        f(Ok(result))
+    })()
+    .unwrap()
+}
+
+fn run_file_and_check_result<F>(path: &str, f: F)
+where
+    F: FnOnce(Result<Value, Error>) -> Result<(), Error>,
+{
+    (|| {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+        let source = fs::read_to_string(&path).unwrap();
+        let ast = parse_source_file(&source).unwrap();
+        let module = match Module::with_source_path(ast, &path) {
+            Ok(value) => value,
+            Err(e) => return f(Err(e.into())),
+        };
+        let mut bytecode = Vec::new();
+        write_bytecode(&mut bytecode, &module).unwrap();
+        let module = match parse_bytecode(&bytecode) {
+            Ok(value) => value,
+            Err(e) => return f(Err(e.into())),
+        };
+        let result = match Vm::new(&module).and_then(Vm::run) {
+            Ok(value) => value,
+            Err(e) => return f(Err(e.into())),
+        };
+        f(Ok(result))
+    })()
+    .unwrap()
+}
+
+fn call_and_check_result<I, F>(source: &str, name: &str, args: I, f: F)
+where
+    I: IntoIterator<Item = Value<'static>>,
+    F: FnOnce(Result<Value, Error>) -> Result<(), Error>,
+{
+    (|| {
+        let mut bytecode = Vec::new();
+        match compile_source_file(&mut bytecode, source) {
+            Ok(value) => value,
+            Err(e) => return f(Err(e.into())),
+        };
+        let module = match parse_bytecode(&bytecode) {
+            Ok(value) => value,
+            Err(e) => return f(Err(e.into())),
+        };
+        // shorten the arguments' lifetime to that of the module
+        fn shorten<'b>(value: Value<'static>) -> Value<'b> {
+            value
+        }
+
+        let args = args.into_iter().map(shorten);
+        let result = match Vm::new(&module).and_then(|vm| vm.call_function(name, args)) {
             Ok(value) => value,
             Err(e) => return f(Err(e.into())),
         };
@@ -58,6 +126,13 @@ fn run_and_check_result_string(source: &str, expected: &str) {
     })
 }
 
+fn run_and_check_result_unit(source: &str) {
+    run_and_check_result(source, |actual| {
+        assert!(actual?.is_unit());
+        Ok(())
+    })
+}
+
 fn run_and_check_result_error<F>(source: &str, f: F)
 where
     F: FnOnce(Error),
@@ -98,6 +173,25 @@ mod operators {
         run_and_check_result_42(source);
     }
 
+    #[test]
+    fn test_negative_zero() {
+        // numbers have no signed zero, so negating zero gives plain zero
+        run_and_check_result_true("fn main() { -0 == 0 }");
+        run_and_check_result_true("fn main() { -0.0 == 0 }");
+        run_and_check_result_true("fn main() { 0 * -1 == 0 }");
+
+        for (source, expected) in [
+            ("fn main() { 0 * -1 }", "0"),
+            ("fn main() { -0 }", "0"),
+            ("fn main() { 0.0 * -1 }", "0.0"),
+        ] {
+            run_and_check_result(source, |actual| {
+                assert_eq!(actual?.to_string(), expected);
+                Ok(())
+            });
+        }
+    }
+
     #[test]
     fn test_mul() {
         let source = "fn main() { 3 * 14 }";
@@ -108,12 +202,81 @@ mod operators {
     fn test_div() {
         let source = "fn main() { 84 / 2 }";
         run_and_check_result_42(source);
+
+        let source = "fn main() { 10 / 2 }";
+        run_and_check_result_decimal(source, 5);
+
+        let source = "fn main() { 10 / 4 }";
+        run_and_check_result_decimal(source, BigDecimal::from(5) / BigDecimal::from(2));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        for source in [
+            "fn main() { 1 / 0 }",
+            "fn main() { 1.5 / 0.0 }",
+            "fn main() { 1 % 0 }",
+            "fn main() { 0 / 0 }",
+        ] {
+            run_and_check_result_error(source, |error| {
+                assert!(matches!(error, Error::Runtime(RuntimeError::ValueError(_))));
+            });
+        }
+    }
+
+    #[test]
+    fn test_bool_arithmetic() {
+        for source in [
+            "fn main() { true + 1 }",
+            "fn main() { 1 * false }",
+            "fn main() { 1 / true }",
+        ] {
+            run_and_check_result_error(source, |error| {
+                assert!(matches!(
+                    error,
+                    Error::Runtime(RuntimeError::BoolArithmetic)
+                ));
+                let message = error.to_string();
+                assert!(
+                    message.ends_with("cannot use bool in arithmetic"),
+                    "{message}"
+                );
+            });
+        }
+
+        // other non-numbers still get the generic error
+        run_and_check_result_error("fn main() { () + 1 }", |error| {
+            assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+        });
     }
 
     #[test]
     fn test_mod() {
         let source = "fn main() { 242 % 100 }";
         run_and_check_result_42(source);
+
+        let source = "fn main() { 10 % 3 }";
+        run_and_check_result_decimal(source, 1);
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let source = "fn main() { 9223372036854775807 + 1 }";
+        run_and_check_result_decimal(source, BigDecimal::from(i64::MAX) + BigDecimal::from(1));
+
+        let source = "fn main() { 9223372036854775807 * 2 }";
+        run_and_check_result_decimal(source, BigDecimal::from(i64::MAX) * BigDecimal::from(2));
+
+        let source = "fn main() { (0 - 9223372036854775807 - 1) / (0 - 1) }";
+        run_and_check_result_decimal(source, -BigDecimal::from(i64::MIN));
+    }
+
+    #[test]
+    fn test_plus_sign() {
+        run_and_check_result_decimal("fn main() { +42 }", 42);
+        run_and_check_result_decimal("fn main() { +1.5 * 2 }", 3);
+        let expected: BigDecimal = "-0.5".parse().unwrap();
+        run_and_check_result_decimal("fn main() { 1 - +1.5 }", expected);
     }
 
     #[test]
@@ -140,6 +303,29 @@ mod operators {
         run_and_check_result_42(source);
     }
 
+    #[test]
+    fn test_shl_too_large() {
+        let source = "fn main() { 1 << 1000000 }";
+        run_and_check_result_error(source, |error| {
+            assert!(matches!(error, Error::Runtime(RuntimeError::ValueError(_))));
+        });
+
+        // negative shift amounts are rejected instead of shifting the other way
+        for source in [
+            "fn main() { 1 >> -1000000 }",
+            "fn main() { 1 >> -1 }",
+            "fn main() { 1 << -1 }",
+        ] {
+            run_and_check_result_error(source, |error| {
+                assert!(matches!(
+                    error,
+                    Error::Runtime(RuntimeError::ValueError(message))
+                        if message == "negative shift amount"
+                ));
+            });
+        }
+    }
+
     #[test]
     fn test_bit_and() {
         // 42 = 0b101010
@@ -147,6 +333,12 @@ mod operators {
         //      0b101111 = 47
         let source = "fn main() { 58 & 47 }";
         run_and_check_result_42(source);
+
+        let source = "fn main() { true & true }";
+        run_and_check_result_true(source);
+
+        let source = "fn main() { true & false }";
+        run_and_check_result_false(source);
     }
 
     #[test]
@@ -156,6 +348,12 @@ mod operators {
         //      0b010111 = 23
         let source = "fn main() { 61 ^ 23 }";
         run_and_check_result_42(source);
+
+        let source = "fn main() { true ^ false }";
+        run_and_check_result_true(source);
+
+        let source = "fn main() { true ^ true }";
+        run_and_check_result_false(source);
     }
 
     #[test]
@@ -165,6 +363,12 @@ mod operators {
         //      0b101000 = 40
         let source = "fn main() { 10 | 40 }";
         run_and_check_result_42(source);
+
+        let source = "fn main() { true | false }";
+        run_and_check_result_true(source);
+
+        let source = "fn main() { false | false }";
+        run_and_check_result_false(source);
     }
 
     #[test]
@@ -173,6 +377,13 @@ mod operators {
         run_and_check_result_true(source);
 
         let source = "fn main() { 42 == 69 }";
+        run_and_check_result_false(source);
+
+        // `3 / 2 * 2` has a different scale than `3`
+        let source = "fn main() { 3 / 2 * 2 == 3 }";
+        run_and_check_result_true(source);
+
+        let source = "fn main() { 5 / 2 - 1 / 2 == 2 }";
         run_and_check_result_true(source);
     }
 
@@ -182,6 +393,9 @@ mod operators {
         run_and_check_result_false(source);
 
         let source = "fn main() { 42 != 69 }";
+        run_and_check_result_true(source);
+
+        let source = "fn main() { 3 / 2 * 2 != 3 }";
         run_and_check_result_false(source);
     }
 
@@ -246,6 +460,64 @@ fn test_assign_immutable() {
     })
 }
 
+#[test]
+fn test_deferred_init() {
+    run_and_check_result_decimal("fn main() { let x; x = 1; x }", 1);
+    run_and_check_result_decimal("fn main() { let mut x; x = 1; x = x + 1; x }", 2);
+
+    let source = "fn main() { let x; if true { x = 1; } else { x = 2; }; x }";
+    run_and_check_result_decimal(source, 1);
+    let source = "fn main() { let x; if false { x = 1; } else { return 2; }; x }";
+    run_and_check_result_decimal(source, 2);
+    let source = "fn main() { let x; loop { x = 42; break; }; x }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::ImmutableVariable)
+        ));
+    });
+    run_and_check_result_42("fn main() { let mut x; loop { x = 42; break; }; x }");
+
+    // after an if without else, the variable may or may not be initialized:
+    // it can't be read, and an immutable one can't be assigned again
+    for source in [
+        "fn main() { let c = true; let x; if c { x = 1; }; x = 2; x }",
+        "fn main() { let c = 1; let x; if c == 0 { x = 1; } else if c == 1 {}; x = 2; x }",
+    ] {
+        run_and_check_result_error(source, |error| {
+            assert!(matches!(
+                error,
+                Error::Compiler(CompilerError::ImmutableVariable)
+            ));
+        });
+    }
+    let source = "fn main() { let c = true; let mut x; if c { x = 1; }; x = 2; x }";
+    run_and_check_result_decimal(source, 2);
+
+    for source in [
+        "fn main() { let x; x }",
+        "fn main() { let mut x; x = x + 1; x }",
+        "fn main() { let c = true; let x; if c { x = 1; }; x }",
+        "fn main() { let c = 1; let x; if c == 0 { x = 1; } else if c == 1 {}; x }",
+        "fn main() { let mut x; loop { if true { break; }; x = 1; break; }; x }",
+    ] {
+        run_and_check_result_error(source, |error| {
+            assert!(matches!(
+                error,
+                Error::Compiler(CompilerError::UseBeforeInit(name)) if name == "x"
+            ));
+        });
+    }
+
+    let source = "fn main() { let x = 1; x = 2; }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::ImmutableVariable)
+        ));
+    });
+}
+
 #[test]
 fn test_bool() {
     run_and_check_result_42(include_str!("programs/bool.spr"))
@@ -256,6 +528,33 @@ fn test_break() {
     run_and_check_result_42(include_str!("programs/break.spr"))
 }
 
+#[test]
+fn test_conditional() {
+    let source = "fn main() { true ? 42 : 0 }";
+    run_and_check_result_42(source);
+
+    // the untaken branch is not evaluated
+    let source = "fn main() { false ? missing() : 42 }";
+    run_and_check_result_42(source);
+
+    let source =
+        "fn sign(x) { x < 0 ? -1 : x == 0 ? 0 : 1 } fn main() { sign(-5) + sign(0) + sign(5) }";
+    run_and_check_result_decimal(source, 0);
+}
+
+#[test]
+fn test_labeled_break() {
+    run_and_check_result_42(include_str!("programs/labeled_break.spr"));
+
+    let source = "fn main() { 'outer: loop { loop { break 'inner 42; } } }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::UnknownLabel(_))
+        ));
+    });
+}
+
 #[test]
 fn test_continue() {
     run_and_check_result_42(include_str!("programs/continue.spr"))
@@ -281,6 +580,74 @@ fn test_higher_order() {
     run_and_check_result_42(include_str!("programs/higher_order.spr"))
 }
 
+#[test]
+fn test_import() {
+    run_file_and_check_result("tests/programs/import/main.spr", |actual| {
+        assert_eq!(actual?.as_number()?, &BigDecimal::from(42));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_import_private() {
+    run_file_and_check_result("tests/programs/import/private.spr", |actual| {
+        let error = actual.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::UnresolvedImport(name)) if name == "math::square"
+        ));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_import_collision() {
+    run_file_and_check_result("tests/programs/import/collision.spr", |actual| {
+        let error = actual.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::DuplicateGlobal(name)) if name == "add"
+        ));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_import_private_access() {
+    // importing a whole file doesn't make its private items accessible
+    run_file_and_check_result("tests/programs/import/private_access.spr", |actual| {
+        let error = actual.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Runtime(RuntimeError::NameError(name)) if name == "square"
+        ));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_import_same_file_names() {
+    // `other.spr` links to `other/other.spr`, which imports `other/math.spr`.
+    // Both `math.spr` files have a private `square`, which must not clash
+    run_file_and_check_result("tests/programs/import/stems.spr", |actual| {
+        assert_eq!(actual?.as_number()?, &BigDecimal::from(42));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_import_cycle() {
+    run_file_and_check_result("tests/programs/import_cycle/a.spr", |actual| {
+        let error = actual.unwrap_err();
+        // the cycle is detected when `b` imports the root file `a` again
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::CyclicImport(path)) if path.ends_with("a.spr")
+        ));
+        Ok(())
+    })
+}
+
 #[test]
 fn test_loop() {
     run_and_check_result_42(include_str!("programs/loop.spr"))
@@ -291,17 +658,1714 @@ fn test_max() {
     run_and_check_result_42(include_str!("programs/max.spr"))
 }
 
+#[test]
+fn test_mutual_recursion() {
+    // `is_even` calls `is_odd` before it is declared; names are resolved at runtime
+    run_and_check_result_true(include_str!("programs/mutual_recursion.spr"));
+}
+
+#[test]
+fn test_switch() {
+    // dense if chains over integers are compiled to a jump table
+    run_and_check_result_true(include_str!("programs/switch.spr"));
+}
+
 #[test]
 fn test_return() {
     run_and_check_result_42(include_str!("programs/return.spr"))
 }
 
+#[test]
+fn test_jump_expression() {
+    // `return` and `break` in the right operand of a binary expression,
+    // only taken depending on a condition
+    run_and_check_result_42(include_str!("programs/jump_expression.spr"))
+}
+
+#[test]
+fn test_jump_in_condition() {
+    // the condition's value is never pushed when the jump is taken; the
+    // compiler must still account for it being popped by the conditional jump
+    let source = "
+        fn main() {
+            let a = 1;
+            let b = loop {
+                let c = 2;
+                if (break a + c) { 0 } else { 1 };
+            };
+            let mut i = 0;
+            loop {
+                i = i + 1;
+                if i < 10 {
+                    let d = i;
+                    if (continue) { d };
+                };
+                break;
+            };
+            b + i + 29
+        }
+    ";
+    run_and_check_result_42(source);
+
+    // verification checks each instruction's stack effect
+    let bytecode = compile(source);
+    let module = parse_bytecode(&bytecode).unwrap();
+    let vm = Vm::new(&module).unwrap().with_verification(true);
+    assert_eq!(
+        vm.run().unwrap().as_number().unwrap(),
+        &BigDecimal::from(42)
+    );
+}
+
 #[test]
 fn test_statement() {
     run_and_check_result_42(include_str!("programs/statement.spr"))
 }
 
 #[test]
-fn test_variable() {
-    run_and_check_result_42(include_str!("programs/variable.spr"))
+fn test_struct_fields() {
+    let source = "struct Foo { a, b } fn main() { 42 }";
+    run_and_check_result_42(source);
+
+    let source = "struct Foo { a, b, a } fn main() { 42 }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::DuplicateField(name)) if name == "a"
+        ));
+    });
+}
+
+#[test]
+fn test_impl() {
+    use sprachli::bytecode::Constant;
+
+    let source = "
+        struct Foo(x);
+
+        impl Foo {
+            fn add(self, a, b) { a + b }
+            fn is(self, other) { self == other }
+        }
+
+        fn main() {
+            let foo = Foo(1);
+            if !foo.is(foo) { 0 } else if foo.is(Foo(1)) { 1 } else { foo.add(40, 2) }
+        }
+    ";
+    run_and_check_result_42(source);
+
+    let bytecode = compile(source);
+    let module = parse_bytecode(&bytecode).unwrap();
+
+    let methods = module.methods().keys().copied().collect::<Vec<_>>();
+    assert_eq!(methods, [("Foo", "add"), ("Foo", "is")]);
+    assert!(matches!(
+        module.method("Foo", "add"),
+        Some(Constant::Function(function)) if function.arity() == 3
+    ));
+
+    let source = "struct Foo; fn main() { Foo().bar() }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Runtime(RuntimeError::MissingMethod(struct_type, name))
+                if struct_type == "Foo" && name == "bar"
+        ));
+    });
+
+    let source = "struct Foo; fn main() { 1.bar() }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+    });
+
+    // constructions that can't be checked at compile time fail at runtime
+    let source = "struct Foo(x); fn main() { let make = Foo; make() }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::ValueError(_))));
+    });
+
+    // a method may only be defined once per struct type, even across impl blocks
+    for source in [
+        "struct Foo; impl Foo { fn bar(self) {} fn bar(self) {} } fn main() { 42 }",
+        "struct Foo; impl Foo { fn bar(self) {} } impl Foo { fn bar(self) {} } fn main() { 42 }",
+    ] {
+        run_and_check_result_error(source, |error| {
+            assert!(matches!(
+                error,
+                Error::Compiler(CompilerError::DuplicateGlobal(name)) if name == "Foo::bar"
+            ));
+        });
+    }
+    // but different struct types can have methods of the same name
+    let source = "
+        struct Foo; struct Bar;
+        impl Foo { fn get(self) { 40 } }
+        impl Bar { fn get(self) { 2 } }
+        fn main() { Foo().get() + Bar().get() }
+    ";
+    run_and_check_result_42(source);
+
+    let source = "impl Foo { fn bar(self) {} } fn main() { 42 }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::UndeclaredStructType(name)) if name == "Foo"
+        ));
+    });
+}
+
+#[test]
+fn test_wrong_field_number() {
+    let check = |source: &str, expected: usize, actual: usize| {
+        run_and_check_result_error(source, |error| {
+            assert!(matches!(
+                error,
+                Error::Compiler(CompilerError::WrongFieldNumber(name, e, a))
+                    if name == "Foo" && e == expected && a == actual
+            ));
+        });
+    };
+
+    check("struct Foo; fn main() { Foo(1) }", 0, 1);
+    check("struct Foo(x); fn main() { Foo() }", 1, 0);
+    check("struct Foo(x); fn main() { Foo(1, 2) }", 1, 2);
+    check("struct Foo { x, y } fn main() { Foo(1) }", 2, 1);
+    // the order of declarations doesn't matter
+    check("fn main() { Foo(1) } struct Foo;", 0, 1);
+
+    // a local variable shadows the struct type
+    let source = "struct Foo; fn main() { let Foo = fn(x) { x }; Foo(42) }";
+    run_and_check_result_42(source);
+
+    // but a function can't, as globals and struct types share their names
+    let source = "fn Foo(x) { x } struct Foo; fn main() { 42 }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::DuplicateGlobal(name)) if name == "Foo"
+        ));
+    });
+}
+
+#[test]
+fn test_method_call() {
+    let source = "
+        struct Point { x, y }
+
+        impl Point {
+            fn sum(self) { self.x + self.y }
+            fn scale(self, factor) { Point(self.x * factor, self.y * factor) }
+        }
+
+        fn main() {
+            let p = Point(4, 3);
+            p.scale(6).sum() + p.x.foo
+        }
+    ";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+    });
+
+    let source = "
+        struct Point { x, y }
+
+        impl Point {
+            fn sum(self) { self.x + self.y }
+            fn scale(self, factor) { Point(self.x * factor, self.y * factor) }
+        }
+
+        fn main() {
+            let p = Point(4, 3);
+            p.scale(6).sum() + p.x - p.scale(2).x / 2
+        }
+    ";
+    run_and_check_result_42(source);
+
+    let source = "
+        struct Holder { f }
+
+        impl Holder {
+            fn f(self) { 1 }
+        }
+
+        fn main() {
+            let h = Holder(fn () { 41 });
+            (h.f)() + h.f()
+        }
+    ";
+    run_and_check_result_42(source);
+
+    let source = "struct Foo { a } fn main() { Foo(1).b }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Runtime(RuntimeError::MissingField(struct_type, name))
+                if struct_type == "Foo" && name == "b"
+        ));
+    });
+}
+
+#[test]
+fn test_lists() {
+    let source = "
+        fn main() {
+            let mut list = [1, 2,];
+            list = push(list, 40);
+            list[0] = 0;
+            let copy = list;
+            list[1] = 3;
+            // assigning to an element doesn't affect copies of the list
+            len(list) + get(list, 2) + copy[1] - list[1]
+        }
+    ";
+    run_and_check_result_42(source);
+
+    let source = "fn main() { [1, 2][2] }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::ValueError(_))));
+    });
+
+    let source = "fn main() { len(42) }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+    });
+
+    let source = "fn main() { let list = [1]; list[0] = 2; }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Compiler(CompilerError::ImmutableVariable)
+        ));
+    });
+
+    // lists are compared element-wise
+    run_and_check_result_true("fn main() { [1, [2, 3]] == [1.0, [2, 3]] }");
+    run_and_check_result_true("fn main() { [] == [] }");
+    run_and_check_result_false("fn main() { [1, 2] == [1, 3] }");
+    run_and_check_result_false("fn main() { [1, 2] == [1, 2, 3] }");
+    run_and_check_result_true("fn main() { [1, 2] != [2, 1] }");
+}
+
+#[test]
+fn test_cmp() {
+    run_and_check_result_decimal("fn main() { cmp(1, 2) }", -1);
+    run_and_check_result_decimal("fn main() { cmp(2, 2) }", 0);
+    run_and_check_result_decimal("fn main() { cmp(3, 2) }", 1);
+    run_and_check_result_decimal("fn main() { cmp(2, 2.0) }", 0);
+    run_and_check_result_decimal("fn main() { cmp(-0.5, 0.25) }", -1);
+
+    run_and_check_result_decimal(r#"fn main() { cmp("a", "b") }"#, -1);
+    run_and_check_result_decimal(r#"fn main() { cmp("b", "b") }"#, 0);
+    run_and_check_result_decimal(r#"fn main() { cmp("ba", "b") }"#, 1);
+    run_and_check_result_decimal(r#"fn main() { cmp("", "a") }"#, -1);
+
+    for source in [
+        r#"fn main() { cmp(1, "1") }"#,
+        "fn main() { cmp(true, false) }",
+    ] {
+        run_and_check_result_error(source, |error| {
+            assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+        });
+    }
+}
+
+#[test]
+fn test_maps() {
+    let source = r#"
+        fn main() {
+            let map = { "a": 1, "b": 20, "a": 2, };
+            let copy = map_set(map, "c", 20);
+            // setting a key doesn't affect the original map
+            assert(!map_has(map, "c"));
+            assert(map_has(copy, "a"));
+            map_get(map, "a") + map_get(map, "b") + map_get(copy, "c")
+        }
+    "#;
+    run_and_check_result_42(source);
+
+    let source = r#"fn main() { { "b": 2, "a": 1 } }"#;
+    run_and_check_result(source, |actual| {
+        assert_eq!(format!("{:?}", actual?), r#"{"a": 1, "b": 2}"#);
+        Ok(())
+    });
+
+    // missing keys are looked up as unit
+    let source = r#"fn main() { map_get({ "a": 1 }, "b") }"#;
+    run_and_check_result_unit(source);
+
+    let source = r#"fn main() { map_get([1], "a") }"#;
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+    });
+
+    // maps are compared entry-wise, regardless of the order in the literal
+    run_and_check_result_true(r#"fn main() { { "a": 1, "b": [2] } == { "b": [2], "a": 1 } }"#);
+    run_and_check_result_true("fn main() { {} == {} }");
+    run_and_check_result_false(r#"fn main() { { "a": 1 } == { "a": 2 } }"#);
+    run_and_check_result_false(r#"fn main() { { "a": 1 } == { "b": 1 } }"#);
+    run_and_check_result_false(r#"fn main() { { "a": 1 } == { "a": 1, "b": 2 } }"#);
+}
+
+#[test]
+fn test_map_literal() {
+    run_and_check_result_42(include_str!("programs/map.spr"));
+
+    let source = "fn main() { { 1: 2 } }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+    });
+}
+
+#[test]
+fn test_string_interpolation() {
+    run_and_check_result_string(r#"fn main() { let v = 42; "x={v}" }"#, "x=42");
+    let source = r#"fn main() { let a = "a"; "{1 + 1} {a + a} {[()]}{{}}" }"#;
+    run_and_check_result_string(source, "2 aa [unit]{}");
+
+    let source = r#"fn main() { "a" + 1 }"#;
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::TypeError(_))));
+    });
+
+    let source = r#"fn main() { "total: {x" }"#;
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Compiler(CompilerError::Parse(_))));
+        assert!(
+            error.to_string().contains("unclosed '{' in string literal"),
+            "{error}"
+        );
+    });
+}
+
+#[test]
+fn test_bytes() {
+    let source = r#"fn main() { let data = b"\x00*\xff"; data[1] }"#;
+    run_and_check_result_42(source);
+
+    let source = r#"fn main() { b"\xff"[0] }"#;
+    run_and_check_result_decimal(source, 255);
+
+    let source = r#"fn main() { b"a\tb\x00" }"#;
+    run_and_check_result(source, |actual| {
+        let actual = actual?;
+        assert_eq!(actual.as_bytes()?, b"a\tb\x00");
+        assert_eq!(format!("{actual:?}"), r#"b"a\tb\x00""#);
+        Ok(())
+    });
+
+    run_and_check_result_true(r#"fn main() { b"ab" == b"ab" }"#);
+    run_and_check_result_false(r#"fn main() { b"ab" == "ab" }"#);
+
+    let source = r#"fn main() { b"ab"[2] }"#;
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::ValueError(_))));
+    });
+}
+
+#[test]
+fn test_assert_and_panic() {
+    let source = "fn main() { assert(1 < 2); 42 }";
+    run_and_check_result_42(source);
+
+    let source = "fn main() { assert(false); 42 }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(
+            error,
+            Error::Runtime(RuntimeError::AssertionFailed)
+        ));
+    });
+
+    let source = r#"fn main() { if true { panic("boom"); }; 42 }"#;
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::Panic(message)) if message == "boom"));
+    });
+}
+
+#[test]
+fn test_print() {
+    use std::sync::{Arc, Mutex};
+
+    let source = r#"
+        fn main() {
+            print(40 + 2);
+            print(" is ");
+            println("the answer");
+            println(1.5)
+        }
+    "#;
+    let bytecode = compile(source);
+    let module = parse_bytecode(&bytecode).unwrap();
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let vm = Vm::new(&module).unwrap().with_output(output.clone());
+    assert!(vm.run().unwrap().is_unit());
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, "42 is the answer\n1.5\n");
+
+    // a local variable shadows the built-in
+    let source = "fn main() { let print = fn(x) { x }; print(42) }";
+    run_and_check_result_42(source);
+
+    // as does a global function, even one declared after its use
+    let source = "fn main() { print(40, 2) } fn print(a, b) { a + b }";
+    run_and_check_result_42(source);
+
+    let source = "fn main() { print(1, 2) }";
+    let mut bytecode = Vec::new();
+    let error = compile_source_file(&mut bytecode, source).unwrap_err();
+    assert!(matches!(error, CompilerError::WrongArgumentNumber(name, 1, 2) if name == "print"));
+}
+
+#[test]
+fn test_multiple_return_values() {
+    let source = "
+        fn sum_diff(a, b) {
+            return a + b, a - b;
+        }
+
+        fn main() {
+            let (sum, diff) = sum_diff(30, 12);
+            let (a, b,) = (sum * 2, diff);
+            a / 2 + b - diff
+        }
+    ";
+    run_and_check_result_42(source);
+
+    let source = "struct Foo(a, b); fn main() { let (a, b) = Foo(40, 2); a + b }";
+    run_and_check_result_42(source);
+
+    let source = "fn main() { let (a, b) = (1, 2, 3); a }";
+    run_and_check_result_error(source, |error| {
+        assert!(matches!(error, Error::Runtime(RuntimeError::ValueError(_))));
+    });
+}
+
+#[test]
+fn test_trailing_expression() {
+    let source = "fn main() { 1; 42 }";
+    run_and_check_result_42(source);
+
+    let source = "fn main() { let x = { 1; 42 }; x }";
+    run_and_check_result_42(source);
+}
+
+#[test]
+fn test_trailing_statement() {
+    let source = "fn main() { 1; 42; }";
+    run_and_check_result_unit(source);
+
+    let source = "fn main() { let x = { 1; 42; }; x }";
+    run_and_check_result_unit(source);
+}
+
+#[test]
+fn test_unit_literal() {
+    let source = "fn main() { () }";
+    run_and_check_result_unit(source);
+
+    let source = "fn main() { () == () }";
+    run_and_check_result_true(source);
+}
+
+/// Functions whose body ends in a trailing expression don't contain a `Return`
+/// instruction; their result is what's left on the stack when the body's
+/// instructions run out.
+mod fall_through {
+    use super::*;
+
+    #[test]
+    fn test_bool() {
+        let source = "fn not(x) { let y = !x; y } fn main() { not(false) }";
+        run_and_check_result_true(source);
+    }
+
+    #[test]
+    fn test_number() {
+        let source = "fn add(a, b) { let c = a + b; c } fn main() { add(40, 2) }";
+        run_and_check_result_42(source);
+    }
+
+    #[test]
+    fn test_call() {
+        let source = "fn id(x) { x } fn twice(x) { id(x) + id(x) } fn main() { twice(21) }";
+        run_and_check_result_42(source);
+    }
+
+    #[test]
+    fn test_if() {
+        let source = "fn pick(c, a, b) { if c { a } else { b } } fn main() { pick(true, 42, 0) }";
+        run_and_check_result_42(source);
+
+        let source = "fn pick(c, a, b) { if c { a } else { b } } fn main() { pick(false, 0, 42) }";
+        run_and_check_result_42(source);
+    }
+
+    #[test]
+    fn test_if_without_else() {
+        run_and_check_result_unit("fn main() { if false { 1 } }");
+        run_and_check_result_42("fn main() { if true { 42 } }");
+        run_and_check_result_unit("fn main() { if false { 1 } else if false { 2 } }");
+        // compiled as a jump table
+        run_and_check_result_unit("fn main() { let x = 2; if x == 0 { 1 } else if x == 1 { 2 } }");
+        run_and_check_result_42("fn main() { let x = 1; if x == 0 { 1 } else if x == 1 { 42 } }");
+
+        // verification checks each instruction's stack effect
+        for source in [
+            "fn main() { if false { 1 } }",
+            "fn main() { let a = 1; if a == 0 { let b = 2; b } }",
+            "fn main() { let x = 2; if x == 0 { 1 } else if x == 1 { 2 } }",
+        ] {
+            let bytecode = compile(source);
+            let module = parse_bytecode(&bytecode).unwrap();
+            let vm = Vm::new(&module).unwrap().with_verification(true);
+            assert!(vm.run().unwrap().is_unit(), "{source:?}");
+        }
+    }
+}
+
+#[test]
+fn test_variable() {
+    run_and_check_result_42(include_str!("programs/variable.spr"))
+}
+
+mod embedding {
+    use super::*;
+
+    #[test]
+    fn test_call_with_bool() {
+        let source = "fn not(x) { !x }";
+        call_and_check_result(source, "not", [false.into()], |actual| {
+            assert!(actual?.as_bool()?);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_call_with_number() {
+        let source = "fn add(a, b) { a + b }";
+        call_and_check_result(source, "add", [20.into(), 22.into()], |actual| {
+            assert_eq!(actual?.as_number()?, &BigDecimal::from(42));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_call_with_string() {
+        let source = "fn id(x) { x }";
+        call_and_check_result(source, "id", ["foo".into()], |actual| {
+            assert_eq!(actual?.as_string()?, "foo");
+            Ok(())
+        });
+
+        call_and_check_result(source, "id", ["bar".to_string().into()], |actual| {
+            assert_eq!(actual?.as_string()?, "bar");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_string_formatting() {
+        fn check(value: Value) {
+            assert_eq!(format!("{value:?}"), r#""a\nb\t""#);
+            assert_eq!(format!("{value}"), "a\nb\t");
+        }
+
+        check("a\nb\t".into());
+        run_and_check_result(r#"fn main() { "a\nb\t" }"#, |actual| {
+            check(actual?);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_call_wrong_arity() {
+        let source = "fn id(x) { x }";
+        call_and_check_result(source, "id", [], |actual| {
+            assert!(matches!(
+                actual.unwrap_err(),
+                Error::Runtime(RuntimeError::ValueError(_))
+            ));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_main_with_parameters() {
+        let source = "fn main(x) { x }";
+        run_and_check_result_error(source, |error| {
+            assert!(matches!(
+                error,
+                Error::Runtime(RuntimeError::MainSignature(1))
+            ));
+        });
+
+        // calling `main` explicitly with arguments still works
+        call_and_check_result(source, "main", [42.into()], |actual| {
+            assert_eq!(actual?.as_number()?, &BigDecimal::from(42));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_owned_vm() {
+        let bytecode = compile("fn main() { 42 } fn add(a, b) { a + b }");
+
+        let vm = OwnedVm::new(bytecode).unwrap();
+        assert_eq!(
+            vm.run().unwrap().as_number().unwrap(),
+            &BigDecimal::from(42)
+        );
+
+        let result = vm.call_function("add", [20.into(), 22.into()]).unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+    }
+
+    #[test]
+    fn test_owned_vm_invalid_bytecode() {
+        let error = OwnedVm::new(b"not bytecode".to_vec()).unwrap_err();
+        assert!(matches!(error, RuntimeError::Internal(_)));
+    }
+
+    #[test]
+    fn test_number_conversion() {
+        let number = |value: &str| Value::number(value.parse().unwrap());
+
+        assert_eq!(number("42").as_i64().unwrap(), 42);
+        assert_eq!(number("42.000").as_i64().unwrap(), 42);
+        assert_eq!(number("42").as_f64().unwrap(), 42.0);
+        assert_eq!(number("-1.5").as_f64().unwrap(), -1.5);
+        assert_eq!(number("0.1").as_f64().unwrap(), 0.1);
+
+        assert!(matches!(
+            number("1.5").as_i64(),
+            Err(RuntimeError::ValueError(_))
+        ));
+        let large = number("99999999999999999999");
+        assert!(matches!(large.as_i64(), Err(RuntimeError::ValueError(_))));
+        assert_eq!(large.as_f64().unwrap(), 1e20);
+        let huge = number("1e400");
+        assert!(matches!(huge.as_f64(), Err(RuntimeError::ValueError(_))));
+        let huge = number("-1e400");
+        assert!(matches!(huge.as_f64(), Err(RuntimeError::ValueError(_))));
+
+        assert!(matches!(
+            Value::from("42").as_i64(),
+            Err(RuntimeError::TypeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_precision() {
+        use sprachli::vm::{PrecisionContext, RoundingMode::*};
+
+        let run = |source: &str, precision: Option<PrecisionContext>| {
+            let bytecode = compile(source);
+            let module = parse_bytecode(&bytecode).unwrap();
+            let mut vm = Vm::new(&module).unwrap();
+            if let Some(precision) = precision {
+                vm = vm.with_precision(precision);
+            }
+            vm.run().unwrap().to_string()
+        };
+
+        let third = "fn main() { 1 / 3 }";
+        assert!(run(third, None).starts_with("0.3333333333"));
+        assert_eq!(run(third, Some(PrecisionContext::new(2, HalfUp))), "0.33");
+        assert_eq!(run(third, Some(PrecisionContext::new(5, Up))), "0.33334");
+
+        let cases = [
+            ("fn main() { 2 / 3 }", HalfUp, "0.67"),
+            ("fn main() { 2 / 3 }", Down, "0.66"),
+            ("fn main() { -2 / 3 }", Floor, "-0.67"),
+            ("fn main() { -2 / 3 }", Ceiling, "-0.66"),
+            ("fn main() { 1 / 8 }", HalfUp, "0.13"),
+            ("fn main() { 1 / 8 }", HalfDown, "0.12"),
+            ("fn main() { 1 / 8 }", HalfEven, "0.12"),
+            ("fn main() { 3 / 8 }", HalfEven, "0.38"),
+            ("fn main() { 0.15 * 0.5 }", HalfEven, "0.08"),
+            // results that are precise enough are not touched
+            ("fn main() { 1 / 4 }", Down, "0.25"),
+            ("fn main() { 2 / 3 * 3 }", HalfUp, "2.01"),
+        ];
+        for (source, rounding, expected) in cases {
+            let precision = PrecisionContext::new(2, rounding);
+            assert_eq!(
+                run(source, Some(precision)),
+                expected,
+                "{source} with {rounding:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_verification() {
+        use std::sync::{Arc, Mutex};
+
+        use sprachli::bytecode::instruction::Instruction::*;
+        use sprachli::bytecode::ModuleBuilder;
+        use sprachli::vm::InternalError;
+
+        // prints before reaching an invalid constant reference
+        let mut builder = ModuleBuilder::new();
+        let hello = builder.add_string("hello");
+        let main = builder
+            .add_function(0, [Constant(hello), Print, Pop, Constant(99), Return])
+            .unwrap();
+        builder.add_global("main", main);
+        let module = builder.build();
+
+        let run = |verify| {
+            let output = Arc::new(Mutex::new(Vec::new()));
+            let vm = Vm::new(&module)
+                .unwrap()
+                .with_output(output.clone())
+                .with_verification(verify);
+            let result = vm.run().map(|value| value.to_string());
+            let output = output.lock().unwrap().clone();
+            (result, output)
+        };
+
+        let (result, output) = run(false);
+        assert!(matches!(result, Err(RuntimeError::Internal(_))));
+        assert_eq!(output, b"hello");
+
+        let (result, output) = run(true);
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error,
+            RuntimeError::Internal(InternalError::InvalidBytecode(_))
+        ));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_value_encoding() {
+        use sprachli::bytecode::{Constant, Function, InstructionSequence};
+        use sprachli::vm::MAX_DECODING_DEPTH;
+
+        let point = |x: i64| {
+            let fields = vec![x.into(), Value::list(vec![true.into(), "x".into()])];
+            Value::new_struct("Point", fields)
+        };
+        let values = [
+            Value::unit(),
+            false.into(),
+            true.into(),
+            Value::number("-1.50".parse().unwrap()),
+            "hällo".into(),
+            Value::list(vec![]),
+            Value::bytes(vec![0, 255]),
+            Value::tuple(vec![1.into(), Value::unit()]),
+            Value::list(vec![point(1), point(2)]),
+            Value::map([("b".to_string(), point(1)), ("a".to_string(), 2.into())].into()),
+        ];
+        for value in values {
+            let bytes = value.encode().unwrap();
+            let decoded = Value::decode(&bytes).unwrap();
+            assert_eq!(format!("{decoded:?}"), format!("{value:?}"));
+        }
+
+        let function = Function::new(0, InstructionSequence::new(&[]));
+        let function = Value::constant(Constant::Function(function));
+        assert!(matches!(
+            function.encode(),
+            Err(RuntimeError::ValueError(_))
+        ));
+        let point_type = Value::struct_type("Point");
+        assert!(matches!(
+            point_type.encode(),
+            Err(RuntimeError::ValueError(_))
+        ));
+
+        let bytes = Value::from("abc").encode().unwrap();
+        assert!(Value::decode(&bytes[..bytes.len() - 1]).is_err());
+        let bytes = [
+            Value::unit().encode().unwrap(),
+            Value::unit().encode().unwrap(),
+        ]
+        .concat();
+        assert!(Value::decode(&bytes).is_err());
+        assert!(Value::decode(&[42]).is_err());
+
+        // lists nested `depth` levels deep, each containing the next one, and finally unit
+        let nested = |depth: usize| [[5, 0, 0, 0, 1].repeat(depth - 1), vec![0]].concat();
+        assert!(Value::decode(&nested(MAX_DECODING_DEPTH)).is_ok());
+        let error = Value::decode(&nested(MAX_DECODING_DEPTH + 1)).unwrap_err();
+        assert!(matches!(error, RuntimeError::ValueError(message) if message.contains("nested")));
+    }
+
+    #[test]
+    fn test_strict_equality() {
+        let source = "fn main() { 1 == \"1\" }";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        let result = Vm::new(&module).unwrap().run().unwrap();
+        assert!(!result.as_bool().unwrap());
+
+        let vm = Vm::new(&module).unwrap().with_strict_equality(true);
+        let error = vm.run().unwrap_err();
+        assert!(matches!(error, RuntimeError::TypeError(_)));
+    }
+
+    #[test]
+    fn test_div_by_zero() {
+        use sprachli::vm::{DivByZero, DIV_BY_ZERO_SENTINEL};
+
+        let run = |source: &str, div_by_zero| {
+            let bytecode = compile(source);
+            let module = parse_bytecode(&bytecode).unwrap();
+            let vm = Vm::new(&module).unwrap().with_div_by_zero(div_by_zero);
+            vm.run().map(|value| format!("{value:?}"))
+        };
+
+        let error = run("fn main() { 1 / 0 }", DivByZero::Error).unwrap_err();
+        assert!(matches!(error, RuntimeError::ValueError(_)));
+
+        let sentinel = DIV_BY_ZERO_SENTINEL.to_string();
+        assert_eq!(
+            run("fn main() { 1 / 0 }", DivByZero::Infinity).unwrap(),
+            sentinel
+        );
+        let negative = format!("-{sentinel}");
+        assert_eq!(
+            run("fn main() { -1 / 0 }", DivByZero::Infinity).unwrap(),
+            negative
+        );
+        assert_eq!(
+            run("fn main() { 0 / 0 }", DivByZero::Infinity).unwrap(),
+            "0"
+        );
+        assert_eq!(
+            run("fn main() { 1 % 0 }", DivByZero::Infinity).unwrap(),
+            sentinel
+        );
+
+        assert_eq!(run("fn main() { 1 / 0 }", DivByZero::Unit).unwrap(), "unit");
+        assert_eq!(
+            run("fn main() { 1 % 0.0 }", DivByZero::Unit).unwrap(),
+            "unit"
+        );
+    }
+
+    #[test]
+    fn test_truthiness() {
+        use std::collections::BTreeMap;
+
+        let number = |value: &str| Value::number(value.parse().unwrap());
+
+        for falsy in [
+            number("0"),
+            number("-0.0"),
+            number("0.00"),
+            "".into(),
+            false.into(),
+            Value::Unit,
+            Value::list(Vec::new()),
+            Value::map(BTreeMap::new()),
+        ] {
+            assert!(!falsy.is_truthy(), "{falsy:?} should be falsy");
+        }
+        for truthy in [
+            number("1"),
+            number("-0.01"),
+            "0".into(),
+            true.into(),
+            Value::list(vec![Value::Unit]),
+            Value::map(BTreeMap::from([("".to_string(), Value::Unit)])),
+        ] {
+            assert!(truthy.is_truthy(), "{truthy:?} should be truthy");
+        }
+
+        let source = "fn main() { if 0 { 1 } else if !\"\" { 42 } else { 2 } }";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        let error = Vm::new(&module).unwrap().run().unwrap_err();
+        assert!(matches!(error, RuntimeError::TypeError(_)));
+
+        let vm = Vm::new(&module).unwrap().with_truthy_conditions(true);
+        let result = vm.run().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+
+        let run = |source: &str| {
+            let bytecode = compile(source);
+            let module = parse_bytecode(&bytecode).unwrap();
+            let mut vm = Vm::new(&module).unwrap().with_truthy_conditions(true);
+            vm.set_global("empty", Value::map(BTreeMap::new()));
+            let result = vm.run().unwrap();
+            assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+        };
+        run("fn main() { if [] { 1 } else if [0] { 42 } else { 2 } }");
+        // `{}` is an empty block, so the empty map is provided by the host
+        run("fn main() { if empty { 1 } else if { \"a\": 0 } { 42 } else { 2 } }");
+    }
+
+    #[test]
+    fn test_divide_normalizes() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash(value: &BigDecimal) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let number = |value: &str| Value::number(value.parse().unwrap());
+        let source = "fn div(a, b) { a / b }";
+
+        for (a, b) in [("84", "2"), ("84.0", "2"), ("4.2", "0.1"), ("84.00", "2.0")] {
+            call_and_check_result(source, "div", [number(a), number(b)], |actual| {
+                let actual = actual?;
+                assert_eq!(format!("{actual:?}"), "42");
+                assert_eq!(hash(actual.as_number()?), hash(&BigDecimal::from(42)));
+                Ok(())
+            });
+        }
+
+        call_and_check_result(source, "div", [number("1.0"), number("4")], |actual| {
+            assert_eq!(format!("{:?}", actual?), "0.25");
+            Ok(())
+        });
+
+        let source = "fn main() { 184467440737095516160 / 10 }";
+        run_and_check_result(source, |actual| {
+            assert_eq!(format!("{:?}", actual?), "18446744073709551616");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_module_builder() {
+        use sprachli::bytecode::instruction::{BinaryOperator, Instruction::*};
+        use sprachli::bytecode::ModuleBuilder;
+
+        let mut builder = ModuleBuilder::new();
+        let double = builder
+            .add_function(
+                1,
+                [
+                    LoadLocal(0),
+                    LoadLocal(0),
+                    Binary(BinaryOperator::Add),
+                    Return,
+                ],
+            )
+            .unwrap();
+        let number = builder.add_number(21);
+        let name = builder.add_string("double");
+        let main = builder
+            .add_function(0, [LoadFunction(name), Constant(number), Call(1), Return])
+            .unwrap();
+        builder.add_global("double", double);
+        builder.add_global("main", main);
+
+        let result = Vm::new(&builder.build()).unwrap().run().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+
+        let mut bytecode = Vec::new();
+        builder.write_bytecode(&mut bytecode).unwrap();
+        let vm = OwnedVm::new(bytecode).unwrap();
+        let result = vm.call_function("double", [4.into()]).unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(8));
+    }
+
+    #[test]
+    fn test_nop() {
+        use sprachli::bytecode::instruction::{
+            InlineConstant, Instruction, Instruction::*, Offset,
+        };
+        use sprachli::bytecode::ModuleBuilder;
+
+        fn run(body: impl FnOnce(usize, usize) -> Vec<Instruction>) {
+            let mut builder = ModuleBuilder::new();
+            let zero = builder.add_number(0);
+            let answer = builder.add_number(42);
+            let main = builder.add_function(0, body(zero, answer)).unwrap();
+            builder.add_global("main", main);
+
+            let mut bytecode = Vec::new();
+            builder.write_bytecode(&mut bytecode).unwrap();
+            let module = parse_bytecode(&bytecode).unwrap();
+            let result = Vm::new(&module).unwrap().run().unwrap();
+            assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+        }
+
+        run(|_, answer| vec![Constant(answer), Return]);
+        run(|_, answer| vec![Nop, Constant(answer), Nop, Nop, Return]);
+
+        // blanking out the skipped instructions doesn't change the jump's target
+        let cond = |value| InlineConstant(InlineConstant::Bool(value));
+        run(|zero, answer| {
+            let jump = JumpIf(Offset::Forward(3));
+            vec![
+                cond(true),
+                jump,
+                Constant(zero),
+                Return,
+                Constant(answer),
+                Return,
+            ]
+        });
+        for value in [true, false] {
+            run(|zero, answer| {
+                let mut body = vec![cond(value), JumpIf(Offset::Forward(3))];
+                body.extend(
+                    [Constant(zero), Return]
+                        .into_iter()
+                        .flat_map(Instruction::blanked),
+                );
+                body.extend([Constant(answer), Return]);
+                body
+            });
+        }
+    }
+
+    #[test]
+    fn test_drop() {
+        use std::sync::{Arc, Mutex};
+
+        use sprachli::bytecode::instruction::{InlineConstant, Instruction::*};
+        use sprachli::bytecode::ModuleBuilder;
+
+        fn run(module: &sprachli::bytecode::Module<'_>) -> Vec<BigDecimal> {
+            let dropped = Arc::new(Mutex::new(Vec::new()));
+            let log = dropped.clone();
+            let vm = Vm::new(module)
+                .unwrap()
+                .with_native("open", 1, |mut args| {
+                    Ok(Value::droppable("file", args.remove(0)))
+                })
+                .with_destructor("file", move |value| {
+                    log.lock().unwrap().push(value.as_number()?.clone());
+                    Ok(())
+                });
+            vm.run().unwrap();
+            Arc::try_unwrap(dropped).unwrap().into_inner().unwrap()
+        }
+
+        // locals are dropped when their scope ends, but only once for all copies;
+        // discarded temporaries such as `open(2)` are dropped right away
+        let source = "\
+            fn main() {
+                let a = { let f = open(1); let g = f; open(2); 3 };
+                let b = open(4);
+                ()
+            }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        assert_eq!(run(&module), [2, 1, 4].map(BigDecimal::from));
+
+        // parameters are dropped when the call returns, overwritten values on
+        // assignment, and values in lists, maps and structs with the last
+        // reference to their container
+        let source = r#"
+            struct Handle(file);
+            fn f(x) { () }
+            fn main() {
+                f(open(1));
+                let l = [open(2)];
+                let m = { "file": open(3) };
+                let s = Handle(open(4));
+                let mut v = open(5);
+                v = open(6);
+                ()
+            }
+        "#;
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        assert_eq!(run(&module), [1, 5, 2, 3, 4, 6].map(BigDecimal::from));
+
+        // `Drop` runs the destructor of the value on top of the stack
+        let mut builder = ModuleBuilder::new();
+        let open = builder.add_string("open");
+        let answer = builder.add_number(42);
+        let body = vec![
+            LoadNamed(open),
+            Constant(answer),
+            Call(1),
+            Drop,
+            InlineConstant(InlineConstant::Unit),
+            Return,
+        ];
+        let main = builder.add_function(0, body).unwrap();
+        builder.add_global("main", main);
+        assert_eq!(run(&builder.build()), [BigDecimal::from(42)]);
+    }
+
+    #[test]
+    fn test_forked_format() {
+        use sprachli::bytecode::{parse_bytecode_with_config, BytecodeConfig};
+        use sprachli::compiler::write_bytecode_with_config;
+
+        let source = "fn main() { 42 }";
+        let module = Module::new(parse_source_file(source).unwrap()).unwrap();
+
+        let fork = BytecodeConfig::new(b"forkli", 3);
+        let mut bytecode = Vec::new();
+        write_bytecode_with_config(&mut bytecode, &module, &fork).unwrap();
+        assert!(bytecode.starts_with(b"forkli\0\x03"));
+
+        // stock sprachli doesn't accept the fork's bytecode
+        assert!(parse_bytecode(&bytecode).is_err());
+        // ... and a version mismatch is reported as such
+        let newer = BytecodeConfig::new(b"forkli", 2);
+        assert!(matches!(
+            parse_bytecode_with_config(&bytecode, &newer),
+            Err(BytecodeError::UnsupportedVersion(3))
+        ));
+
+        let module = parse_bytecode_with_config(&bytecode, &fork).unwrap();
+        let result = Vm::new(&module).unwrap().run().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+    }
+
+    #[test]
+    fn test_global_referring_to_identifier() {
+        use sprachli::bytecode::instruction::Instruction::{LoadNamed, Return};
+        use sprachli::bytecode::ModuleBuilder;
+        use sprachli::vm::InternalError;
+
+        let mut builder = ModuleBuilder::new();
+        let name = builder.add_string("main");
+        builder.add_global("main", name);
+
+        let error = Vm::new(&builder.build()).unwrap_err();
+        assert!(matches!(
+            error,
+            RuntimeError::Internal(InternalError::InvalidGlobal(name, _)) if name == "main"
+        ));
+
+        let mut bytecode = Vec::new();
+        builder.write_bytecode(&mut bytecode).unwrap();
+        assert!(OwnedVm::new(bytecode).is_err());
+
+        // string values are fine, even if they look like identifiers
+        let mut builder = ModuleBuilder::new();
+        let hello = builder.add_string("hello");
+        let greeting = builder.add_global("greeting", hello);
+        let main = builder
+            .add_function(0, [LoadNamed(greeting), Return])
+            .unwrap();
+        builder.add_global("main", main);
+        let result = Vm::new(&builder.build()).unwrap().run().unwrap();
+        assert_eq!(result.as_string().unwrap(), "hello");
+
+        let mut builder = ModuleBuilder::new();
+        builder.add_global("main", 42);
+        let error = Vm::new(&builder.build()).unwrap_err();
+        assert!(matches!(
+            error,
+            RuntimeError::Internal(InternalError::InvalidConstant(42, _))
+        ));
+    }
+
+    #[test]
+    fn test_link_modules() {
+        let compile = |source: &str| Module::new(parse_source_file(source).unwrap()).unwrap();
+
+        let main = compile("fn main() { helper(20) + 1 }");
+        let helper = compile("fn helper(x) { x * 2 + 1 }");
+        let module = main.link(helper).unwrap();
+
+        let mut bytecode = Vec::new();
+        write_bytecode(&mut bytecode, &module).unwrap();
+        let module = parse_bytecode(&bytecode).unwrap();
+        let result = Vm::new(&module).and_then(Vm::run).unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+
+        let main = compile("fn main() { helper() }");
+        let error = main.clone().link(main).unwrap_err();
+        assert!(matches!(error, CompilerError::DuplicateGlobal(name) if name == "main"));
+
+        // a function and a struct type can't share a name either
+        let main = compile("struct Foo; fn main() { Foo }");
+        let foo = compile("fn Foo(x) { x }");
+        let error = main.clone().link(foo.clone()).unwrap_err();
+        assert!(matches!(error, CompilerError::DuplicateGlobal(name) if name == "Foo"));
+        let error = foo.link(main).unwrap_err();
+        assert!(matches!(error, CompilerError::DuplicateGlobal(name) if name == "Foo"));
+    }
+
+    #[test]
+    fn test_set_global() {
+        let source = "\
+            fn answer() { 0 }
+            fn main() { answer + offset }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        let mut vm = Vm::new(&module).unwrap();
+
+        let error = vm.clone().run().unwrap_err();
+        assert!(matches!(error, RuntimeError::NameError(name) if name == "offset"));
+
+        vm.set_global("offset", Value::from(2));
+        // host globals override the module's
+        vm.set_global("answer", Value::from(40));
+        let result = vm.run().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+
+        // overrides also apply to functions that are called directly
+        let source = "\
+            fn answer() { 0 }
+            fn forty_two() { 42 }
+            fn main() { answer() }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        let mut vm = Vm::new(&module).unwrap();
+        let forty_two = module.global("forty_two").unwrap().clone();
+        vm.set_global("answer", Value::constant(forty_two));
+        let result = vm.run().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+
+        // `a` and `b` compile to the same constant, but overriding one doesn't
+        // affect the other
+        let source = "\
+            fn a() { 0 }
+            fn b() { 0 }
+            fn main() { b() }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        assert_eq!(module.globals()["a"], module.globals()["b"]);
+        let mut vm = Vm::new(&module).unwrap();
+        vm.set_global("a", Value::from(1));
+        let result = vm.run().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(0));
+    }
+
+    #[test]
+    fn test_shared_module() {
+        let bytecode = compile("fn main() { offset * 2 }");
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        // both VMs borrow the same module at the same time
+        let mut first = Vm::new(&module).unwrap();
+        let mut second = Vm::new(&module).unwrap();
+        first.set_global("offset", Value::from(1));
+        second.set_global("offset", Value::from(21));
+
+        let first = first.run().unwrap();
+        let second = second.run().unwrap();
+        assert_eq!(first.as_number().unwrap(), &BigDecimal::from(2));
+        assert_eq!(second.as_number().unwrap(), &BigDecimal::from(42));
+    }
+
+    #[test]
+    fn test_call_native() {
+        let source = "\
+            fn apply(f, x) { f(x) }
+            fn increment(x) { x + 1 }
+            fn main() { apply(double, 10) + apply(increment, 1) + apply(fn(x) { x * 10 }, 2) }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        let vm = Vm::new(&module).unwrap().with_native("double", 1, |args| {
+            let x = args[0].as_number()?;
+            Ok(Value::number(x + x))
+        });
+
+        // the same call site in `apply` calls a native, a global and an anonymous function
+        let result = vm.clone().run().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+
+        let error = vm.call_function("double", [Value::from(1), Value::from(2)]);
+        assert!(matches!(error, Err(RuntimeError::ValueError(_))));
+    }
+
+    fn gas_vm(source: &str, gas: u64, f: impl FnOnce(&Vm, Result<Value, RuntimeError>)) {
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        let mut vm = Vm::new(&module).unwrap();
+        let result = vm.run_with_gas(gas);
+        f(&vm, result);
+    }
+
+    #[test]
+    fn test_return_from_nested_scopes() {
+        let source = "\
+            fn nested(a) {
+                let b = a + 1;
+                {
+                    let c = b + 1;
+                    {
+                        let d = c + 1;
+                        {
+                            let e = d + 1;
+                            return a + b + c + d + e + 27;
+                        };
+                        0
+                    }
+                }
+            }
+            fn main() { let x = nested(1); x }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        let mut vm = Vm::new(&module).unwrap().with_verification(true);
+
+        let result = vm.run_in_place().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+        // the return value replaced all of the locals of `nested`
+        assert!(vm.stack().is_empty());
+    }
+
+    #[test]
+    fn test_run_with_gas() {
+        let source = "\
+            fn count(n) { if n == 0 { 42 } else { count(n - 1) } }
+            fn main() { count(5) }
+        ";
+        gas_vm(source, 10_000, |vm, result| {
+            assert_eq!(result.unwrap().as_number().unwrap(), &BigDecimal::from(42));
+            assert!(vm.consumed_gas() > 0);
+            // main, then count(5) down to count(0)
+            assert_eq!(vm.peak_depth(), 7);
+        });
+    }
+
+    #[test]
+    fn test_cancellation() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::Duration;
+
+        let bytecode = compile("fn main() { loop {} }");
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let canceller = {
+            let cancelled = cancelled.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                cancelled.store(true, Ordering::Relaxed);
+            })
+        };
+
+        let vm = Vm::new(&module).unwrap().with_cancellation(cancelled);
+        let result = vm.run();
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(RuntimeError::Cancelled)));
+    }
+
+    #[test]
+    fn test_call_step_limit() {
+        let source = "\
+            fn busy(n) { let mut i = 0; loop { if i == n { break i; }; i = i + 1; } }
+            fn spin() { loop {} }
+            fn main() { busy(10) + busy(10) + busy(10) + spin() }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        // all calls of `busy` together exceed the limit, but none of them alone
+        let result = Vm::new(&module).unwrap().with_call_step_limit(200).run();
+        assert!(matches!(result, Err(RuntimeError::CallStepLimit(name)) if name == "spin"));
+
+        let busy = |limit| {
+            let vm = Vm::new(&module).unwrap().with_call_step_limit(limit);
+            vm.call_function("busy", [Value::from(10)])
+        };
+        assert_eq!(
+            busy(200).unwrap().as_number().unwrap(),
+            &BigDecimal::from(10)
+        );
+        let result = busy(20);
+        assert!(matches!(result, Err(RuntimeError::CallStepLimit(name)) if name == "busy"));
+    }
+
+    #[test]
+    fn test_stack_after_run() {
+        let source = "\
+            fn count(n) { if n == 0 { 42 } else { count(n - 1) } }
+            fn main() { let x = 1; count(5) }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        let mut vm = Vm::new(&module).unwrap();
+
+        let result = vm.run_in_place().unwrap();
+        assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+        assert!(vm.stack().is_empty());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap() {
+        use sprachli::mmap::map_bytecode_file;
+
+        let name = format!("sprachli-mmap-{}.sprb", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let mut file = fs::File::create(&path).unwrap();
+        compile_source_file(&mut file, include_str!("programs/max.spr")).unwrap();
+        drop(file);
+
+        {
+            // SAFETY: the file is private to this test and not modified while mapped
+            let bytecode = unsafe { map_bytecode_file(&path) }.unwrap();
+            let module = parse_bytecode(&bytecode).unwrap();
+            let result = Vm::new(&module).and_then(Vm::run).unwrap();
+            assert_eq!(result.as_number().unwrap(), &BigDecimal::from(42));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_run_out_of_gas() {
+        let source = "fn main() { loop {} }";
+        gas_vm(source, 100, |vm, result| {
+            assert!(matches!(result, Err(RuntimeError::OutOfGas)));
+            assert_eq!(vm.consumed_gas(), 100);
+            assert_eq!(vm.peak_depth(), 1);
+        });
+    }
+}
+
+mod analysis {
+    use super::*;
+
+    use sprachli::bytecode::instruction::Instruction;
+
+    #[test]
+    fn test_find_missing_return() {
+        let source = "\
+            fn implicit() { 42 }
+            fn explicit() { return 42; }
+            fn branch(x) { if x { return 1; }; 2 }
+            fn main() { return implicit(); }
+        ";
+
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        // a function without any `Return` instruction always falls off the end;
+        // a `return` at the end of a function is compiled like a final
+        // expression, so only `branch` needs a `Return` instruction
+        let missing = module
+            .functions()
+            .filter(|(_, function)| {
+                let mut instructions = function.instructions();
+                !instructions.any(|ins| matches!(ins, Ok(Instruction::Return)))
+            })
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+
+        assert_eq!(missing, ["explicit", "implicit", "main"]);
+    }
+
+    #[test]
+    fn test_max_stack() {
+        use sprachli::compiler::Constant;
+
+        let source = "\
+            fn constant() { 42 }
+            fn nested(a) { a + 2 * (3 - a) }
+            fn locals(a) { let b = a; { let c = b; c } }
+            fn main() { nested(1) }
+        ";
+        let module = Module::new(parse_source_file(source).unwrap()).unwrap();
+
+        let max_stack = |name| match module.global(name) {
+            Some(Constant::Function(function)) => function.max_stack(),
+            _ => panic!("{name} is not a function"),
+        };
+        assert_eq!(max_stack("constant"), 1);
+        // `a`, then `a`, `2`, `3` and `a` for the operands of the innermost operator
+        assert_eq!(max_stack("nested"), 5);
+        // `a`, `b` and `c`, plus `c` loaded as the block's value
+        assert_eq!(max_stack("locals"), 4);
+
+        let dump = format!("{module:#?}");
+        assert!(dump.contains("fn (_0) { -- max stack 5\n"), "{dump}");
+    }
+
+    #[test]
+    fn test_constant_conditions() {
+        let source = "\
+            fn taken() { if true { 42 } else { crash } }
+            fn skipped() { if false { crash } else { 42 } }
+            fn chain(x) {
+                if x == 1 { 1 } else if false { crash } else if true { 42 } else { crash }
+            }
+            fn unit() { if false { crash } }
+            fn main() { taken() + skipped() - chain(2) }
+        ";
+
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        // the dead branches are not compiled, so `crash` is never referenced
+        assert!(module.unresolved_globals().is_empty());
+        for (name, function) in module.functions() {
+            let jumps = function
+                .instructions()
+                .filter(|ins| matches!(ins, Ok(Instruction::Jump(_) | Instruction::JumpIf(_))))
+                .count();
+            let expected = if name == "chain" { 2 } else { 0 };
+            assert_eq!(jumps, expected, "{name}");
+        }
+
+        run_and_check_result_42(source);
+        call_and_check_result(source, "unit", [], |actual| {
+            assert!(actual?.is_unit());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_trailing_return() {
+        use sprachli::bytecode::Constant;
+
+        let source = "\
+            fn implicit(x) { let y = x; y }
+            fn explicit(x) { let y = x; return y; }
+            fn expression(x) { let y = x; return y }
+            fn unit() { return; }
+            fn main() { explicit(42) }
+        ";
+
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        let body_len = |name| match module.global(name) {
+            Some(Constant::Function(function)) => function.body_len(),
+            _ => panic!("{name} is not a function"),
+        };
+        // without dropping it, the `Return` would add one byte
+        assert_eq!(body_len("explicit"), body_len("implicit"));
+        assert_eq!(body_len("expression"), body_len("implicit"));
+        // `return;` returns unit, like an empty body
+        assert_eq!(body_len("unit"), 3);
+
+        run_and_check_result_42(source);
+    }
+
+    #[test]
+    fn test_slice_loop_body() {
+        use sprachli::bytecode::instruction::{BinaryOperator, Offset};
+
+        let source = "\
+            fn main() {
+                let mut i = 0;
+                loop {
+                    if i == 42 { break i; };
+                    i = i + 1;
+                }
+            }
+        ";
+
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        let (_, main) = module
+            .functions()
+            .find(|(name, _)| *name == "main")
+            .unwrap();
+
+        // the loop body spans from the backwards jump's target up to the jump itself
+        let (start, end) = main
+            .instructions()
+            .with_offset()
+            .find_map(|(offset, ins)| match ins.unwrap() {
+                Instruction::Jump(Offset::Backward(jump)) => Some((offset + 2 - jump, offset)),
+                _ => None,
+            })
+            .unwrap();
+
+        let body = main.body().slice(start..end).unwrap();
+        let instructions = body.iter().collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(instructions.contains(&Instruction::Binary(BinaryOperator::Add)));
+        assert!(!instructions
+            .iter()
+            .any(|ins| matches!(ins, Instruction::Jump(Offset::Backward(_)))));
+
+        // the loop body starts with loading `i`, and ends with a two-byte jump
+        assert!(main.body().slice(start + 1..end).is_none());
+        assert!(main.body().slice(start..end + 1).is_none());
+        assert!(main
+            .body()
+            .slice(start..main.body().get().len() + 1)
+            .is_none());
+    }
+
+    #[test]
+    fn test_discarded_block_pushes_no_unit() {
+        use sprachli::bytecode::instruction::InlineConstant;
+
+        let body_len = |source: &str| {
+            let bytecode = compile(source);
+            let module = parse_bytecode(&bytecode).unwrap();
+            let (_, main) = module
+                .functions()
+                .find(|(name, _)| *name == "main")
+                .unwrap();
+            let instructions = main.instructions().collect::<Result<Vec<_>, _>>().unwrap();
+            assert!(!instructions.contains(&Instruction::InlineConstant(InlineConstant::Unit)));
+            main.body().get().len()
+        };
+
+        let source = "fn main() { { 1; 2; }; 42 }";
+        assert_eq!(body_len(source), body_len("fn main() { 1; 2; 42 }"));
+        run_and_check_result_42(source);
+    }
+
+    #[test]
+    fn test_direct_call_loads_function() {
+        let source = "\
+            fn answer() { 42 }
+            fn main() { answer() }
+        ";
+
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+
+        let (_, main) = module
+            .functions()
+            .find(|(name, _)| *name == "main")
+            .unwrap();
+        let instructions = main.instructions().collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(matches!(instructions[0], Instruction::LoadFunction(_)));
+        assert!(module.disassemble().contains("LOAD FN #"));
+
+        run_and_check_result_42(source);
+
+        // local variables and function expressions are not statically known
+        let source = "fn main() { let f = fn() { 42 }; f() }";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        assert!(!module.disassemble().contains("LOAD FN #"));
+
+        // breaking out of a call's arguments doesn't leave the callee behind for
+        // the next call at the same stack position
+        let source = "\
+            fn zero() { 0 }
+            fn main() { let f = fn() { 42 }; loop { zero(break 0) }; f() }
+        ";
+        run_and_check_result_42(source);
+    }
+    #[test]
+    fn test_switch_jump_table() {
+        let bytecode = compile(include_str!("programs/switch.spr"));
+        let module = parse_bytecode(&bytecode).unwrap();
+        let disassembly = module.disassemble();
+        assert!(disassembly.contains("SWITCH 10"));
+        // January to December, and 0 leading to the else branch
+        assert!(disassembly.contains("SWITCH 13"));
+
+        // too few or sparse cases are compiled to comparisons
+        let source = "\
+            fn few(x) { if x == 0 { 1 } else if x == 1 { 2 } else { 3 } }
+            fn sparse(x) { if x == 0 { 1 } else if x == 10 { 2 } else if x == 20 { 3 } else if x == 30 { 4 } }
+            fn main() { few(1) + sparse(30) }
+        ";
+        let bytecode = compile(source);
+        let module = parse_bytecode(&bytecode).unwrap();
+        assert!(!module.disassemble().contains("SWITCH"));
+        run_and_check_result_decimal(source, 6);
+    }
 }